@@ -0,0 +1,123 @@
+//! Throughput of the book reads, run the way the handlers ran them on the blocking pool before
+//! the async pool, and the way they run them now.
+//!
+//! Both pools get [`POOL_SIZE`] connections, so the two ways of reading have the same number
+//! of connections to work with. The reads need a database with the migrations and the test
+//! seed, `DATABASE_URL` is read from `.env` or the environment. Run with
+//! `cargo bench --bench db`.
+
+use std::collections::HashMap;
+
+use actix_web::web;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use diesel::prelude::*;
+use dotenv::dotenv;
+use futures_util::future::join_all;
+use tokio::runtime::Runtime;
+
+use lib_api::books::{Books, BOOKS_SORTABLE};
+use lib_api::config::{self, Config};
+use lib_api::db;
+use lib_api::error_handler::CustomError;
+use lib_api::schema::books;
+use lib_api::utils::pagination::Pagination;
+
+/// Requests in flight at once on the worker.
+const CONCURRENCY: usize = 64;
+
+/// Connections of the blocking pool and of the async pool of the worker alike.
+const POOL_SIZE: u32 = 10;
+
+/// Book of the test seed.
+const BOOK_ID: i32 = 1;
+
+fn blocking_find(id: i32) -> Result<Books, CustomError> {
+    let mut conn = db::connection()?;
+    let book = books::table
+        .filter(books::id.eq(id))
+        .filter(books::deleted_at.is_null())
+        .first(&mut conn)?;
+    Ok(book)
+}
+
+fn blocking_page(pagination: &Pagination) -> Result<(Vec<Books>, i64), CustomError> {
+    let mut conn = db::connection()?;
+    let query = || books::table.filter(books::deleted_at.is_null());
+    let total = query().count().get_result::<i64>(&mut conn)?;
+    let books = query()
+        .order(books::id.asc())
+        .limit(pagination.limit)
+        .offset(pagination.offset)
+        .load::<Books>(&mut conn)?;
+    Ok((books, total))
+}
+
+fn find(c: &mut Criterion, runtime: &Runtime) {
+    let mut group = c.benchmark_group("find_book");
+    group.throughput(Throughput::Elements(CONCURRENCY as u64));
+
+    group.bench_function("blocking", |b| {
+        b.to_async(runtime).iter(|| async {
+            let requests = (0..CONCURRENCY).map(|_| web::block(|| blocking_find(BOOK_ID)));
+            for book in join_all(requests).await {
+                book.unwrap().unwrap();
+            }
+        })
+    });
+    group.bench_function("async", |b| {
+        b.to_async(runtime).iter(|| async {
+            let requests = (0..CONCURRENCY).map(|_| Books::find(BOOK_ID, false));
+            for book in join_all(requests).await {
+                book.unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn find_all(c: &mut Criterion, runtime: &Runtime) {
+    let pagination = Pagination::from_params(&mut HashMap::new(), &BOOKS_SORTABLE).unwrap();
+    let mut group = c.benchmark_group("find_all_books");
+    group.throughput(Throughput::Elements(CONCURRENCY as u64));
+
+    group.bench_function("blocking", |b| {
+        b.to_async(runtime).iter(|| async {
+            let requests = (0..CONCURRENCY).map(|_| {
+                let pagination = pagination.clone();
+                web::block(move || blocking_page(&pagination))
+            });
+            for page in join_all(requests).await {
+                page.unwrap().unwrap();
+            }
+        })
+    });
+    group.bench_function("async", |b| {
+        b.to_async(runtime).iter(|| async {
+            let requests = (0..CONCURRENCY).map(|_| Books::find_all(&pagination, false));
+            for page in join_all(requests).await {
+                page.unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn reads(c: &mut Criterion) {
+    dotenv().ok();
+    let mut settings = Config::load(&[]).unwrap_or_else(|e| panic!("{e}"));
+    settings.database.pool_size = POOL_SIZE;
+    settings.database.async_pool_size = POOL_SIZE;
+    config::init(settings);
+
+    // An actix worker is a current thread runtime, and the async connections it opens live
+    // as long as it does, so both groups share one.
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build the runtime");
+    find(c, &runtime);
+    find_all(c, &runtime);
+}
+
+criterion_group!(benches, reads);
+criterion_main!(benches);
@@ -2,6 +2,6 @@
 export PGPASSWORD=postgres
 set -e
 
-diesel migration run
+cargo run -- migrate up
 
 cargo run
\ No newline at end of file
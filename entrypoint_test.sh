@@ -2,11 +2,14 @@
 export PGPASSWORD=postgres
 set -e
 
-diesel migration run
+cargo run -- migrate up
 
 psql -v ON_ERROR_STOP=1 -U postgres -h db_test --dbname "tests" <<-EOSQL
 insert into members (id, first_name, last_name, email, address, age) values (1,'username', 'user last_name','user@gg.com', 'elm street', 38);
-insert into books (id, title, isbn, copies_available, copies) values (1,'title_1', '1234',4, 4);
+insert into books (id, title, isbn) values (1,'title_1', '1234');
+insert into items (barcode, book_id) select '1234-' || n, 1 from generate_series(1, 4) as n;
+select setval('members_id_seq', (select max(id) from members));
+select setval('books_id_seq', (select max(id) from books));
 EOSQL
 
 cargo test
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS loans
+(
+    id SERIAL PRIMARY KEY,
+    member_id INT NOT NULL REFERENCES members (id),
+    book_id INT NOT NULL REFERENCES books (id),
+    loaned_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    due_at TIMESTAMP NOT NULL,
+    returned_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS loans_member_id_idx ON loans (member_id);
+CREATE INDEX IF NOT EXISTS loans_book_id_idx ON loans (book_id);
@@ -0,0 +1 @@
+DROP TABLE holds;
\ No newline at end of file
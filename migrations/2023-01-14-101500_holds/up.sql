@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS holds
+(
+    id SERIAL PRIMARY KEY,
+    member_id INT NOT NULL REFERENCES members (id),
+    book_id INT NOT NULL REFERENCES books (id),
+    status VARCHAR NOT NULL DEFAULT 'waiting'
+        CHECK (status IN ('waiting', 'ready', 'fulfilled', 'cancelled', 'expired')),
+    placed_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    ready_at TIMESTAMP,
+    expires_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS holds_book_id_status_idx ON holds (book_id, status, placed_at);
+CREATE UNIQUE INDEX IF NOT EXISTS holds_active_member_book_idx ON holds (member_id, book_id)
+    WHERE status IN ('waiting', 'ready');
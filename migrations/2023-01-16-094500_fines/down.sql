@@ -0,0 +1,2 @@
+DROP TABLE payments;
+DROP TABLE fines;
\ No newline at end of file
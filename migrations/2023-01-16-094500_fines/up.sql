@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS fines
+(
+    id SERIAL PRIMARY KEY,
+    member_id INT NOT NULL REFERENCES members (id),
+    loan_id INT UNIQUE REFERENCES loans (id),
+    amount_cents INT NOT NULL CHECK (amount_cents > 0),
+    reason VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    waived_at TIMESTAMP,
+    waive_reason VARCHAR
+);
+
+CREATE TABLE IF NOT EXISTS payments
+(
+    id SERIAL PRIMARY KEY,
+    member_id INT NOT NULL REFERENCES members (id),
+    amount_cents INT NOT NULL CHECK (amount_cents > 0),
+    note VARCHAR,
+    paid_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS fines_member_id_idx ON fines (member_id);
+CREATE INDEX IF NOT EXISTS payments_member_id_idx ON payments (member_id);
@@ -0,0 +1,6 @@
+-- Users created before roles existed had access to everything.
+ALTER TABLE users
+    ADD COLUMN role VARCHAR NOT NULL DEFAULT 'admin'
+        CHECK (role IN ('admin', 'librarian', 'kiosk'));
+
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'librarian';
@@ -0,0 +1,2 @@
+DROP INDEX books_search_idx;
+DROP FUNCTION books_search_vector(VARCHAR, VARCHAR);
\ No newline at end of file
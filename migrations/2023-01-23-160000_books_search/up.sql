@@ -0,0 +1,7 @@
+-- Words of the title, stemmed in english, rank above the digits of the ISBN.
+CREATE OR REPLACE FUNCTION books_search_vector(title VARCHAR, isbn VARCHAR) RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english', coalesce(title, '')), 'A')
+        || setweight(to_tsvector('simple', regexp_replace(coalesce(isbn, ''), '[^0-9Xx]', '', 'g')), 'B');
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE INDEX IF NOT EXISTS books_search_idx ON books USING GIN (books_search_vector(title, isbn));
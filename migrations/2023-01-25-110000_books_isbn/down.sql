@@ -0,0 +1 @@
+DROP INDEX IF EXISTS books_isbn_key;
\ No newline at end of file
@@ -0,0 +1,58 @@
+-- Canonical ISBN-13 of an ISBN-10 or ISBN-13 written with or without hyphens and spaces, NULL when the
+-- value is not a valid ISBN. Only needed to clean up the rows saved before the API normalized them.
+CREATE FUNCTION pg_temp.isbn13(value VARCHAR) RETURNS VARCHAR AS $$
+DECLARE
+    isbn VARCHAR := upper(regexp_replace(value, '[\s-]', '', 'g'));
+    total INT := 0;
+BEGIN
+    IF isbn ~ '^[0-9]{9}[0-9X]$' THEN
+        FOR i IN 1..10 LOOP
+            total := total + (11 - i) * CASE WHEN substr(isbn, i, 1) = 'X' THEN 10 ELSE substr(isbn, i, 1)::INT END;
+        END LOOP;
+        IF total % 11 <> 0 THEN
+            RETURN NULL;
+        END IF;
+        isbn := '978' || left(isbn, 9);
+    ELSIF isbn ~ '^[0-9]{13}$' THEN
+        FOR i IN 1..13 LOOP
+            total := total + substr(isbn, i, 1)::INT * CASE WHEN i % 2 = 0 THEN 3 ELSE 1 END;
+        END LOOP;
+        IF total % 10 <> 0 THEN
+            RETURN NULL;
+        END IF;
+        RETURN isbn;
+    ELSE
+        RETURN NULL;
+    END IF;
+
+    total := 0;
+    FOR i IN 1..12 LOOP
+        total := total + substr(isbn, i, 1)::INT * CASE WHEN i % 2 = 0 THEN 3 ELSE 1 END;
+    END LOOP;
+    RETURN isbn || ((10 - total % 10) % 10)::TEXT;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+UPDATE books SET isbn = pg_temp.isbn13(isbn)
+WHERE pg_temp.isbn13(isbn) IS NOT NULL AND isbn <> pg_temp.isbn13(isbn);
+
+-- Books saved twice, in ISBN-10 and ISBN-13 form, have to be merged by hand before the index can be built.
+DO $$
+DECLARE
+    duplicates TEXT;
+BEGIN
+    SELECT string_agg(isbn || ' (books ' || ids || ')', ', ') INTO duplicates
+    FROM (
+        SELECT isbn, string_agg(id::TEXT, ', ' ORDER BY id) AS ids
+        FROM books
+        GROUP BY isbn
+        HAVING count(*) > 1
+    ) AS duplicated;
+
+    IF duplicates IS NOT NULL THEN
+        RAISE EXCEPTION 'merge the books sharing an ISBN first: %', duplicates;
+    END IF;
+END;
+$$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS books_isbn_key ON books (isbn);
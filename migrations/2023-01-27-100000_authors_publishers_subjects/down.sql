@@ -0,0 +1,6 @@
+DROP TABLE book_subjects;
+DROP TABLE book_publishers;
+DROP TABLE book_authors;
+DROP TABLE subjects;
+DROP TABLE publishers;
+DROP TABLE authors;
\ No newline at end of file
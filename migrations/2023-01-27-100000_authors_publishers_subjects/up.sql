@@ -0,0 +1,43 @@
+CREATE TABLE IF NOT EXISTS authors
+(
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS publishers
+(
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS subjects
+(
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS book_authors
+(
+    book_id INT NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    author_id INT NOT NULL REFERENCES authors (id) ON DELETE CASCADE,
+    PRIMARY KEY (book_id, author_id)
+);
+
+CREATE TABLE IF NOT EXISTS book_publishers
+(
+    book_id INT NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    publisher_id INT NOT NULL REFERENCES publishers (id) ON DELETE CASCADE,
+    PRIMARY KEY (book_id, publisher_id)
+);
+
+CREATE TABLE IF NOT EXISTS book_subjects
+(
+    book_id INT NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    subject_id INT NOT NULL REFERENCES subjects (id) ON DELETE CASCADE,
+    PRIMARY KEY (book_id, subject_id)
+);
+
+-- The primary keys already index the book side, the filters look books up from the other side.
+CREATE INDEX IF NOT EXISTS book_authors_author_id_idx ON book_authors (author_id);
+CREATE INDEX IF NOT EXISTS book_publishers_publisher_id_idx ON book_publishers (publisher_id);
+CREATE INDEX IF NOT EXISTS book_subjects_subject_id_idx ON book_subjects (subject_id);
@@ -0,0 +1,7 @@
+ALTER TABLE books ALTER COLUMN copies_available DROP DEFAULT;
+ALTER TABLE books ALTER COLUMN copies DROP DEFAULT;
+DROP TRIGGER items_count ON items;
+DROP FUNCTION items_count_trigger();
+DROP FUNCTION books_count_items(INT);
+ALTER TABLE loans DROP COLUMN item_id;
+DROP TABLE items;
\ No newline at end of file
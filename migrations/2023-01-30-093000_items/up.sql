@@ -0,0 +1,69 @@
+CREATE TABLE IF NOT EXISTS items
+(
+    id SERIAL PRIMARY KEY,
+    barcode VARCHAR NOT NULL UNIQUE,
+    book_id INT NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    status VARCHAR NOT NULL DEFAULT 'available'
+        CHECK (status IN ('available', 'on_loan', 'on_hold', 'damaged', 'lost', 'withdrawn')),
+    condition VARCHAR NOT NULL DEFAULT 'good'
+        CHECK (condition IN ('new', 'good', 'fair', 'poor')),
+    shelf_location VARCHAR,
+    acquired_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS items_book_id_status_idx ON items (book_id, status);
+
+ALTER TABLE loans ADD COLUMN item_id INT REFERENCES items (id);
+
+-- copies counts the items the library still owns and copies_available the ones on the shelf. They are
+-- kept up to date from the items, the API no longer writes them.
+CREATE OR REPLACE FUNCTION books_count_items(book INT) RETURNS VOID AS $$
+    UPDATE books SET
+        copies = (SELECT count(*) FROM items WHERE book_id = book AND status NOT IN ('lost', 'withdrawn')),
+        copies_available = (SELECT count(*) FROM items WHERE book_id = book AND status = 'available')
+    WHERE id = book;
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION items_count_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        PERFORM books_count_items(OLD.book_id);
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        PERFORM books_count_items(NEW.book_id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER items_count AFTER INSERT OR UPDATE OR DELETE ON items
+    FOR EACH ROW EXECUTE FUNCTION items_count_trigger();
+
+ALTER TABLE books ALTER COLUMN copies SET DEFAULT 0;
+ALTER TABLE books ALTER COLUMN copies_available SET DEFAULT 0;
+
+-- One item per existing copy. The copies missing from the shelf are set aside for the ready holds first and
+-- lent to the open loans after that.
+INSERT INTO items (barcode, book_id, status)
+SELECT format('%s-%s', books.id, n), books.id, CASE
+    WHEN n <= books.copies_available THEN 'available'
+    WHEN n <= books.copies_available + (
+        SELECT count(*) FROM holds WHERE holds.book_id = books.id AND holds.status = 'ready'
+    ) THEN 'on_hold'
+    ELSE 'on_loan'
+END
+FROM books, generate_series(1, books.copies) AS n;
+
+WITH lent AS (
+    SELECT id, book_id, row_number() OVER (PARTITION BY book_id ORDER BY id) AS n
+    FROM items
+    WHERE status = 'on_loan'
+), open_loans AS (
+    SELECT id, book_id, row_number() OVER (PARTITION BY book_id ORDER BY id) AS n
+    FROM loans
+    WHERE returned_at IS NULL
+)
+UPDATE loans SET item_id = lent.id
+FROM open_loans
+JOIN lent ON lent.book_id = open_loans.book_id AND lent.n = open_loans.n
+WHERE loans.id = open_loans.id;
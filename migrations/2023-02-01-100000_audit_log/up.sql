@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS audit_log
+(
+    id SERIAL PRIMARY KEY,
+    -- Taken from the token of the request, the entries outlive the user accounts so there is no
+    -- foreign key and the username is kept as well.
+    actor_id INT NOT NULL,
+    actor VARCHAR NOT NULL,
+    action VARCHAR NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    entity VARCHAR NOT NULL,
+    entity_id INT NOT NULL,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_entity_idx ON audit_log (entity, entity_id);
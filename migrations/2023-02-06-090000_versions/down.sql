@@ -0,0 +1,5 @@
+DROP TRIGGER IF EXISTS set_updated_at ON members;
+DROP TRIGGER IF EXISTS set_updated_at ON books;
+
+ALTER TABLE members DROP COLUMN IF EXISTS updated_at, DROP COLUMN IF EXISTS version;
+ALTER TABLE books DROP COLUMN IF EXISTS updated_at, DROP COLUMN IF EXISTS version;
\ No newline at end of file
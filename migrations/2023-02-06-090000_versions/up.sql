@@ -0,0 +1,11 @@
+-- version is bumped by every change made through the API and sent as the ETag of the record,
+-- updated_at follows any change of the row, copies counted by the items trigger included.
+ALTER TABLE books
+    ADD COLUMN IF NOT EXISTS version INT NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP NOT NULL DEFAULT NOW();
+ALTER TABLE members
+    ADD COLUMN IF NOT EXISTS version INT NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+SELECT diesel_manage_updated_at('books');
+SELECT diesel_manage_updated_at('members');
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::auth::Claims;
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::schema::audit_log;
+use crate::utils::check::{self, filter_int, filter_text};
+use crate::utils::pagination::Pagination;
+
+pub mod action {
+    pub const CREATE: &str = "create";
+    pub const UPDATE: &str = "update";
+    pub const DELETE: &str = "delete";
+    pub const RESTORE: &str = "restore";
+}
+
+/// Fields accepted by the `sort` param of the audit log.
+pub const AUDIT_SORTABLE: [&str; 6] =
+    ["id", "actor", "action", "entity", "entity_id", "created_at"];
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct Audit<'a> {
+    pub actor_id: i32,
+    pub actor: &'a str,
+    pub action: &'a str,
+    pub entity: &'a str,
+    pub entity_id: i32,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, ToSchema)]
+#[diesel(table_name = audit_log)]
+pub struct Audits {
+    pub id: i32,
+    /// Id of the user that made the change.
+    pub actor_id: i32,
+    pub actor: String,
+    /// create, update, delete or restore.
+    pub action: String,
+    /// Table of the changed record, books or members.
+    pub entity: String,
+    pub entity_id: i32,
+    /// Values before the change, only the changed fields on an update.
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<Value>,
+    /// Values after the change, only the changed fields on an update.
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
+
+impl Audits {
+    /// Page of the entries matching `params`, newest first unless sorted otherwise, along with
+    /// how many entries match in total.
+    pub async fn get(
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+    ) -> Result<(Vec<Self>, i64), CustomError> {
+        let mut conn = db::async_connection().await?;
+        let total = Audits::filtered(params)?
+            .count()
+            .get_result::<i64>(&mut conn)
+            .await?;
+
+        let mut query = Audits::filtered(params)?;
+        for sort in &pagination.sort {
+            query = match (sort.field.as_str(), sort.descending) {
+                ("id", false) => query.then_order_by(audit_log::id.asc()),
+                ("id", true) => query.then_order_by(audit_log::id.desc()),
+                ("actor", false) => query.then_order_by(audit_log::actor.asc()),
+                ("actor", true) => query.then_order_by(audit_log::actor.desc()),
+                ("action", false) => query.then_order_by(audit_log::action.asc()),
+                ("action", true) => query.then_order_by(audit_log::action.desc()),
+                ("entity", false) => query.then_order_by(audit_log::entity.asc()),
+                ("entity", true) => query.then_order_by(audit_log::entity.desc()),
+                ("entity_id", false) => query.then_order_by(audit_log::entity_id.asc()),
+                ("entity_id", true) => query.then_order_by(audit_log::entity_id.desc()),
+                ("created_at", false) => query.then_order_by(audit_log::created_at.asc()),
+                ("created_at", true) => query.then_order_by(audit_log::created_at.desc()),
+                _ => query,
+            };
+        }
+
+        let entries = query
+            .then_order_by(audit_log::id.desc())
+            .limit(pagination.limit)
+            .offset(pagination.offset)
+            .load::<Audits>(&mut conn)
+            .await?;
+        Ok((entries, total))
+    }
+
+    fn filtered(
+        params: &HashMap<String, String>,
+    ) -> Result<audit_log::BoxedQuery<'_, Pg>, CustomError> {
+        let mut query = audit_log::table.into_boxed();
+
+        for (key, value) in params {
+            let (field, operator) = check::parse_filter(key)?;
+            query = match field {
+                "id" => filter_int!(
+                    query,
+                    audit_log::entity_id,
+                    operator,
+                    check::validate_int(value)?
+                ),
+                "actor_id" => filter_int!(
+                    query,
+                    audit_log::actor_id,
+                    operator,
+                    check::validate_int(value)?
+                ),
+                "entity" => filter_text!(query, audit_log::entity, operator, value),
+                "action" => filter_text!(query, audit_log::action, operator, value),
+                "actor" => filter_text!(query, audit_log::actor, operator, value),
+                _ => {
+                    return Err(CustomError::new(
+                        400,
+                        format!("the parameter '{key}' is incorrect"),
+                    ))
+                }
+            };
+        }
+
+        Ok(query)
+    }
+}
+
+impl<'a> Audit<'a> {
+    /// Save who did `action` on record `entity_id` of `entity`. Call it with the connection of
+    /// the transaction making the change, so the entry is kept only if the change is.
+    ///
+    /// Creates keep the whole record in `after` and deletes in `before`. Updates keep only the
+    /// fields that changed, and are not saved when nothing did.
+    pub async fn record<T: Serialize>(
+        conn: &mut AsyncPgConnection,
+        actor: &'a Claims,
+        action: &'a str,
+        entity: &'a str,
+        entity_id: i32,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Result<(), CustomError> {
+        let (mut before, mut after) = (to_value(before)?, to_value(after)?);
+        if let (Some(old), Some(new)) = (&before, &after) {
+            let (old, new) = diff(old, new);
+            if old.is_empty() && new.is_empty() {
+                return Ok(());
+            }
+            (before, after) = (Some(Value::Object(old)), Some(Value::Object(new)));
+        }
+
+        let entry = Audit {
+            actor_id: actor.sub,
+            actor: &actor.username,
+            action,
+            entity,
+            entity_id,
+            before,
+            after,
+        };
+        diesel::insert_into(audit_log::table)
+            .values(entry)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+fn to_value<T: Serialize>(record: Option<&T>) -> Result<Option<Value>, CustomError> {
+    record
+        .map(serde_json::to_value)
+        .transpose()
+        .map_err(|e| CustomError::new(500, format!("Failed serializing audit entry: {e}")))
+}
+
+/// Fields of two JSON objects whose values differ, as they are in `before` and in `after`.
+///
+/// # Examples
+///
+/// ```
+/// use lib_api::audit::diff;
+/// use serde_json::json;
+///
+/// let before = json!({"id": 1, "email": "ada@example.com", "age": 36});
+/// let after = json!({"id": 1, "email": "ada@example.org", "age": 36});
+/// let (before, after) = diff(&before, &after);
+/// assert_eq!(json!(before), json!({"email": "ada@example.com"}));
+/// assert_eq!(json!(after), json!({"email": "ada@example.org"}));
+/// ```
+pub fn diff(before: &Value, after: &Value) -> (Map<String, Value>, Map<String, Value>) {
+    let empty = Map::new();
+    let old = before.as_object().unwrap_or(&empty);
+    let new = after.as_object().unwrap_or(&empty);
+
+    let mut changed_before = Map::new();
+    let mut changed_after = Map::new();
+    for key in old
+        .keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)))
+    {
+        let (old_value, new_value) = (old.get(key), new.get(key));
+        if old_value != new_value {
+            if let Some(value) = old_value {
+                changed_before.insert(key.clone(), value.clone());
+            }
+            if let Some(value) = new_value {
+                changed_after.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    (changed_before, changed_after)
+}
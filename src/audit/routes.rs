@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+
+use crate::audit::{Audits, AUDIT_SORTABLE};
+use crate::auth::RequireRole;
+use crate::error_handler::CustomError;
+use crate::utils::check;
+use crate::utils::pagination::{Page, Pagination};
+use crate::utils::response;
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    responses(
+        (status = 200, description = "Get the changes made to books and members, newest first", body = inline(response::AuditResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("entity" = Option<String>, Query, description = "books or members, also as entity__ne"),
+        ("id" = Option<i32>, Query, description = "Id of the changed record, also as id__ne, __gt, __gte, __lt or __lte"),
+        ("action" = Option<String>, Query, description = "create, update, delete or restore, also as action__ne"),
+        ("actor" = Option<String>, Query, description = "Username that made the change, also as actor__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("actor_id" = Option<i32>, Query, description = "User id that made the change, also as actor_id__ne, __gt, __gte, __lt or __lte"),
+        ("limit" = Option<i64>, Query, description = "Max number of entries per page, 50 by default and 500 at most"),
+        ("offset" = Option<i64>, Query, description = "Number of entries to skip"),
+        ("sort" = Option<String>, Query, description = "Comma separated fields to sort by, prefix a field with '-' for descending order example (entity,-created_at)"),
+    )
+)]
+#[get("/audit", wrap = "RequireRole::admin()")]
+async fn find_all(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let pagination = Pagination::from_params(&mut params, &AUDIT_SORTABLE)?;
+    check::validate_filters(&params, &["id", "actor_id"], &["entity", "action", "actor"])?;
+
+    let (entries, total) = Audits::get(&params, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(Page::new(entries, total, &pagination, req.path(), &params)))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(find_all);
+}
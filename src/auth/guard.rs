@@ -0,0 +1,112 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpMessage, ResponseError};
+use futures_util::future::LocalBoxFuture;
+
+use crate::auth::{authenticate, Claims, Role};
+use crate::error_handler::CustomError;
+
+/// Let a route through only for callers whose role is in `roles`.
+///
+/// Attach it to a route with the `wrap` argument of the actix route macros, for example
+/// `#[put("/books/{id}", wrap = "RequireRole::staff()")]`. Callers without a valid token get a
+/// 401 and callers with any other role a 403.
+#[derive(Clone, Copy)]
+pub struct RequireRole {
+    roles: &'static [Role],
+}
+
+impl RequireRole {
+    pub fn new(roles: &'static [Role]) -> Self {
+        RequireRole { roles }
+    }
+
+    pub fn admin() -> Self {
+        RequireRole::new(&[Role::Admin])
+    }
+
+    /// Admins and librarians.
+    pub fn staff() -> Self {
+        RequireRole::new(&[Role::Admin, Role::Librarian])
+    }
+
+    /// Any authenticated caller, kiosks included.
+    pub fn any() -> Self {
+        RequireRole::new(&[Role::Admin, Role::Librarian, Role::Kiosk])
+    }
+
+    fn check(&self, claims: &Claims) -> Result<(), CustomError> {
+        match self.roles.contains(&claims.role) {
+            true => Ok(()),
+            false => Err(CustomError::new(
+                403,
+                format!(
+                    "Role '{}' is not allowed to access this resource",
+                    claims.role
+                ),
+            )),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireRoleMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleMiddleware {
+            service: Rc::new(service),
+            guard: *self,
+        }))
+    }
+}
+
+pub struct RequireRoleMiddleware<S> {
+    service: Rc<S>,
+    guard: RequireRole,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireRoleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let guard = self.guard;
+
+        Box::pin(async move {
+            let claims = match req.extensions().get::<Claims>() {
+                Some(claims) => Ok(claims.clone()),
+                None => authenticate(req.request()),
+            };
+
+            match claims.and_then(|claims| guard.check(&claims).map(|_| claims)) {
+                Ok(claims) => {
+                    req.extensions_mut().insert(claims);
+                    service.call(req).await.map(|res| res.map_into_left_body())
+                }
+                Err(err) => {
+                    let res = err.error_response();
+                    Ok(req.into_response(res).map_into_right_body())
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,90 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpMessage, ResponseError};
+use futures_util::future::LocalBoxFuture;
+
+use crate::auth::authenticate;
+
+/// Paths reachable without a token.
+const PUBLIC_PATHS: [&str; 5] = [
+    "/auth/login",
+    "/health",
+    "/health/live",
+    "/health/ready",
+    "/metrics",
+];
+
+/// Prefixes of the Swagger UI and its OpenAPI document, reachable without a token.
+const PUBLIC_PREFIXES: [&str; 2] = ["/swagger-ui/", "/api-doc/"];
+
+fn is_public(path: &str) -> bool {
+    PUBLIC_PATHS.contains(&path)
+        || PUBLIC_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+}
+
+/// Reject requests without a valid bearer token with a 401, except for the login, the health
+/// checks, the metrics and the Swagger UI.
+///
+/// The validated [`Claims`](crate::auth::Claims) are stored in the request extensions for the
+/// handlers and guards that run afterwards.
+pub struct Authentication;
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            if is_public(req.path()) {
+                return service.call(req).await.map(|res| res.map_into_left_body());
+            }
+
+            match authenticate(req.request()) {
+                Ok(claims) => {
+                    req.extensions_mut().insert(claims);
+                    service.call(req).await.map(|res| res.map_into_left_body())
+                }
+                Err(err) => {
+                    let res = err.error_response();
+                    Ok(req.into_response(res).map_into_right_body())
+                }
+            }
+        })
+    }
+}
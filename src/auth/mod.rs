@@ -0,0 +1,9 @@
+pub use guard::*;
+pub use middleware::*;
+pub use model::*;
+pub use routes::*;
+
+mod guard;
+mod middleware;
+mod model;
+mod routes;
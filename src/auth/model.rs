@@ -0,0 +1,307 @@
+use std::fmt;
+use std::str::FromStr;
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::future::{ready, Ready};
+use utoipa::ToSchema;
+
+use crate::config::{self, AuthConfig};
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::repository::UserRepository;
+use crate::schema::users;
+use crate::telemetry;
+
+/// What a staff user is allowed to do.
+///
+/// # Examples
+///
+/// ```
+/// use lib_api::auth::Role;
+///
+/// assert_eq!(Role::Kiosk, "kiosk".parse::<Role>().unwrap());
+/// match "guest".parse::<Role>() {
+///     Err(e) if e.to_string() == "unknown role 'guest', use admin, librarian or kiosk" => (),
+///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+///     Ok(_) => panic!("Returned an Ok variant!"),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Full access, including staff accounts.
+    Admin,
+    /// Day to day desk work on books, members, loans, holds and fines.
+    Librarian,
+    /// Self service terminals that can only search the catalogue.
+    Kiosk,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Librarian => "librarian",
+            Role::Kiosk => "kiosk",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = CustomError;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "admin" => Ok(Role::Admin),
+            "librarian" => Ok(Role::Librarian),
+            "kiosk" => Ok(Role::Kiosk),
+            _ => Err(CustomError::new(
+                400,
+                format!("unknown role '{role}', use admin, librarian or kiosk"),
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct User {
+    pub username: String,
+    pub password: String,
+    /// Defaults to `librarian`. Ignored on login.
+    pub role: Option<Role>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, ToSchema)]
+#[diesel(table_name = users)]
+pub struct Users {
+    pub id: i32,
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub created_at: NaiveDateTime,
+    pub role: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Token {
+    pub token: String,
+    pub token_type: String,
+    /// Seconds until the token expires.
+    pub expires_in: i64,
+}
+
+/// Claims carried by the bearer tokens issued on login.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// Id of the authenticated user.
+    pub sub: i32,
+    pub username: String,
+    pub role: Role,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+impl Users {
+    pub async fn create(repository: &dyn UserRepository, user: User) -> Result<Self, CustomError> {
+        if user.username.trim().is_empty() || user.password.is_empty() {
+            return Err(CustomError::new(
+                400,
+                "username and password are required".to_string(),
+            ));
+        }
+
+        let password = user.password;
+        let password_hash = telemetry::block(move || hash_password(&password))
+            .await
+            .map_err(|e| CustomError::new(500, e.to_string()))??;
+        let role = user.role.unwrap_or(Role::Librarian);
+        repository
+            .insert(user.username.trim(), &password_hash, role)
+            .await
+    }
+
+    /// Create the first admin account from the `admin_username` and `admin_password` of `auth`
+    /// while there is no user yet, so a fresh deploy has someone able to log in.
+    pub async fn bootstrap(
+        repository: &dyn UserRepository,
+        auth: &AuthConfig,
+    ) -> Result<Option<Self>, CustomError> {
+        let (Some(username), Some(password)) = (&auth.admin_username, &auth.admin_password) else {
+            return Ok(None);
+        };
+
+        if repository.count().await? > 0 {
+            return Ok(None);
+        }
+        let user = User {
+            username: username.clone(),
+            password: password.clone(),
+            role: Some(Role::Admin),
+        };
+        Users::create(repository, user).await.map(Some)
+    }
+
+    /// Check `user` credentials and issue a signed token for them.
+    pub async fn login(repository: &dyn UserRepository, user: User) -> Result<Token, CustomError> {
+        let found = repository.find_by_username(&user.username).await?;
+        let Some(found) = found else {
+            return Err(invalid_credentials());
+        };
+
+        let password = user.password;
+        let (verified, found) =
+            telemetry::block(move || (verify_password(&password, &found.password_hash), found))
+                .await
+                .map_err(|e| CustomError::new(500, e.to_string()))?;
+        match verified {
+            true => create_token(&found),
+            false => Err(invalid_credentials()),
+        }
+    }
+
+    /// The user named `username` in Postgres.
+    pub fn find_by_username(username: &str) -> Result<Option<Self>, CustomError> {
+        let mut conn = db::connection()?;
+        let user = users::table
+            .filter(users::username.eq(username))
+            .first::<Users>(&mut conn)
+            .optional()?;
+        Ok(user)
+    }
+
+    /// How many users Postgres holds.
+    pub fn count() -> Result<i64, CustomError> {
+        let mut conn = db::connection()?;
+        let users = users::table.count().get_result(&mut conn)?;
+        Ok(users)
+    }
+
+    /// Save a user whose password is already hashed in Postgres.
+    pub fn insert(username: &str, password_hash: &str, role: Role) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        let user = diesel::insert_into(users::table)
+            .values((
+                users::username.eq(username),
+                users::password_hash.eq(password_hash),
+                users::role.eq(role.as_str()),
+            ))
+            .get_result(&mut conn)?;
+        Ok(user)
+    }
+}
+
+fn invalid_credentials() -> CustomError {
+    CustomError::new(401, "Invalid username or password".to_string())
+}
+
+fn hash_password(password: &str) -> Result<String, CustomError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| CustomError::new(500, format!("Failed hashing password: {e}")))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn secret() -> &'static str {
+    &config::get().auth.jwt_secret
+}
+
+fn expiration() -> Duration {
+    Duration::minutes(config::get().auth.jwt_expiration_minutes)
+}
+
+/// Sign a bearer token for `user` with the configured secret.
+pub fn create_token(user: &Users) -> Result<Token, CustomError> {
+    let now = Utc::now();
+    let expires_in = expiration();
+    let claims = Claims {
+        sub: user.id,
+        username: user.username.clone(),
+        role: user.role.parse()?,
+        iat: now.timestamp(),
+        exp: (now + expires_in).timestamp(),
+    };
+
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret().as_bytes()),
+    )
+    .map_err(|e| CustomError::new(500, format!("Failed signing token: {e}")))?;
+
+    Ok(Token {
+        token,
+        token_type: "Bearer".to_string(),
+        expires_in: expires_in.num_seconds(),
+    })
+}
+
+/// Validate the signature and expiration of `token`.
+pub fn decode_token(token: &str) -> Result<Claims, CustomError> {
+    decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret().as_bytes()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|e| CustomError::new(401, format!("Invalid token: {e}")))
+}
+
+/// Read and validate the bearer token of the `Authorization` header.
+pub fn authenticate(req: &HttpRequest) -> Result<Claims, CustomError> {
+    let header = req
+        .headers()
+        .get("Authorization")
+        .ok_or_else(|| CustomError::new(401, "Missing Authorization header".to_string()))?;
+
+    let token = header
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| {
+            CustomError::new(
+                401,
+                "Authorization header must be 'Bearer <token>'".to_string(),
+            )
+        })?;
+
+    decode_token(token.trim())
+}
+
+/// Handlers take `Claims` to know who is calling. The claims validated by
+/// [`Authentication`](crate::auth::Authentication) are reused when present.
+impl FromRequest for Claims {
+    type Error = CustomError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let claims = match req.extensions().get::<Claims>() {
+            Some(claims) => Ok(claims.clone()),
+            None => authenticate(req),
+        };
+        ready(claims)
+    }
+}
@@ -0,0 +1,50 @@
+use actix_web::{post, web, HttpResponse};
+
+use crate::auth::{RequireRole, Token, User, Users};
+use crate::error_handler::CustomError;
+use crate::repository::UserRepository;
+use crate::utils::response;
+
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    request_body = User,
+    security(()),
+    responses(
+        (status = 200, description = "Log in and get a bearer token", body = inline(Token)),
+        (status = 401, description = "Invalid credentials", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/auth/login")]
+async fn login(
+    repository: web::Data<dyn UserRepository>,
+    user: web::Json<User>,
+) -> Result<HttpResponse, CustomError> {
+    let token = Users::login(repository.get_ref(), user.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(token))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users",
+    request_body = User,
+    responses(
+        (status = 200, description = "Create a staff user", body = inline(Users)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 401, description = "Unauthorized", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Username already taken", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/users", wrap = "RequireRole::admin()")]
+async fn create(
+    repository: web::Data<dyn UserRepository>,
+    user: web::Json<User>,
+) -> Result<HttpResponse, CustomError> {
+    let user = Users::create(repository.get_ref(), user.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(login);
+    config.service(create);
+}
@@ -0,0 +1,6 @@
+//! Authors of books, each a name linked to its books through `book_authors`. The listing,
+//! lookup, create, update and delete are generated by [`named_model`].
+
+use crate::utils::named::named_model;
+
+named_model!(authors, book_authors, Author, Authors, AUTHORS_SORTABLE);
@@ -0,0 +1,22 @@
+//! Routes generated by [`named_routes`]: `GET /authors` and `GET /authors/{id}` for any signed-in
+//! user, kiosks included, and `POST /authors`, `PUT /authors/{id}` and `DELETE /authors/{id}` for
+//! admins and librarians. Their queries run on the blocking thread pool.
+
+use crate::authors::{Author, Authors, AUTHORS_SORTABLE};
+use crate::utils::named::named_routes;
+
+named_routes! {
+    records: Authors,
+    record: Author,
+    sortable: AUTHORS_SORTABLE,
+    page: AuthorsResponse,
+    list: "/authors",
+    one: "/authors/{id}",
+    find_all: "Get a page of all authors",
+    limit: "Max number of authors per page, 50 by default and 500 at most",
+    offset: "Number of authors to skip",
+    find: "Get an author identified with id",
+    create: "Create a new author",
+    update: "Modify an author",
+    delete: "Delete an author and remove it from its books",
+}
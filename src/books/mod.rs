@@ -1,6 +1,7 @@
 pub use model::*;
+pub use relations::*;
 pub use routes::*;
 
 mod model;
-
+mod relations;
 mod routes;
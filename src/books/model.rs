@@ -1,112 +1,609 @@
 use std::collections::{HashMap, HashSet};
 
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::{exists, not};
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use futures_util::FutureExt;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use utoipa::ToSchema;
 
+use crate::audit::{action, Audit};
+use crate::auth::Claims;
 use crate::db;
 use crate::error_handler::CustomError;
-use crate::schema::books;
-use crate::utils::check;
+use crate::holds::status as hold_status;
+use crate::schema::{authors, book_authors, book_publishers, book_subjects, books};
+use crate::schema::{holds, loans, publishers, subjects};
+use crate::utils::batch::{self, Batch, BatchReport, Operation};
+use crate::utils::check::{self, filter_int, filter_text, Isbn};
+use crate::utils::etag::Precondition;
+use crate::utils::import::{ImportReport, Outcome};
+use crate::utils::pagination::Pagination;
+use crate::utils::patch::Patch;
 
-#[derive(Serialize, Deserialize, AsChangeset, Insertable)]
+#[derive(Serialize, Deserialize, PartialEq, AsChangeset, Insertable)]
 #[diesel(table_name = books)]
 pub struct Book {
     pub title: String,
     pub isbn: String,
-    pub copies_available: i32,
-    pub copies: i32,
 }
 
+/// The fields of a book a PATCH changes, the ones left to `None` are kept as they are.
+#[derive(Default, AsChangeset)]
+#[diesel(table_name = books)]
+pub struct BookPatch {
+    pub title: Option<String>,
+    pub isbn: Option<String>,
+}
+
+/// Name of the books in the audit log.
+const ENTITY: &str = "books";
+
+/// Fields accepted by the `sort` param of the book listings.
+pub const BOOKS_SORTABLE: [&str; 5] = ["id", "title", "isbn", "copies_available", "copies"];
+
 #[derive(Serialize, Deserialize, Queryable, Insertable, ToSchema)]
 #[diesel(table_name = books)]
 pub struct Books {
     pub id: i32,
     pub title: String,
     pub isbn: String,
+    /// Copies on the shelf, counted from the items of the book.
     pub copies_available: i32,
+    /// Copies the library owns, counted from the items of the book that are not lost or
+    /// withdrawn.
     pub copies: i32,
+    /// When the book was deleted, only shown on deleted books.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Bumped on every change, sent as the `ETag` of the book.
+    pub version: i32,
+    pub updated_at: NaiveDateTime,
 }
 
 impl Books {
-    pub fn find_all() -> Result<Vec<Self>, CustomError> {
-        let mut conn = db::connection()?;
-        let books = books::table.load::<Books>(&mut conn)?;
+    pub async fn find_all(
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Self>, i64), CustomError> {
+        Books::get(&HashMap::new(), pagination, include_deleted).await
+    }
+
+    /// Page of the books matching `params`, along with how many books match in total. Deleted
+    /// books are left out unless `include_deleted` is set.
+    pub async fn get(
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Self>, i64), CustomError> {
+        let mut conn = db::async_connection().await?;
+        let total = Books::filtered(params, include_deleted)?
+            .count()
+            .get_result::<i64>(&mut conn)
+            .await?;
+
+        let mut query = Books::filtered(params, include_deleted)?;
+        for sort in &pagination.sort {
+            query = match (sort.field.as_str(), sort.descending) {
+                ("id", false) => query.then_order_by(books::id.asc()),
+                ("id", true) => query.then_order_by(books::id.desc()),
+                ("title", false) => query.then_order_by(books::title.asc()),
+                ("title", true) => query.then_order_by(books::title.desc()),
+                ("isbn", false) => query.then_order_by(books::isbn.asc()),
+                ("isbn", true) => query.then_order_by(books::isbn.desc()),
+                ("copies_available", false) => query.then_order_by(books::copies_available.asc()),
+                ("copies_available", true) => query.then_order_by(books::copies_available.desc()),
+                ("copies", false) => query.then_order_by(books::copies.asc()),
+                ("copies", true) => query.then_order_by(books::copies.desc()),
+                _ => query,
+            };
+        }
+
+        let books = query
+            .then_order_by(books::id.asc())
+            .limit(pagination.limit)
+            .offset(pagination.offset)
+            .load::<Books>(&mut conn)
+            .await?;
+        Ok((books, total))
+    }
+
+    /// Up to `limit` of the books matching `params` with an id above `after`, in id order.
+    pub async fn chunk(
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Self>, CustomError> {
+        let mut conn = db::async_connection().await?;
+        let books = Books::filtered(params, false)?
+            .filter(books::id.gt(after))
+            .order(books::id.asc())
+            .limit(limit)
+            .load::<Books>(&mut conn)
+            .await?;
         Ok(books)
     }
 
-    pub fn get(params: HashMap<String, String>) -> Result<Vec<Self>, CustomError> {
+    fn filtered(
+        params: &HashMap<String, String>,
+        include_deleted: bool,
+    ) -> Result<books::BoxedQuery<'_, Pg>, CustomError> {
         let mut query = books::table.into_boxed();
+        if !include_deleted {
+            query = query.filter(books::deleted_at.is_null());
+        }
 
-        if params.contains_key("id") {
-            match check::validate_int(params.get("id").unwrap()) {
-                Ok(id) => query = query.filter(books::id.eq(id)),
-                Err(error) => return Err(error),
+        for (key, value) in params {
+            if key == "ids" {
+                let ids_clean: HashSet<i32> = check::parse_ids(value)?.into_iter().collect();
+                query = query.filter(books::id.eq_any(ids_clean));
+                continue;
             }
-        }
-        if params.contains_key("ids") {
-            match check::parse_ids(params.get("ids").unwrap()) {
-                Ok(ids) => {
-                    let ids_clean: HashSet<i32> = ids.into_iter().collect();
-                    query = query.filter(books::id.eq_any(ids_clean));
+
+            let (field, operator) = check::parse_filter(key)?;
+            query = match field {
+                "id" => filter_int!(query, books::id, operator, check::validate_int(value)?),
+                "copies_available" => filter_int!(
+                    query,
+                    books::copies_available,
+                    operator,
+                    check::validate_int(value)?
+                ),
+                "copies" => {
+                    filter_int!(query, books::copies, operator, check::validate_int(value)?)
                 }
-                Err(error) => return Err(error),
-            }
-        }
-        if let Some(title) = params.get("title") {
-            query = query.filter(books::title.eq(title))
+                "title" => filter_text!(query, books::title, operator, value),
+                "isbn" => {
+                    let isbn = Isbn::filter_value(operator, value);
+                    filter_text!(query, books::isbn, operator, &isbn)
+                }
+                "author_id" => {
+                    let linked = filter_int!(
+                        book_authors::table.into_boxed(),
+                        book_authors::author_id,
+                        operator,
+                        check::validate_int(value)?
+                    );
+                    query.filter(books::id.eq_any(linked.select(book_authors::book_id)))
+                }
+                "author" => {
+                    let named =
+                        filter_text!(authors::table.into_boxed(), authors::name, operator, value);
+                    let linked = book_authors::table
+                        .filter(book_authors::author_id.eq_any(named.select(authors::id)))
+                        .select(book_authors::book_id);
+                    query.filter(books::id.eq_any(linked))
+                }
+                "publisher_id" => {
+                    let linked = filter_int!(
+                        book_publishers::table.into_boxed(),
+                        book_publishers::publisher_id,
+                        operator,
+                        check::validate_int(value)?
+                    );
+                    query.filter(books::id.eq_any(linked.select(book_publishers::book_id)))
+                }
+                "publisher" => {
+                    let named = filter_text!(
+                        publishers::table.into_boxed(),
+                        publishers::name,
+                        operator,
+                        value
+                    );
+                    let linked = book_publishers::table
+                        .filter(book_publishers::publisher_id.eq_any(named.select(publishers::id)))
+                        .select(book_publishers::book_id);
+                    query.filter(books::id.eq_any(linked))
+                }
+                "subject_id" => {
+                    let linked = filter_int!(
+                        book_subjects::table.into_boxed(),
+                        book_subjects::subject_id,
+                        operator,
+                        check::validate_int(value)?
+                    );
+                    query.filter(books::id.eq_any(linked.select(book_subjects::book_id)))
+                }
+                "subject" => {
+                    let named = filter_text!(
+                        subjects::table.into_boxed(),
+                        subjects::name,
+                        operator,
+                        value
+                    );
+                    let linked = book_subjects::table
+                        .filter(book_subjects::subject_id.eq_any(named.select(subjects::id)))
+                        .select(book_subjects::book_id);
+                    query.filter(books::id.eq_any(linked))
+                }
+                _ => {
+                    return Err(CustomError::new(
+                        400,
+                        format!("the parameter '{key}' is incorrect"),
+                    ))
+                }
+            };
         }
-        if let Some(isbn) = params.get("isbn") {
-            query = query.filter(books::isbn.eq(isbn))
+
+        Ok(query)
+    }
+
+    pub async fn find(id: i32, include_deleted: bool) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        let mut query = books::table.filter(books::id.eq(id)).into_boxed();
+        if !include_deleted {
+            query = query.filter(books::deleted_at.is_null());
         }
+        let book = query.first(&mut conn).await?;
+        Ok(book)
+    }
 
-        let mut conn = db::connection()?;
-        let books = match query.get_results(&mut conn) {
-            Ok(books) => books,
-            Err(e) => return Err(CustomError::from(e)),
-        };
+    pub async fn create(book: Book, actor: &Claims) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| Books::insert(conn, book, actor).await)
+            .await
+    }
 
-        Ok(books)
+    /// Save `book` over the book `id` if it is still at the version `precondition` expects.
+    pub async fn update(
+        id: i32,
+        book: Book,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| Books::save(conn, id, book, precondition, actor).await)
+            .await
     }
 
-    pub fn find(id: i32) -> Result<Self, CustomError> {
-        let mut conn = db::connection()?;
-        let book = books::table.filter(books::id.eq(id)).first(&mut conn)?;
-        Ok(book)
+    /// Apply `patch` to the book `id` if it is still at the version `precondition` expects. The
+    /// patched book is checked like a whole one, and only the fields that changed are written.
+    pub async fn patch(
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| {
+            let before = Books::lock(conn, id)
+                .await?
+                .filter(|book| book.deleted_at.is_none())
+                .ok_or_else(|| CustomError::new(404, format!("Book {id} not found")))?;
+            precondition.check(before.version)?;
+            let book = patch.apply(&Book::of(&before))?.normalize()?;
+            let Some(changes) = BookPatch::between(&before, book) else {
+                return Ok(before);
+            };
+
+            let book: Books = diesel::update(books::table)
+                .filter(books::id.eq(id))
+                .set((changes, books::version.eq(books::version + 1)))
+                .get_result(conn)
+                .await?;
+            Audit::record(
+                conn,
+                actor,
+                action::UPDATE,
+                ENTITY,
+                id,
+                Some(&before),
+                Some(&book),
+            )
+            .await?;
+            Ok(book)
+        })
+        .await
     }
 
-    pub fn create(book: Book) -> Result<Self, CustomError> {
-        let mut conn = db::connection()?;
-        let book = Book::from(book);
-        let book = diesel::insert_into(books::table)
+    /// Insert the rows of an upload in one transaction, checked like [`Books::create`]. A row
+    /// with the ISBN of a book already in the catalogue, in either form, is skipped.
+    pub async fn import(
+        rows: Vec<(u64, Result<Book, String>)>,
+        dry_run: bool,
+        actor: &Claims,
+    ) -> Result<ImportReport, CustomError> {
+        let mut conn = db::async_connection().await?;
+        ImportReport::run(&mut conn, rows, dry_run, |conn, book| {
+            let actor = actor.clone();
+            async move {
+                let book = book.normalize()?;
+                let existing = books::table
+                    .filter(books::isbn.eq(&book.isbn))
+                    .select(books::id)
+                    .first::<i32>(conn)
+                    .await
+                    .optional()?;
+                if let Some(id) = existing {
+                    return Ok(Outcome::Skipped(
+                        id,
+                        format!("isbn {} is already book {id}", book.isbn),
+                    ));
+                }
+
+                let book = Books::insert(conn, book, &actor).await?;
+                Ok(Outcome::Created(book.id))
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    /// Run a batch of creates, updates and deletes, each one checked and audited like its own
+    /// route.
+    pub async fn batch(batch: Batch, actor: &Claims) -> Result<BatchReport, CustomError> {
+        let mut conn = db::async_connection().await?;
+        BatchReport::run(&mut conn, batch, |conn, operation| {
+            let actor = actor.clone();
+            async move {
+                match operation {
+                    Operation::Create { data } => {
+                        batch::ok(&Books::insert(conn, data, &actor).await?)
+                    }
+                    Operation::Update { id, data, version } => {
+                        let precondition = Precondition::required(version)?;
+                        batch::ok(&Books::save(conn, id, data, &precondition, &actor).await?)
+                    }
+                    Operation::Delete { id } => {
+                        Ok(json!({ "deleted": Books::remove(conn, id, &actor).await? }))
+                    }
+                }
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    /// Mark a book as deleted, it is hidden from the catalogue until it is restored or purged.
+    /// A book still lent or waited for can not be deleted.
+    pub async fn delete(id: i32, actor: &Claims) -> Result<usize, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| Books::remove(conn, id, actor).await)
+            .await
+    }
+
+    /// Bring back a deleted book.
+    pub async fn restore(id: i32, actor: &Claims) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| {
+            let before = Books::lock(conn, id)
+                .await?
+                .ok_or_else(|| CustomError::new(404, format!("Book {id} not found")))?;
+            if before.deleted_at.is_none() {
+                return Err(CustomError::new(409, format!("Book {id} is not deleted")));
+            }
+            let book: Books = diesel::update(books::table.filter(books::id.eq(id)))
+                .set((
+                    books::deleted_at.eq(None::<NaiveDateTime>),
+                    books::version.eq(books::version + 1),
+                ))
+                .get_result(conn)
+                .await?;
+            Audit::record(
+                conn,
+                actor,
+                action::RESTORE,
+                ENTITY,
+                id,
+                Some(&before),
+                Some(&book),
+            )
+            .await?;
+            Ok(book)
+        })
+        .await
+    }
+
+    /// Remove for good the books deleted before `deleted_before`, along with their items. Books
+    /// that were ever lent or held are kept for their history.
+    pub async fn purge(deleted_before: NaiveDateTime) -> Result<usize, CustomError> {
+        let mut conn = db::async_connection().await?;
+        let res = diesel::delete(
+            books::table
+                .filter(books::deleted_at.lt(deleted_before))
+                .filter(not(exists(
+                    loans::table.filter(loans::book_id.eq(books::id)),
+                )))
+                .filter(not(exists(
+                    holds::table.filter(holds::book_id.eq(books::id)),
+                ))),
+        )
+        .execute(&mut conn)
+        .await?;
+        Ok(res)
+    }
+
+    async fn insert(
+        conn: &mut AsyncPgConnection,
+        book: Book,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        let book = Book::from(book.normalize()?);
+        let book: Books = diesel::insert_into(books::table)
             .values(book)
-            .get_result(&mut conn)?;
+            .get_result(conn)
+            .await?;
+        Audit::record(
+            conn,
+            actor,
+            action::CREATE,
+            ENTITY,
+            book.id,
+            None,
+            Some(&book),
+        )
+        .await?;
         Ok(book)
     }
 
-    pub fn update(id: i32, book: Book) -> Result<Self, CustomError> {
-        let mut conn = db::connection()?;
-        let book = diesel::update(books::table)
+    async fn save(
+        conn: &mut AsyncPgConnection,
+        id: i32,
+        book: Book,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        let book = book.normalize()?;
+        let before = Books::lock(conn, id)
+            .await?
+            .filter(|book| book.deleted_at.is_none())
+            .ok_or_else(|| CustomError::new(404, format!("Book {id} not found")))?;
+        precondition.check(before.version)?;
+        let book: Books = diesel::update(books::table)
             .filter(books::id.eq(id))
-            .set(book)
-            .get_result(&mut conn)?;
+            .set((book, books::version.eq(books::version + 1)))
+            .get_result(conn)
+            .await?;
+        Audit::record(
+            conn,
+            actor,
+            action::UPDATE,
+            ENTITY,
+            id,
+            Some(&before),
+            Some(&book),
+        )
+        .await?;
         Ok(book)
     }
 
-    pub fn delete(id: i32) -> Result<usize, CustomError> {
-        let mut conn = db::connection()?;
-        let res = diesel::delete(books::table.filter(books::id.eq(id))).execute(&mut conn)?;
+    async fn remove(
+        conn: &mut AsyncPgConnection,
+        id: i32,
+        actor: &Claims,
+    ) -> Result<usize, CustomError> {
+        let before = Books::lock(conn, id).await?;
+        let Some(before) = before.filter(|book| book.deleted_at.is_none()) else {
+            return Ok(0);
+        };
+        let in_use = diesel::select(
+            exists(
+                loans::table
+                    .filter(loans::book_id.eq(id))
+                    .filter(loans::returned_at.is_null()),
+            )
+            .or(exists(holds::table.filter(holds::book_id.eq(id)).filter(
+                holds::status.eq_any([hold_status::WAITING, hold_status::READY]),
+            ))),
+        )
+        .get_result::<bool>(conn)
+        .await?;
+        if in_use {
+            return Err(CustomError::new(
+                409,
+                format!("Book {id} has open loans or active holds"),
+            ));
+        }
+        let res = diesel::update(books::table.filter(books::id.eq(id)))
+            .set((
+                books::deleted_at.eq(Utc::now().naive_utc()),
+                books::version.eq(books::version + 1),
+            ))
+            .execute(conn)
+            .await?;
+        Audit::record(conn, actor, action::DELETE, ENTITY, id, Some(&before), None).await?;
         Ok(res)
     }
+
+    async fn lock(conn: &mut AsyncPgConnection, id: i32) -> Result<Option<Self>, CustomError> {
+        let book = books::table
+            .filter(books::id.eq(id))
+            .for_update()
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(book)
+    }
 }
 
 impl Book {
+    /// Check the fields of a book before it is created, saved or imported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::books::Book;
+    ///
+    /// let book = Book {
+    ///     title: " ".to_string(),
+    ///     isbn: "9780441013593".to_string(),
+    /// };
+    /// match book.validate() {
+    ///     Err(e) if e.to_string() == "title can not be empty" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    pub fn validate(&self) -> Result<(), CustomError> {
+        if self.title.trim().is_empty() {
+            return Err(CustomError::new(400, "title can not be empty".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check a book with [`Book::validate`] before it is saved, and put its ISBN in canonical
+    /// ISBN-13 form.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::books::Book;
+    ///
+    /// let book = Book {
+    ///     title: "Dune".to_string(),
+    ///     isbn: "0-441-01359-7".to_string(),
+    /// };
+    /// assert_eq!("9780441013593", book.normalize().unwrap().isbn);
+    ///
+    /// let book = Book {
+    ///     title: "Dune".to_string(),
+    ///     isbn: "0-441-01359-8".to_string(),
+    /// };
+    /// match book.normalize() {
+    ///     Err(e) if e.to_string() == "isbn '0-441-01359-8' has a wrong check digit" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    pub fn normalize(self) -> Result<Self, CustomError> {
+        self.validate()?;
+        let isbn = self.isbn.parse::<Isbn>()?;
+        Ok(Book {
+            isbn: isbn.to_string(),
+            ..self
+        })
+    }
+
+    /// The editable fields of a saved book.
+    pub(crate) fn of(book: &Books) -> Book {
+        Book {
+            title: book.title.clone(),
+            isbn: book.isbn.clone(),
+        }
+    }
+
     fn from(book: Book) -> Book {
         Book {
             title: book.title,
             isbn: book.isbn,
-            copies_available: book.copies_available,
-            copies: book.copies,
+        }
+    }
+}
+
+impl BookPatch {
+    /// The fields of `book` that differ from `before`, `None` when there are none.
+    fn between(before: &Books, book: Book) -> Option<Self> {
+        let changes = BookPatch {
+            title: (book.title != before.title).then_some(book.title),
+            isbn: (book.isbn != before.isbn).then_some(book.isbn),
+        };
+        let unchanged = changes.title.is_none() && changes.isbn.is_none();
+        match unchanged {
+            true => None,
+            false => Some(changes),
         }
     }
 }
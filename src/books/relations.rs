@@ -0,0 +1,337 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::authors::Authors;
+use crate::books::Books;
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::publishers::Publishers;
+use crate::schema::{authors, book_authors, book_publishers, book_subjects, books};
+use crate::schema::{publishers, subjects};
+use crate::subjects::Subjects;
+
+/// Resources linked to books through a join table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Authors,
+    Publishers,
+    Subjects,
+}
+
+impl Relation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Relation::Authors => "authors",
+            Relation::Publishers => "publishers",
+            Relation::Subjects => "subjects",
+        }
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Relation {
+    type Err = CustomError;
+
+    /// Parse the name of a relation, in plural or singular.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::books::Relation;
+    ///
+    /// assert_eq!(Relation::Authors, "authors".parse().unwrap());
+    /// assert_eq!(Relation::Publishers, "publisher".parse().unwrap());
+    /// assert!("editors".parse::<Relation>().is_err());
+    /// ```
+    fn from_str(relation: &str) -> Result<Self, Self::Err> {
+        match relation {
+            "authors" | "author" => Ok(Relation::Authors),
+            "publishers" | "publisher" => Ok(Relation::Publishers),
+            "subjects" | "subject" => Ok(Relation::Subjects),
+            _ => Err(CustomError::new(
+                400,
+                format!("unknown relation '{relation}', use authors, publishers or subjects"),
+            )),
+        }
+    }
+}
+
+/// Relations embedded in book responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Include {
+    pub authors: bool,
+    pub publishers: bool,
+    pub subjects: bool,
+}
+
+impl Include {
+    /// Take the `include` param out of `params`, a comma separated list of relations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::books::Include;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut params = HashMap::new();
+    /// params.insert("include".to_string(), "authors,publisher".to_string());
+    ///
+    /// let include = Include::from_params(&mut params).unwrap();
+    /// assert!(include.authors && include.publishers && !include.subjects);
+    /// assert!(params.is_empty());
+    /// ```
+    pub fn from_params(params: &mut HashMap<String, String>) -> Result<Self, CustomError> {
+        let mut include = Include::default();
+        if let Some(relations) = params.remove("include") {
+            for relation in relations.split(',').filter(|relation| !relation.is_empty()) {
+                include.add(relation.trim().parse()?);
+            }
+        }
+        Ok(include)
+    }
+
+    pub fn only(relation: Relation) -> Self {
+        let mut include = Include::default();
+        include.add(relation);
+        include
+    }
+
+    /// Put the `include` param back in `params`, so the links to other pages keep it.
+    pub fn keep(&self, params: &mut HashMap<String, String>) {
+        let relations: Vec<&str> = [
+            (self.authors, Relation::Authors),
+            (self.publishers, Relation::Publishers),
+            (self.subjects, Relation::Subjects),
+        ]
+        .iter()
+        .filter(|(included, _)| *included)
+        .map(|(_, relation)| relation.as_str())
+        .collect();
+        if !relations.is_empty() {
+            params.insert("include".to_string(), relations.join(","));
+        }
+    }
+
+    fn add(&mut self, relation: Relation) {
+        match relation {
+            Relation::Authors => self.authors = true,
+            Relation::Publishers => self.publishers = true,
+            Relation::Subjects => self.subjects = true,
+        }
+    }
+}
+
+/// A book with the relations asked for in the `include` param. Relations that were not asked
+/// for are left out of the response.
+#[derive(Serialize, ToSchema)]
+pub struct BookDetails {
+    #[serde(flatten)]
+    pub book: Books,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authors: Option<Vec<Authors>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publishers: Option<Vec<Publishers>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subjects: Option<Vec<Subjects>>,
+}
+
+impl BookDetails {
+    /// Load the relations in `include` for all of `books` at once.
+    pub async fn load(books: Vec<Books>, include: Include) -> Result<Vec<Self>, CustomError> {
+        if include == Include::default() {
+            return Ok(BookDetails::with(
+                books,
+                include,
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+            ));
+        }
+        let mut conn = db::async_connection().await?;
+        BookDetails::load_with(&mut conn, books, include).await
+    }
+
+    async fn load_with(
+        conn: &mut AsyncPgConnection,
+        books: Vec<Books>,
+        include: Include,
+    ) -> Result<Vec<Self>, CustomError> {
+        let ids: Vec<i32> = books.iter().map(|book| book.id).collect();
+        let authors = match include.authors {
+            true => grouped(Authors::of_books(conn, &ids).await?),
+            false => HashMap::new(),
+        };
+        let publishers = match include.publishers {
+            true => grouped(Publishers::of_books(conn, &ids).await?),
+            false => HashMap::new(),
+        };
+        let subjects = match include.subjects {
+            true => grouped(Subjects::of_books(conn, &ids).await?),
+            false => HashMap::new(),
+        };
+        Ok(BookDetails::with(
+            books, include, authors, publishers, subjects,
+        ))
+    }
+
+    fn with(
+        books: Vec<Books>,
+        include: Include,
+        mut authors: HashMap<i32, Vec<Authors>>,
+        mut publishers: HashMap<i32, Vec<Publishers>>,
+        mut subjects: HashMap<i32, Vec<Subjects>>,
+    ) -> Vec<Self> {
+        books
+            .into_iter()
+            .map(|book| BookDetails {
+                authors: include
+                    .authors
+                    .then(|| authors.remove(&book.id).unwrap_or_default()),
+                publishers: include
+                    .publishers
+                    .then(|| publishers.remove(&book.id).unwrap_or_default()),
+                subjects: include
+                    .subjects
+                    .then(|| subjects.remove(&book.id).unwrap_or_default()),
+                book,
+            })
+            .collect()
+    }
+}
+
+fn grouped<T>(rows: Vec<(i32, T)>) -> HashMap<i32, Vec<T>> {
+    let mut groups: HashMap<i32, Vec<T>> = HashMap::new();
+    for (book_id, row) in rows {
+        groups.entry(book_id).or_default().push(row);
+    }
+    groups
+}
+
+impl Books {
+    /// Replace the `relation` of the book `id` with the records `ids`.
+    pub async fn link(
+        id: i32,
+        relation: Relation,
+        ids: Vec<i32>,
+    ) -> Result<BookDetails, CustomError> {
+        let ids: Vec<i32> = ids
+            .into_iter()
+            .collect::<BTreeSet<i32>>()
+            .into_iter()
+            .collect();
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| {
+            let book: Books = books::table
+                .filter(books::id.eq(id))
+                .filter(books::deleted_at.is_null())
+                .for_update()
+                .first(conn)
+                .await
+                .optional()?
+                .ok_or_else(|| CustomError::new(404, format!("Book {id} not found")))?;
+
+            let found: Vec<i32> = match relation {
+                Relation::Authors => {
+                    authors::table
+                        .filter(authors::id.eq_any(&ids))
+                        .select(authors::id)
+                        .load(conn)
+                        .await?
+                }
+                Relation::Publishers => {
+                    publishers::table
+                        .filter(publishers::id.eq_any(&ids))
+                        .select(publishers::id)
+                        .load(conn)
+                        .await?
+                }
+                Relation::Subjects => {
+                    subjects::table
+                        .filter(subjects::id.eq_any(&ids))
+                        .select(subjects::id)
+                        .load(conn)
+                        .await?
+                }
+            };
+            if let Some(missing) = ids.iter().find(|id| !found.contains(id)) {
+                return Err(CustomError::new(
+                    404,
+                    format!("{missing} is not in {relation}"),
+                ));
+            }
+
+            match relation {
+                Relation::Authors => {
+                    diesel::delete(book_authors::table.filter(book_authors::book_id.eq(id)))
+                        .execute(conn)
+                        .await?;
+                    let rows: Vec<_> = ids
+                        .iter()
+                        .map(|author_id| {
+                            (
+                                book_authors::book_id.eq(id),
+                                book_authors::author_id.eq(author_id),
+                            )
+                        })
+                        .collect();
+                    diesel::insert_into(book_authors::table)
+                        .values(&rows)
+                        .execute(conn)
+                        .await?;
+                }
+                Relation::Publishers => {
+                    diesel::delete(book_publishers::table.filter(book_publishers::book_id.eq(id)))
+                        .execute(conn)
+                        .await?;
+                    let rows: Vec<_> = ids
+                        .iter()
+                        .map(|publisher_id| {
+                            (
+                                book_publishers::book_id.eq(id),
+                                book_publishers::publisher_id.eq(publisher_id),
+                            )
+                        })
+                        .collect();
+                    diesel::insert_into(book_publishers::table)
+                        .values(&rows)
+                        .execute(conn)
+                        .await?;
+                }
+                Relation::Subjects => {
+                    diesel::delete(book_subjects::table.filter(book_subjects::book_id.eq(id)))
+                        .execute(conn)
+                        .await?;
+                    let rows: Vec<_> = ids
+                        .iter()
+                        .map(|subject_id| {
+                            (
+                                book_subjects::book_id.eq(id),
+                                book_subjects::subject_id.eq(subject_id),
+                            )
+                        })
+                        .collect();
+                    diesel::insert_into(book_subjects::table)
+                        .values(&rows)
+                        .execute(conn)
+                        .await?;
+                }
+            }
+
+            let mut details =
+                BookDetails::load_with(conn, vec![book], Include::only(relation)).await?;
+            Ok(details.remove(0))
+        })
+        .await
+    }
+}
@@ -1,25 +1,58 @@
 use std::collections::HashMap;
 
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
 use serde_json::json;
 
-use crate::books::{Book, Books};
+use crate::auth::{Claims, RequireRole};
+use crate::books::{Book, BookDetails, Books, Include, Relation, BOOKS_SORTABLE};
 use crate::error_handler::CustomError;
+use crate::repository::BookRepository;
+use crate::utils;
+use crate::utils::batch::{Batch, BatchReport};
 use crate::utils::check;
+use crate::utils::etag::{self, Precondition};
+use crate::utils::import::{parse, read_upload, ImportReport, Options};
+use crate::utils::pagination::{Page, Pagination};
+use crate::utils::patch::Patch;
 use crate::utils::response;
 
 #[utoipa::path(
     get,
     path = "/books",
     responses(
-        (status = 200, description = "Get all books", body = inline(response::BooksResponse)),
+        (status = 200, description = "Get a page of all books", body = inline(response::BooksResponse)),
         (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("limit" = Option<i64>, Query, description = "Max number of books per page, 50 by default and 500 at most"),
+        ("offset" = Option<i64>, Query, description = "Number of books to skip"),
+        ("sort" = Option<String>, Query, description = "Comma separated fields to sort by, prefix a field with '-' for descending order example (title,-id)"),
+        ("include" = Option<String>, Query, description = "Comma separated relations to embed in each book: authors, publishers and subjects"),
+        ("include_deleted" = Option<bool>, Query, description = "List the deleted books too, admins only"),
     )
 )]
-#[get("/books")]
-async fn find_all() -> Result<HttpResponse, CustomError> {
-    let books = web::block(Books::find_all).await.unwrap();
-    Ok(HttpResponse::Ok().json(books))
+#[get("/books", wrap = "RequireRole::staff()")]
+async fn find_all(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let pagination = Pagination::from_params(&mut params, &BOOKS_SORTABLE)?;
+    let include = Include::from_params(&mut params)?;
+    let include_deleted = check::include_deleted(&mut params, claims.role)?;
+
+    let (books, total) = repository.find_all(&pagination, include_deleted).await?;
+    let books = BookDetails::load(books, include).await?;
+
+    let mut links = HashMap::new();
+    include.keep(&mut links);
+    if include_deleted {
+        links.insert("include_deleted".to_string(), "true".to_string());
+    }
+    Ok(HttpResponse::Ok().json(Page::new(books, total, &pagination, req.path(), &links)))
 }
 
 #[utoipa::path(
@@ -30,43 +63,178 @@ async fn find_all() -> Result<HttpResponse, CustomError> {
         (status = 400, description = "Error", body = inline(response::ErrorResponse))
     ),
     params(
-        ("id" = Option<i32>, Query, description = "Book database id"),
+        ("id" = Option<i32>, Query, description = "Book database id, also as id__ne, __gt, __gte, __lt or __lte"),
         ("ids" = Option<String>, Query, description = "Books database comma separated ids example (1,2,3)"),
-        ("title" = Option<String>, Query,  description = "Book Title"),
-        ("isbn" = Option<String>, Query,  description = "Book isbn"),
-        ("copies_available" = Option<i32>, Query,  description = "Num of copies available"),
-        ("copies" = Option<i32>, Query, description = "Num of total copies"),
+        ("title" = Option<String>, Query,  description = "Book Title, also as title__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("isbn" = Option<String>, Query,  description = "Book ISBN-10 or ISBN-13, also as isbn__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("copies_available" = Option<i32>, Query,  description = "Num of copies available, also as copies_available__ne, __gt, __gte, __lt or __lte"),
+        ("copies" = Option<i32>, Query, description = "Num of total copies, also as copies__ne, __gt, __gte, __lt or __lte"),
+        ("author_id" = Option<i32>, Query, description = "Books by this author id, also as author_id__ne, __gt, __gte, __lt or __lte"),
+        ("author" = Option<String>, Query, description = "Books by an author with this name, also as author__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("publisher_id" = Option<i32>, Query, description = "Books from this publisher id, also as publisher_id__ne, __gt, __gte, __lt or __lte"),
+        ("publisher" = Option<String>, Query, description = "Books from a publisher with this name, also as publisher__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("subject_id" = Option<i32>, Query, description = "Books about this subject id, also as subject_id__ne, __gt, __gte, __lt or __lte"),
+        ("subject" = Option<String>, Query, description = "Books about a subject with this name, also as subject__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("limit" = Option<i64>, Query, description = "Max number of books per page, 50 by default and 500 at most"),
+        ("offset" = Option<i64>, Query, description = "Number of books to skip"),
+        ("sort" = Option<String>, Query, description = "Comma separated fields to sort by, prefix a field with '-' for descending order example (title,-id)"),
+        ("include" = Option<String>, Query, description = "Comma separated relations to embed in each book: authors, publishers and subjects"),
+        ("include_deleted" = Option<bool>, Query, description = "List the deleted books too, admins only"),
     )
 )]
-#[get("/books/filter")]
-async fn filter(_param: web::Query<HashMap<String, String>>) -> Result<HttpResponse, CustomError> {
-    let params = _param.into_inner();
-
-    let books = if params.is_empty() {
-        web::block(Books::find_all).await.unwrap()
-    } else {
-        match check::validate_book_params(&params) {
-            Ok(..) => web::block(move || Books::get(params)).await.unwrap(),
-            Err(err) => return Err(err),
+#[get("/books/filter", wrap = "RequireRole::any()")]
+async fn filter(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let pagination = Pagination::from_params(&mut params, &BOOKS_SORTABLE)?;
+    let include = Include::from_params(&mut params)?;
+    let include_deleted = check::include_deleted(&mut params, claims.role)?;
+    check::validate_book_params(&params)?;
+
+    let (books, total) = repository
+        .get(&params, &pagination, include_deleted)
+        .await?;
+    let books = BookDetails::load(books, include).await?;
+
+    include.keep(&mut params);
+    if include_deleted {
+        params.insert("include_deleted".to_string(), "true".to_string());
+    }
+    Ok(HttpResponse::Ok().json(Page::new(books, total, &pagination, req.path(), &params)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/books/export",
+    responses(
+        (status = 200, description = "Download every book matching the url params, in id order", content_type = "text/csv"),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("format" = Option<String>, Query, description = "csv (default) or ndjson"),
+        ("id" = Option<i32>, Query, description = "Book database id, also as id__ne, __gt, __gte, __lt or __lte"),
+        ("ids" = Option<String>, Query, description = "Books database comma separated ids example (1,2,3)"),
+        ("title" = Option<String>, Query,  description = "Book Title, also as title__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("isbn" = Option<String>, Query,  description = "Book ISBN-10 or ISBN-13, also as isbn__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("copies_available" = Option<i32>, Query,  description = "Num of copies available, also as copies_available__ne, __gt, __gte, __lt or __lte"),
+        ("copies" = Option<i32>, Query, description = "Num of total copies, also as copies__ne, __gt, __gte, __lt or __lte"),
+        ("author_id" = Option<i32>, Query, description = "Books by this author id, also as author_id__ne, __gt, __gte, __lt or __lte"),
+        ("author" = Option<String>, Query, description = "Books by an author with this name, also as author__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("publisher_id" = Option<i32>, Query, description = "Books from this publisher id, also as publisher_id__ne, __gt, __gte, __lt or __lte"),
+        ("publisher" = Option<String>, Query, description = "Books from a publisher with this name, also as publisher__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("subject_id" = Option<i32>, Query, description = "Books about this subject id, also as subject_id__ne, __gt, __gte, __lt or __lte"),
+        ("subject" = Option<String>, Query, description = "Books about a subject with this name, also as subject__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+    )
+)]
+#[get("/books/export", wrap = "RequireRole::staff()")]
+async fn export(
+    _param: web::Query<HashMap<String, String>>,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let format = utils::export::format(&mut params)?;
+    check::validate_book_params(&params)?;
+
+    let fetch = move |after| {
+        let (params, repository) = (params.clone(), repository.clone());
+        async move {
+            repository
+                .chunk(&params, after, utils::export::CHUNK_ROWS)
+                .await
         }
     };
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, utils::export::content_type(format)))
+        .insert_header((
+            CONTENT_DISPOSITION,
+            utils::export::content_disposition("books", format),
+        ))
+        .streaming(utils::export::rows(format, fetch, |book: &Books| book.id)))
+}
 
-    Ok(HttpResponse::Ok().json(books))
+#[utoipa::path(
+    post,
+    path = "/books/import",
+    request_body(content = String, content_type = "text/csv", description = "CSV with a header (title,isbn), or NDJSON with one book per line sent as application/x-ndjson"),
+    responses(
+        (status = 200, description = "Import books, rows with the ISBN of a book already in the catalogue are skipped", body = inline(ImportReport)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 413, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 415, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 422, description = "Some rows failed and nothing was saved", body = inline(ImportReport))
+    ),
+    params(
+        ("format" = Option<String>, Query, description = "csv or ndjson, taken from the Content-Type when missing"),
+        ("dry_run" = Option<bool>, Query, description = "Report what the import would do without saving anything"),
+    )
+)]
+#[post("/books/import", wrap = "RequireRole::staff()")]
+async fn import(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+    payload: web::Payload,
+    claims: Claims,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let options = Options::from_request(&req, &mut params)?;
+    let body = read_upload(payload).await?;
+    let rows = parse::<Book>(options.format, &body)?;
+
+    let report = Books::import(rows, options.dry_run, &claims).await?;
+    Ok(HttpResponse::build(report.status_code()).json(report))
+}
+
+#[utoipa::path(
+    post,
+    path = "/books/batch",
+    request_body(content = String, content_type = "application/json", description = "{\"mode\": \"atomic\" (default) or \"best_effort\", \"operations\": [{\"op\": \"create\", \"data\": {..}}, {\"op\": \"update\", \"id\": 1, \"version\": 2, \"data\": {..}}, {\"op\": \"delete\", \"id\": 1}]}, 1000 operations at most"),
+    responses(
+        (status = 200, description = "Create, update and delete books, with the status and the response of every operation", body = inline(BatchReport)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 413, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 422, description = "An operation failed and nothing was saved", body = inline(BatchReport))
+    )
+)]
+#[post("/books/batch", wrap = "RequireRole::staff()")]
+async fn batch(batch: web::Json<Batch>, claims: Claims) -> Result<HttpResponse, CustomError> {
+    let report = Books::batch(batch.into_inner(), &claims).await?;
+    Ok(HttpResponse::build(report.status_code()).json(report))
 }
 
 #[utoipa::path(
     get,
     path = "/books/{id}",
     responses(
-        (status = 200, description = "Get a book identifies with id", body = inline(Books)),
+        (status = 200, description = "Get a book identifies with id, along with its ETag", body = inline(BookDetails)),
         (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 403, description = "Error", body = inline(response::ErrorResponse)),
         (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("include" = Option<String>, Query, description = "Comma separated relations to embed in each book: authors, publishers and subjects"),
+        ("include_deleted" = Option<bool>, Query, description = "Find the book even if it is deleted, admins only"),
     )
 )]
-#[get("/books/{id}")]
-async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
-    let book = Books::find(id.into_inner())?;
-    Ok(HttpResponse::Ok().json(book))
+#[get("/books/{id}", wrap = "RequireRole::staff()")]
+async fn find(
+    id: web::Path<i32>,
+    _param: web::Query<HashMap<String, String>>,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let include = Include::from_params(&mut params)?;
+    let include_deleted = check::include_deleted(&mut params, claims.role)?;
+    let book = repository.find(id.into_inner(), include_deleted).await?;
+    let version = book.version;
+    let mut book = BookDetails::load(vec![book], include).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(version))
+        .json(book.remove(0)))
 }
 
 #[utoipa::path(
@@ -77,48 +245,162 @@ async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
         (status = 400, description = "Error", body = inline(response::ErrorResponse))
     )
 )]
-#[post("/books")]
-async fn create(book: web::Json<Book>) -> Result<HttpResponse, CustomError> {
-    let book = Books::create(book.into_inner())?;
-    Ok(HttpResponse::Ok().json(book))
+#[post("/books", wrap = "RequireRole::staff()")]
+async fn create(
+    book: web::Json<Book>,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let book = repository.create(book.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(book.version))
+        .json(book))
 }
 
 #[utoipa::path(
     put,
     path = "/books{id}",
     responses(
-    (status = 200, description = "Modify a new book", body = inline(response::BookResponse)),
+    (status = 200, description = "Modify a book, the new ETag is sent back", body = inline(response::BookResponse)),
     (status = 400, description = "Error", body = inline(response::ErrorResponse)),
-    (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+    (status = 412, description = "The book changed since its ETag was read", body = inline(response::ErrorResponse)),
+    (status = 428, description = "The If-Match header is missing", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("If-Match" = String, Header, description = "ETag of the book as it was read"),
     )
 )]
-#[put("/books/{id}")]
-async fn update(id: web::Path<i32>, book: web::Json<Book>) -> Result<HttpResponse, CustomError> {
-    let book = Books::update(id.into_inner(), book.into_inner())?;
-    Ok(HttpResponse::Ok().json(book))
+#[put("/books/{id}", wrap = "RequireRole::staff()")]
+async fn update(
+    id: web::Path<i32>,
+    book: web::Json<Book>,
+    precondition: Precondition,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let book = repository
+        .update(id.into_inner(), book.into_inner(), &precondition, &claims)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(book.version))
+        .json(book))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/books/{id}",
+    request_body(content = String, content_type = "application/merge-patch+json", description = "The fields to change as a JSON Merge Patch, or a list of operations sent as application/json-patch+json"),
+    responses(
+        (status = 200, description = "Change some fields of a book, the new ETag is sent back", body = inline(response::BookResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "A test operation of the JSON Patch failed", body = inline(response::ErrorResponse)),
+        (status = 412, description = "The book changed since its ETag was read", body = inline(response::ErrorResponse)),
+        (status = 415, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 422, description = "The patch can not be applied to the book", body = inline(response::ErrorResponse)),
+        (status = 428, description = "The If-Match header is missing", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("If-Match" = String, Header, description = "ETag of the book as it was read"),
+    )
+)]
+#[patch("/books/{id}", wrap = "RequireRole::staff()")]
+async fn patch(
+    id: web::Path<i32>,
+    req: HttpRequest,
+    body: web::Bytes,
+    precondition: Precondition,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let patch = Patch::from_request(&req, &body)?;
+    let book = repository
+        .patch(id.into_inner(), patch, &precondition, &claims)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(book.version))
+        .json(book))
 }
 
 #[utoipa::path(
     delete,
     path = "/books{id}",
     responses(
-        (status = 200, description = "Delete a new book", body = inline(response::DeleteResponse)),
+        (status = 200, description = "Delete a book, it can be restored until it is purged", body = inline(response::DeleteResponse)),
         (status = 400, description = "Error", body = inline(response::ErrorResponse)),
-        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "The book has open loans or active holds", body = inline(response::ErrorResponse))
     )
 )]
-#[delete("/books/{id}")]
-async fn delete(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
-    let deleted_book = Books::delete(id.into_inner())?;
+#[delete("/books/{id}", wrap = "RequireRole::staff()")]
+async fn delete(
+    id: web::Path<i32>,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let deleted_book = repository.delete(id.into_inner(), &claims).await?;
     Ok(HttpResponse::Ok().json(json!({ "deleted": deleted_book })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/books/{id}/restore",
+    responses(
+        (status = 200, description = "Restore a deleted book", body = inline(response::BookResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "The book is not deleted", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/books/{id}/restore", wrap = "RequireRole::staff()")]
+async fn restore(
+    id: web::Path<i32>,
+    claims: Claims,
+    repository: web::Data<dyn BookRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let book = repository.restore(id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(book.version))
+        .json(book))
+}
+
+#[utoipa::path(
+    put,
+    path = "/books/{id}/{relation}",
+    request_body = Vec<i32>,
+    responses(
+        (status = 200, description = "Replace the authors, publishers or subjects of a book with the given ids", body = inline(BookDetails)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("id" = i32, Path, description = "Book database id"),
+        ("relation" = String, Path, description = "authors, publishers or subjects"),
+    )
+)]
+#[put("/books/{id}/{relation}", wrap = "RequireRole::staff()")]
+async fn link(
+    path: web::Path<(i32, String)>,
+    ids: web::Json<Vec<i32>>,
+) -> Result<HttpResponse, CustomError> {
+    let (id, relation) = path.into_inner();
+    let relation: Relation = relation.parse()?;
+    let book = Books::link(id, relation, ids.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(book))
+}
+
 pub fn init_routes(config: &mut web::ServiceConfig) {
     config.service(find_all);
     config.service(filter);
+    config.service(import);
+    config.service(export);
+    config.service(batch);
     config.service(find);
     config.service(create);
     config.service(update);
+    config.service(patch);
+    config.service(link);
     config.service(delete);
     config.service(delete);
+    config.service(restore);
 }
@@ -0,0 +1,447 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use actix_web::web;
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+use crate::fines::FinePolicy;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Every setting as `(key, environment variable, command line flag)`, the key being its path in
+/// the TOML file. Secrets have no flag so they never show in the process list.
+const SETTINGS: &[(&str, &str, Option<&str>)] = &[
+    ("server.host", "HOST", Some("--host")),
+    ("server.port", "PORT", Some("--port")),
+    ("server.workers", "WORKERS", Some("--workers")),
+    ("database.url", "DATABASE_URL", Some("--database-url")),
+    (
+        "database.pool_size",
+        "DATABASE_POOL_SIZE",
+        Some("--pool-size"),
+    ),
+    (
+        "database.async_pool_size",
+        "DATABASE_ASYNC_POOL_SIZE",
+        Some("--async-pool-size"),
+    ),
+    (
+        "database.timeout_seconds",
+        "DATABASE_TIMEOUT_SECONDS",
+        Some("--db-timeout"),
+    ),
+    ("log.level", "RUST_LOG", Some("--log-level")),
+    ("log.format", "LOG_FORMAT", Some("--log-format")),
+    (
+        "log.otlp_endpoint",
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        Some("--otlp-endpoint"),
+    ),
+    ("auth.jwt_secret", "JWT_SECRET", None),
+    (
+        "auth.jwt_expiration_minutes",
+        "JWT_EXPIRATION_MINUTES",
+        Some("--jwt-expiration-minutes"),
+    ),
+    ("auth.admin_username", "ADMIN_USERNAME", None),
+    ("auth.admin_password", "ADMIN_PASSWORD", None),
+    ("fines.daily_rate_cents", "FINE_DAILY_RATE_CENTS", None),
+    ("fines.cap_cents", "FINE_CAP_CENTS", None),
+    (
+        "purge.retention_days",
+        "PURGE_RETENTION_DAYS",
+        Some("--purge-retention-days"),
+    ),
+    (
+        "features.run_migrations",
+        "RUN_MIGRATIONS",
+        Some("--run-migrations"),
+    ),
+    ("features.purge", "PURGE_ENABLED", Some("--purge")),
+    ("features.swagger_ui", "SWAGGER_UI", Some("--swagger-ui")),
+];
+
+/// Settings of the API, layered from the defaults, then the TOML file named by `--config` or
+/// `CONFIG_FILE`, then the environment, then the command line flags.
+///
+/// The server loads it once on start and hands it to the routes as `web::Data<Config>`, the
+/// pools and the models read it through [`get`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub log: LogConfig,
+    pub auth: AuthConfig,
+    pub fines: FinePolicy,
+    pub purge: PurgeConfig,
+    pub features: FeaturesConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Worker threads, 0 for one per CPU.
+    pub workers: usize,
+}
+
+impl ServerConfig {
+    /// Worker threads the server runs, one per CPU unless `workers` is set.
+    pub fn worker_count(&self) -> usize {
+        match self.workers {
+            0 => std::thread::available_parallelism().map_or(1, usize::from),
+            workers => workers,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8000,
+            workers: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// A `postgres://` url, or a `sqlite:` one to keep books and members in SQLite.
+    pub url: String,
+    /// Connections of the blocking pool.
+    pub pool_size: u32,
+    /// Connections of the async pool of each worker.
+    pub async_pool_size: u32,
+    /// How long to wait for a free connection.
+    pub timeout_seconds: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            pool_size: 10,
+            async_pool_size: 10,
+            timeout_seconds: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// A filter of the log lines, such as `info` or `api_rust=debug,diesel=debug`.
+    pub level: String,
+    /// `text` for lines meant for people, `json` for one JSON object per line.
+    pub format: String,
+    /// OpenTelemetry collector to send the request spans to, such as `http://localhost:4318`.
+    /// Needs the server built with the `otlp` feature.
+    pub otlp_endpoint: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_string(),
+            format: "text".to_string(),
+            otlp_endpoint: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    pub jwt_expiration_minutes: i64,
+    /// First admin account, created while the users table is empty.
+    pub admin_username: Option<String>,
+    pub admin_password: Option<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_secret: String::new(),
+            jwt_expiration_minutes: 60,
+            admin_username: None,
+            admin_password: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PurgeConfig {
+    /// Days a deleted book or member can still be restored.
+    pub retention_days: i64,
+}
+
+impl Default for PurgeConfig {
+    fn default() -> Self {
+        PurgeConfig { retention_days: 30 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturesConfig {
+    /// Apply the pending migrations on start.
+    pub run_migrations: bool,
+    /// Purge the old deleted books and members every hour.
+    pub purge: bool,
+    /// Serve the Swagger UI.
+    pub swagger_ui: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        FeaturesConfig {
+            run_migrations: false,
+            purge: true,
+            swagger_ui: true,
+        }
+    }
+}
+
+/// Every problem found while loading the configuration.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Invalid configuration:")?;
+        for error in &self.0 {
+            write!(f, "\n  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn parse<T: FromStr>(value: &str, expected: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("'{value}' is not {expected}"))
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!("'{value}' is not true or false")),
+    }
+}
+
+impl Config {
+    /// Load the configuration of the server from `args`, its command line flags.
+    pub fn load(args: &[String]) -> Result<Config, ConfigError> {
+        Config::load_with(args, |name| env::var(name).ok())
+    }
+
+    /// Load the configuration with `var` in place of the environment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::config::Config;
+    ///
+    /// let var = |name: &str| match name {
+    ///     "DATABASE_URL" => Some("postgres://localhost/library".to_string()),
+    ///     "JWT_SECRET" => Some("secret".to_string()),
+    ///     "PORT" => Some("9000".to_string()),
+    ///     _ => None,
+    /// };
+    /// let args = ["--port".to_string(), "9001".to_string()];
+    /// let config = Config::load_with(&args, var).unwrap();
+    /// assert_eq!(config.server.port, 9001);
+    /// assert_eq!(config.database.pool_size, 10);
+    /// assert_eq!(config.database.async_pool_size, 10);
+    ///
+    /// let error = Config::load_with(&[], |_| None).unwrap_err();
+    /// assert_eq!(error.0.len(), 2);
+    /// ```
+    pub fn load_with(
+        args: &[String],
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut errors = Vec::new();
+        let flags = flags(args, &mut errors);
+
+        let file = flags
+            .iter()
+            .find(|(flag, _)| *flag == "--config")
+            .map(|(_, path)| path.clone())
+            .or_else(|| var("CONFIG_FILE"));
+        let mut config = match file {
+            Some(path) => Config::read(&path).unwrap_or_else(|e| {
+                errors.push(e);
+                Config::default()
+            }),
+            None => Config::default(),
+        };
+
+        for (key, name, _) in SETTINGS {
+            if let Some(value) = var(name) {
+                if let Err(e) = config.set(key, &value) {
+                    errors.push(format!("{name}: {e}"));
+                }
+            }
+        }
+        for (flag, value) in flags.iter().filter(|(flag, _)| *flag != "--config") {
+            match SETTINGS.iter().find(|(_, _, f)| *f == Some(flag.as_str())) {
+                Some((key, _, _)) => {
+                    if let Err(e) = config.set(key, value) {
+                        errors.push(format!("{flag}: {e}"));
+                    }
+                }
+                None => errors.push(format!("{flag}: unknown flag")),
+            }
+        }
+
+        errors.extend(config.validate());
+        match errors.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigError(errors)),
+        }
+    }
+
+    fn read(path: &str) -> Result<Config, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        toml::from_str(&content).map_err(|e| format!("{path}: {}", e.message()))
+    }
+
+    /// Set the setting at `key` from its text form.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let text = || value.to_string();
+        match key {
+            "server.host" => self.server.host = text(),
+            "server.port" => self.server.port = parse(value, "a port")?,
+            "server.workers" => self.server.workers = parse(value, "a number of workers")?,
+            "database.url" => self.database.url = text(),
+            "database.pool_size" => {
+                self.database.pool_size = parse(value, "a number of connections")?
+            }
+            "database.async_pool_size" => {
+                self.database.async_pool_size = parse(value, "a number of connections")?
+            }
+            "database.timeout_seconds" => {
+                self.database.timeout_seconds = parse(value, "a number of seconds")?
+            }
+            "log.level" => self.log.level = text(),
+            "log.format" => self.log.format = text(),
+            "log.otlp_endpoint" => self.log.otlp_endpoint = Some(text()),
+            "auth.jwt_secret" => self.auth.jwt_secret = text(),
+            "auth.jwt_expiration_minutes" => {
+                self.auth.jwt_expiration_minutes = parse(value, "a number of minutes")?
+            }
+            "auth.admin_username" => self.auth.admin_username = Some(text()),
+            "auth.admin_password" => self.auth.admin_password = Some(text()),
+            "fines.daily_rate_cents" => {
+                self.fines.daily_rate_cents = parse(value, "an amount of cents")?
+            }
+            "fines.cap_cents" => self.fines.cap_cents = parse(value, "an amount of cents")?,
+            "purge.retention_days" => self.purge.retention_days = parse(value, "a number of days")?,
+            "features.run_migrations" => self.features.run_migrations = parse_bool(value)?,
+            "features.purge" => self.features.purge = parse_bool(value)?,
+            "features.swagger_ui" => self.features.swagger_ui = parse_bool(value)?,
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+        Ok(())
+    }
+
+    /// Problems with the settings once every layer is applied.
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.database.url.is_empty() {
+            errors.push("database.url is not set, set DATABASE_URL".to_string());
+        }
+        if self.auth.jwt_secret.is_empty() {
+            errors.push("auth.jwt_secret is not set, set JWT_SECRET".to_string());
+        }
+        if let Err(e) = EnvFilter::try_new(&self.log.level) {
+            errors.push(format!(
+                "log.level '{}' is not a valid filter: {e}",
+                self.log.level
+            ));
+        }
+        if !["text", "json"].contains(&self.log.format.as_str()) {
+            errors.push(format!(
+                "log.format '{}' is not text or json",
+                self.log.format
+            ));
+        }
+        if self.log.otlp_endpoint.is_some() && !cfg!(feature = "otlp") {
+            errors
+                .push("log.otlp_endpoint needs the server built with the otlp feature".to_string());
+        }
+        if self.database.pool_size == 0 {
+            errors.push("database.pool_size must be at least 1".to_string());
+        }
+        if self.database.async_pool_size == 0 {
+            errors.push("database.async_pool_size must be at least 1".to_string());
+        }
+        if self.database.timeout_seconds == 0 {
+            errors.push("database.timeout_seconds must be at least 1".to_string());
+        }
+        if self.auth.jwt_expiration_minutes <= 0 {
+            errors.push("auth.jwt_expiration_minutes must be at least 1".to_string());
+        }
+        if self.auth.admin_username.is_some() != self.auth.admin_password.is_some() {
+            errors.push("auth.admin_username and auth.admin_password go together".to_string());
+        }
+        if self.fines.daily_rate_cents < 0 || self.fines.cap_cents < 0 {
+            errors.push("fines must be non negative amounts of cents".to_string());
+        }
+        if self.purge.retention_days < 0 {
+            errors.push("purge.retention_days must not be negative".to_string());
+        }
+        errors
+    }
+
+    /// Give the routes the configuration as `web::Data`.
+    pub fn init(&self, config: &mut web::ServiceConfig) {
+        config.app_data(web::Data::new(self.clone()));
+    }
+}
+
+/// Pairs of `--flag value` or `--flag=value` in `args`.
+fn flags(args: &[String], errors: &mut Vec<String>) -> Vec<(String, String)> {
+    let mut flags = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            errors.push(format!("{arg}: unexpected argument"));
+            continue;
+        }
+        match arg.split_once('=') {
+            Some((flag, value)) => flags.push((flag.to_string(), value.to_string())),
+            None => match args.next() {
+                Some(value) => flags.push((arg.clone(), value.clone())),
+                None => errors.push(format!("{arg}: missing value")),
+            },
+        }
+    }
+    flags
+}
+
+/// Keep `config` as the configuration of the process, before anything reads it.
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("The configuration was already loaded");
+    }
+}
+
+/// The configuration of the process, loaded from the environment when [`init`] was not called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| Config::load(&[]).unwrap_or_else(|e| panic!("{e}")))
+}
@@ -1,23 +1,124 @@
-use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use diesel::pg::PgConnection;
 use diesel::r2d2::ConnectionManager;
+use diesel_async::pooled_connection::deadpool;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use diesel_async::AsyncPgConnection;
 use lazy_static::lazy_static;
+use serde::Serialize;
+use utoipa::ToSchema;
 
+use crate::config;
 use crate::error_handler::CustomError;
 
 type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 pub type DbConnection = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
 
+type AsyncPool = deadpool::Pool<AsyncPgConnection>;
+pub type AsyncDbConnection = deadpool::Object<AsyncPgConnection>;
+
 lazy_static! {
     static ref POOL: Pool = {
-        let db_url = env::var("DATABASE_URL").expect("Database url not set");
-        let manager = ConnectionManager::<PgConnection>::new(db_url);
-        Pool::new(manager).expect("Failed to create db pool")
+        let database = &config::get().database;
+        let manager = ConnectionManager::<PgConnection>::new(&database.url);
+        Pool::builder()
+            .max_size(database.pool_size)
+            .connection_timeout(timeout())
+            .build_unchecked(manager)
     };
+    /// The async pool of every worker, in the order they were opened, for the metrics.
+    static ref ASYNC_POOLS: Mutex<Vec<AsyncPool>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    // An async connection is driven by a task of the runtime that opened it, so each actix
+    // worker gets its own pool of `database.async_pool_size` connections rather than sharing
+    // them across runtimes.
+    static ASYNC_POOL: AsyncPool = {
+        let database = &config::get().database;
+        let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(&database.url);
+        let pool = AsyncPool::builder(manager)
+            .max_size(database.async_pool_size as usize)
+            .build()
+            .expect("Failed to create async db pool");
+        ASYNC_POOLS.lock().unwrap().push(pool.clone());
+        pool
+    };
+}
+
+/// Connections of a pool, and how many of them are idle.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolState {
+    pub name: &'static str,
+    pub size: u32,
+    pub max_size: u32,
+    pub idle: u32,
+}
+
+impl PoolState {
+    /// Every connection is open and in use, so the next query has to wait for one.
+    pub fn saturated(&self) -> bool {
+        self.size >= self.max_size && self.idle == 0
+    }
+}
+
+/// State of the blocking pool.
+pub fn pool_state() -> PoolState {
+    let state = POOL.state();
+    PoolState {
+        name: "blocking",
+        size: state.connections,
+        max_size: POOL.max_size(),
+        idle: state.idle_connections,
+    }
+}
+
+/// State of the async pool of the current worker.
+pub fn async_pool_state() -> PoolState {
+    ASYNC_POOL.with(async_state)
+}
+
+/// State of the async pool of every worker that opened one, in the order they opened it.
+pub fn async_pool_states() -> Vec<PoolState> {
+    ASYNC_POOLS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(async_state)
+        .collect()
+}
+
+fn async_state(pool: &AsyncPool) -> PoolState {
+    let status = pool.status();
+    PoolState {
+        name: "async",
+        size: status.size as u32,
+        max_size: status.max_size as u32,
+        idle: status.available as u32,
+    }
+}
+
+fn timeout() -> Duration {
+    Duration::from_secs(config::get().database.timeout_seconds)
 }
 
 pub fn connection() -> Result<DbConnection, CustomError> {
     POOL.get()
         .map_err(|e| CustomError::new(500, format!("Failed getting db connection: {e}")))
 }
+
+/// A connection to await queries on, without holding a thread of the blocking pool.
+pub async fn async_connection() -> Result<AsyncDbConnection, CustomError> {
+    let pool = ASYNC_POOL.with(AsyncPool::clone);
+    match tokio::time::timeout(timeout(), pool.get()).await {
+        Ok(conn) => {
+            conn.map_err(|e| CustomError::new(500, format!("Failed getting db connection: {e}")))
+        }
+        Err(_) => Err(CustomError::new(
+            500,
+            "Failed getting db connection: timed out".to_string(),
+        )),
+    }
+}
@@ -6,6 +6,9 @@ use serde::Serialize;
 use serde_json::json;
 use std::fmt;
 
+use crate::metrics;
+use crate::telemetry;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CustomError {
     pub error_status_code: u16,
@@ -27,6 +30,8 @@ impl fmt::Display for CustomError {
     }
 }
 
+impl std::error::Error for CustomError {}
+
 impl From<DieselError> for CustomError {
     fn from(error: DieselError) -> CustomError {
         match error {
@@ -39,18 +44,42 @@ impl From<DieselError> for CustomError {
     }
 }
 
+impl From<rusqlite::Error> for CustomError {
+    fn from(error: rusqlite::Error) -> CustomError {
+        match error {
+            rusqlite::Error::SqliteFailure(err, message)
+                if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                CustomError::new(409, message.unwrap_or_else(|| err.to_string()))
+            }
+            rusqlite::Error::QueryReturnedNoRows => {
+                CustomError::new(404, "The record not found".to_string())
+            }
+            err => CustomError::new(500, format!("Unknown SQLite error: {err}")),
+        }
+    }
+}
+
 impl ResponseError for CustomError {
     fn error_response(&self) -> HttpResponse {
         let status_code = match StatusCode::from_u16(self.error_status_code) {
             Ok(status_code) => status_code,
             Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
+        metrics::observe_error(status_code.as_u16());
 
         let error_message = match status_code.as_u16() < 500 {
             true => self.error_message.clone(),
-            false => "Internal server error".to_string(),
+            false => {
+                tracing::error!(status = status_code.as_u16(), "{}", self.error_message);
+                "Internal server error".to_string()
+            }
         };
 
-        HttpResponse::build(status_code).json(json!({ "Err": error_message }))
+        match telemetry::request_id() {
+            Some(request_id) => HttpResponse::build(status_code)
+                .json(json!({ "Err": error_message, "request_id": request_id })),
+            None => HttpResponse::build(status_code).json(json!({ "Err": error_message })),
+        }
     }
 }
@@ -0,0 +1,244 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::sum;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::schema::{fines, members, payments};
+
+/// Daily fine and cap charged for overdue loans, in cents.
+///
+/// Each library sets its own values through the `fines` settings of its configuration.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FinePolicy {
+    pub daily_rate_cents: i32,
+    pub cap_cents: i32,
+}
+
+impl Default for FinePolicy {
+    fn default() -> Self {
+        FinePolicy {
+            daily_rate_cents: 25,
+            cap_cents: 1000,
+        }
+    }
+}
+
+impl FinePolicy {
+    /// Fine for a loan returned at `returned_at` that was due at `due_at`. Every started day
+    /// counts as a full day and the result never goes over the cap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use lib_api::fines::FinePolicy;
+    ///
+    /// let policy = FinePolicy { daily_rate_cents: 25, cap_cents: 100 };
+    /// let due_at = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    ///
+    /// assert_eq!(0, policy.amount(due_at, due_at));
+    /// assert_eq!(50, policy.amount(due_at, due_at + chrono::Duration::hours(25)));
+    /// assert_eq!(100, policy.amount(due_at, due_at + chrono::Duration::days(30)));
+    /// ```
+    pub fn amount(&self, due_at: NaiveDateTime, returned_at: NaiveDateTime) -> i32 {
+        let overdue = (returned_at - due_at).num_seconds();
+        if overdue <= 0 {
+            return 0;
+        }
+        let days = (overdue + 86_399) / 86_400;
+        (days * i64::from(self.daily_rate_cents)).min(i64::from(self.cap_cents)) as i32
+    }
+}
+
+#[derive(Serialize, Deserialize, Queryable, ToSchema)]
+#[diesel(table_name = fines)]
+pub struct Fines {
+    pub id: i32,
+    pub member_id: i32,
+    pub loan_id: Option<i32>,
+    pub amount_cents: i32,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+    pub waived_at: Option<NaiveDateTime>,
+    pub waive_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Payment {
+    pub amount_cents: i32,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, ToSchema)]
+#[diesel(table_name = payments)]
+pub struct Payments {
+    pub id: i32,
+    pub member_id: i32,
+    pub amount_cents: i32,
+    pub note: Option<String>,
+    pub paid_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Waiver {
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Balance {
+    pub member_id: i32,
+    /// Charged fines that have not been waived.
+    pub fines_cents: i64,
+    pub payments_cents: i64,
+    /// What the member still owes, negative when they have credit.
+    pub balance_cents: i64,
+    pub fines: Vec<Fines>,
+    pub payments: Vec<Payments>,
+}
+
+impl Fines {
+    pub fn find(id: i32) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        let fine = fines::table
+            .filter(fines::id.eq(id))
+            .first(&mut conn)
+            .optional()?
+            .ok_or_else(|| CustomError::new(404, format!("Fine {id} not found")))?;
+        Ok(fine)
+    }
+
+    /// Charge the overdue fine of a returned loan, if any. Meant to run inside the transaction
+    /// that closes the loan.
+    pub fn charge_overdue(
+        conn: &mut PgConnection,
+        member_id: i32,
+        loan_id: i32,
+        due_at: NaiveDateTime,
+        returned_at: NaiveDateTime,
+        policy: &FinePolicy,
+    ) -> Result<Option<Self>, CustomError> {
+        let amount_cents = policy.amount(due_at, returned_at);
+        if amount_cents == 0 {
+            return Ok(None);
+        }
+
+        let fine = diesel::insert_into(fines::table)
+            .values((
+                fines::member_id.eq(member_id),
+                fines::loan_id.eq(loan_id),
+                fines::amount_cents.eq(amount_cents),
+                fines::reason.eq(format!("Loan {loan_id} returned late")),
+                fines::created_at.eq(returned_at),
+            ))
+            .get_result(conn)?;
+        Ok(Some(fine))
+    }
+
+    pub fn waive(id: i32, waiver: Waiver) -> Result<Self, CustomError> {
+        let reason = waiver.reason.trim().to_string();
+        if reason.is_empty() {
+            return Err(CustomError::new(
+                400,
+                "a reason is required to waive a fine".to_string(),
+            ));
+        }
+
+        let mut conn = db::connection()?;
+        conn.transaction(|conn| {
+            let fine: Fines = fines::table
+                .filter(fines::id.eq(id))
+                .for_update()
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| CustomError::new(404, format!("Fine {id} not found")))?;
+            if fine.waived_at.is_some() {
+                return Err(CustomError::new(
+                    409,
+                    format!("Fine {id} has already been waived"),
+                ));
+            }
+
+            let fine = diesel::update(fines::table.filter(fines::id.eq(id)))
+                .set((
+                    fines::waived_at.eq(Utc::now().naive_utc()),
+                    fines::waive_reason.eq(reason),
+                ))
+                .get_result(conn)?;
+            Ok(fine)
+        })
+    }
+}
+
+impl Payments {
+    pub fn create(member_id: i32, payment: Payment) -> Result<Self, CustomError> {
+        if payment.amount_cents <= 0 {
+            return Err(CustomError::new(
+                400,
+                "amount_cents must be greater than 0".to_string(),
+            ));
+        }
+
+        let mut conn = db::connection()?;
+        find_member(&mut conn, member_id)?;
+        let payment = diesel::insert_into(payments::table)
+            .values((
+                payments::member_id.eq(member_id),
+                payments::amount_cents.eq(payment.amount_cents),
+                payments::note.eq(payment.note),
+                payments::paid_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_result(&mut conn)?;
+        Ok(payment)
+    }
+}
+
+impl Balance {
+    pub fn find(member_id: i32) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        find_member(&mut conn, member_id)?;
+
+        let fines_cents: Option<i64> = fines::table
+            .filter(fines::member_id.eq(member_id))
+            .filter(fines::waived_at.is_null())
+            .select(sum(fines::amount_cents))
+            .first(&mut conn)?;
+        let payments_cents: Option<i64> = payments::table
+            .filter(payments::member_id.eq(member_id))
+            .select(sum(payments::amount_cents))
+            .first(&mut conn)?;
+        let fines = fines::table
+            .filter(fines::member_id.eq(member_id))
+            .order(fines::created_at.desc())
+            .load::<Fines>(&mut conn)?;
+        let payments = payments::table
+            .filter(payments::member_id.eq(member_id))
+            .order(payments::paid_at.desc())
+            .load::<Payments>(&mut conn)?;
+
+        let fines_cents = fines_cents.unwrap_or(0);
+        let payments_cents = payments_cents.unwrap_or(0);
+        Ok(Balance {
+            member_id,
+            fines_cents,
+            payments_cents,
+            balance_cents: fines_cents - payments_cents,
+            fines,
+            payments,
+        })
+    }
+}
+
+fn find_member(conn: &mut PgConnection, member_id: i32) -> Result<(), CustomError> {
+    members::table
+        .filter(members::id.eq(member_id))
+        .select(members::id)
+        .first::<i32>(conn)
+        .optional()?
+        .ok_or_else(|| CustomError::new(404, format!("Member {member_id} not found")))?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use actix_web::{get, post, web, HttpResponse};
+
+use crate::auth::RequireRole;
+use crate::error_handler::CustomError;
+use crate::fines::{Balance, Fines, Payment, Payments, Waiver};
+use crate::utils::response;
+
+#[utoipa::path(
+    get,
+    path = "/members/{id}/balance",
+    responses(
+        (status = 200, description = "Get the fines, payments and balance of a member", body = inline(Balance)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/members/{id}/balance", wrap = "RequireRole::staff()")]
+async fn balance(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let balance = Balance::find(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(balance))
+}
+
+#[utoipa::path(
+    post,
+    path = "/members/{id}/payments",
+    request_body = Payment,
+    responses(
+        (status = 200, description = "Record a payment of a member", body = inline(Payments)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/members/{id}/payments", wrap = "RequireRole::staff()")]
+async fn pay(id: web::Path<i32>, payment: web::Json<Payment>) -> Result<HttpResponse, CustomError> {
+    let payment = Payments::create(id.into_inner(), payment.into_inner())?;
+    Ok(HttpResponse::Ok().json(payment))
+}
+
+#[utoipa::path(
+    get,
+    path = "/fines/{id}",
+    responses(
+        (status = 200, description = "Get a fine identified with id", body = inline(Fines)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/fines/{id}", wrap = "RequireRole::staff()")]
+async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let fine = Fines::find(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(fine))
+}
+
+#[utoipa::path(
+    post,
+    path = "/fines/{id}/waive",
+    request_body = Waiver,
+    responses(
+        (status = 200, description = "Waive a fine with a reason", body = inline(Fines)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Fine already waived", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/fines/{id}/waive", wrap = "RequireRole::staff()")]
+async fn waive(id: web::Path<i32>, waiver: web::Json<Waiver>) -> Result<HttpResponse, CustomError> {
+    let fine = Fines::waive(id.into_inner(), waiver.into_inner())?;
+    Ok(HttpResponse::Ok().json(fine))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(balance);
+    config.service(pay);
+    config.service(find);
+    config.service(waive);
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config;
+use crate::db::{self, PoolState};
+use crate::error_handler::CustomError;
+use crate::repository;
+use crate::telemetry;
+use crate::utils::migrate::{self, Status};
+
+/// How long the readiness check waits for a database connection.
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    static ref STARTED_AT: Instant = Instant::now();
+}
+
+/// Start counting the uptime, when the routes are set up.
+pub(crate) fn start() {
+    lazy_static::initialize(&STARTED_AT);
+}
+
+/// Build and uptime of the running server.
+#[derive(Serialize, ToSchema)]
+pub struct Health {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub uptime_seconds: u64,
+}
+
+impl Health {
+    pub fn current() -> Self {
+        Health {
+            status: "ok",
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("GIT_SHA"),
+            uptime_seconds: STARTED_AT.elapsed().as_secs(),
+        }
+    }
+}
+
+/// Whether the server can take traffic: the database answers, every migration is applied and
+/// the pools have a free connection. With the SQLite backend there is no Postgres to check, so
+/// it is always ready.
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    /// `ready`, or `degraded` when one of the checks fails.
+    pub status: &'static str,
+    /// `ok`, or why no connection could be had.
+    pub database: String,
+    pub pending_migrations: Vec<String>,
+    /// Applied to the database but not built into this server.
+    pub missing_migrations: Vec<String>,
+    pub pools: Vec<PoolState>,
+}
+
+impl Readiness {
+    pub async fn check() -> Self {
+        if repository::sqlite_path(&config::get().database.url).is_some() {
+            return Readiness {
+                status: "ready",
+                database: "ok".to_string(),
+                pending_migrations: Vec::new(),
+                missing_migrations: Vec::new(),
+                pools: Vec::new(),
+            };
+        }
+        let statuses = telemetry::block(|| {
+            let mut conn = db::connection()?;
+            migrate::status(&mut conn)
+        });
+        let statuses = match tokio::time::timeout(READY_TIMEOUT, statuses).await {
+            Ok(Ok(statuses)) => statuses,
+            Ok(Err(e)) => Err(CustomError::new(500, e.to_string())),
+            Err(_) => Err(CustomError::new(
+                503,
+                "Timed out getting db connection".to_string(),
+            )),
+        };
+
+        let (mut pending_migrations, mut missing_migrations) = (Vec::new(), Vec::new());
+        let database = match statuses {
+            Ok(statuses) => {
+                for (name, status) in statuses {
+                    match status {
+                        Status::Applied => {}
+                        Status::Pending => pending_migrations.push(name),
+                        Status::Missing => missing_migrations.push(name),
+                    }
+                }
+                "ok".to_string()
+            }
+            Err(e) => e.to_string(),
+        };
+        let pools = vec![db::pool_state(), db::async_pool_state()];
+
+        let ready = database == "ok"
+            && pending_migrations.is_empty()
+            && missing_migrations.is_empty()
+            && !pools.iter().any(PoolState::saturated);
+        Readiness {
+            status: if ready { "ready" } else { "degraded" },
+            database,
+            pending_migrations,
+            missing_migrations,
+            pools,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.status == "ready"
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+
+use crate::health::{self, Health, Readiness};
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    security(()),
+    responses(
+        (status = 200, description = "Version, commit and uptime of the server", body = inline(Health))
+    )
+)]
+#[get("/health")]
+async fn status() -> HttpResponse {
+    HttpResponse::Ok().json(Health::current())
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    security(()),
+    responses(
+        (status = 200, description = "The server is running")
+    )
+)]
+#[get("/health/live")]
+async fn live() -> HttpResponse {
+    HttpResponse::Ok().json(json!({"status": "alive"}))
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    security(()),
+    responses(
+        (status = 200, description = "The server can take traffic", body = inline(Readiness)),
+        (status = 503, description = "The database is unreachable, a migration is not applied or a pool is saturated", body = inline(Readiness))
+    )
+)]
+#[get("/health/ready")]
+async fn ready() -> HttpResponse {
+    let readiness = Readiness::check().await;
+    match readiness.is_ready() {
+        true => HttpResponse::Ok().json(readiness),
+        false => HttpResponse::ServiceUnavailable().json(readiness),
+    }
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    health::start();
+    config.service(status);
+    config.service(live);
+    config.service(ready);
+}
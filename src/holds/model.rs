@@ -0,0 +1,309 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::items::{self, Items};
+use crate::schema::{books, holds, members};
+
+/// Days a member has to pick up a copy once their hold becomes ready.
+pub const HOLD_READY_DAYS: i64 = 3;
+
+pub mod status {
+    pub const WAITING: &str = "waiting";
+    pub const READY: &str = "ready";
+    pub const FULFILLED: &str = "fulfilled";
+    pub const CANCELLED: &str = "cancelled";
+    pub const EXPIRED: &str = "expired";
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Hold {
+    pub member_id: i32,
+    pub book_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Queryable, ToSchema)]
+#[diesel(table_name = holds)]
+pub struct Holds {
+    pub id: i32,
+    pub member_id: i32,
+    pub book_id: i32,
+    pub status: String,
+    pub placed_at: NaiveDateTime,
+    pub ready_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct HoldPosition {
+    pub id: i32,
+    pub book_id: i32,
+    pub status: String,
+    /// Place in the book queue: `0` while a copy waits for pick up, `None` once the hold has
+    /// left the queue.
+    pub position: Option<i64>,
+}
+
+impl Holds {
+    pub fn find_all() -> Result<Vec<Self>, CustomError> {
+        let mut conn = db::connection()?;
+        Self::expire(&mut conn)?;
+        let holds = holds::table.load::<Holds>(&mut conn)?;
+        Ok(holds)
+    }
+
+    pub fn find(id: i32) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        Self::expire(&mut conn)?;
+        Self::find_with(&mut conn, id)
+    }
+
+    /// Active holds of a book in the order they will be served.
+    pub fn queue(book_id: i32) -> Result<Vec<Self>, CustomError> {
+        let mut conn = db::connection()?;
+        Self::expire(&mut conn)?;
+        let holds = holds::table
+            .filter(holds::book_id.eq(book_id))
+            .filter(holds::status.eq_any([status::READY, status::WAITING]))
+            .order((
+                holds::ready_at.asc(),
+                holds::placed_at.asc(),
+                holds::id.asc(),
+            ))
+            .load::<Holds>(&mut conn)?;
+        Ok(holds)
+    }
+
+    pub fn position(id: i32) -> Result<HoldPosition, CustomError> {
+        let mut conn = db::connection()?;
+        Self::expire(&mut conn)?;
+        let hold = Self::find_with(&mut conn, id)?;
+
+        let position = match hold.status.as_str() {
+            status::READY => Some(0),
+            status::WAITING => {
+                let ahead: i64 = holds::table
+                    .filter(holds::book_id.eq(hold.book_id))
+                    .filter(holds::status.eq(status::WAITING))
+                    .filter(
+                        holds::placed_at.lt(hold.placed_at).or(holds::placed_at
+                            .eq(hold.placed_at)
+                            .and(holds::id.lt(hold.id))),
+                    )
+                    .count()
+                    .get_result(&mut conn)?;
+                Some(ahead + 1)
+            }
+            _ => None,
+        };
+
+        Ok(HoldPosition {
+            id: hold.id,
+            book_id: hold.book_id,
+            status: hold.status,
+            position,
+        })
+    }
+
+    /// Queue `hold.member_id` for `hold.book_id`. Holds are only accepted while the book has no
+    /// copies left on the shelf.
+    pub fn place(hold: Hold) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        Self::expire(&mut conn)?;
+        conn.transaction(|conn| {
+            members::table
+                .filter(members::id.eq(hold.member_id))
+                .filter(members::deleted_at.is_null())
+                .select(members::id)
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or_else(|| {
+                    CustomError::new(404, format!("Member {} not found", hold.member_id))
+                })?;
+
+            let copies_available = books::table
+                .filter(books::id.eq(hold.book_id))
+                .filter(books::deleted_at.is_null())
+                .select(books::copies_available)
+                .for_update()
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or_else(|| CustomError::new(404, format!("Book {} not found", hold.book_id)))?;
+
+            if copies_available > 0 {
+                return Err(CustomError::new(
+                    409,
+                    format!(
+                        "Book {} has copies available, check it out instead",
+                        hold.book_id
+                    ),
+                ));
+            }
+
+            let active = holds::table
+                .filter(holds::member_id.eq(hold.member_id))
+                .filter(holds::book_id.eq(hold.book_id))
+                .filter(holds::status.eq_any([status::WAITING, status::READY]))
+                .count()
+                .get_result::<i64>(conn)?;
+            if active > 0 {
+                return Err(CustomError::new(
+                    409,
+                    format!(
+                        "Member {} already has a hold on book {}",
+                        hold.member_id, hold.book_id
+                    ),
+                ));
+            }
+
+            let hold = diesel::insert_into(holds::table)
+                .values((
+                    holds::member_id.eq(hold.member_id),
+                    holds::book_id.eq(hold.book_id),
+                    holds::status.eq(status::WAITING),
+                    holds::placed_at.eq(Utc::now().naive_utc()),
+                ))
+                .get_result(conn)?;
+            Ok(hold)
+        })
+    }
+
+    /// Cancel a waiting or ready hold. A copy set aside for a ready hold moves on to the next
+    /// member in the queue.
+    pub fn cancel(id: i32) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        Self::expire(&mut conn)?;
+        conn.transaction(|conn| {
+            let hold = Self::lock(conn, id)?;
+            if hold.status != status::WAITING && hold.status != status::READY {
+                return Err(CustomError::new(
+                    409,
+                    format!("Hold {id} is already {}", hold.status),
+                ));
+            }
+
+            let cancelled = diesel::update(holds::table.filter(holds::id.eq(id)))
+                .set(holds::status.eq(status::CANCELLED))
+                .get_result::<Holds>(conn)?;
+
+            if hold.status == status::READY {
+                Self::release_copy(conn, hold.book_id, None)?;
+            }
+            Ok(cancelled)
+        })
+    }
+
+    /// Hand a copy to the head of the book queue.
+    ///
+    /// `item_id` is the copy that just came back from a loan, `None` stands for one of the copies
+    /// set aside for a hold that left the queue. Returns `true` when a waiting hold took the
+    /// copy, which then stays on the hold shelf; otherwise the copy goes back to the shelf.
+    pub fn release_copy(
+        conn: &mut PgConnection,
+        book_id: i32,
+        item_id: Option<i32>,
+    ) -> Result<bool, CustomError> {
+        let next = holds::table
+            .filter(holds::book_id.eq(book_id))
+            .filter(holds::status.eq(status::WAITING))
+            .order((holds::placed_at.asc(), holds::id.asc()))
+            .select(holds::id)
+            .for_update()
+            .first::<i32>(conn)
+            .optional()?;
+
+        match next {
+            Some(hold_id) => {
+                let now = Utc::now().naive_utc();
+                diesel::update(holds::table.filter(holds::id.eq(hold_id)))
+                    .set((
+                        holds::status.eq(status::READY),
+                        holds::ready_at.eq(now),
+                        holds::expires_at.eq(now + Duration::days(HOLD_READY_DAYS)),
+                    ))
+                    .execute(conn)?;
+                if let Some(item_id) = item_id {
+                    Items::set_status(conn, item_id, items::status::ON_HOLD)?;
+                }
+                Ok(true)
+            }
+            None => {
+                match item_id {
+                    Some(item_id) => {
+                        Items::set_status(conn, item_id, items::status::AVAILABLE)?;
+                    }
+                    None => {
+                        Items::take(
+                            conn,
+                            book_id,
+                            None,
+                            items::status::ON_HOLD,
+                            items::status::AVAILABLE,
+                        )?;
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// Mark the ready hold of `member_id` on `book_id` as picked up.
+    ///
+    /// Returns `true` when such a hold existed, meaning the copy to lend is one of the copies on
+    /// the hold shelf rather than on the open shelf.
+    pub fn fulfill(
+        conn: &mut PgConnection,
+        member_id: i32,
+        book_id: i32,
+    ) -> Result<bool, CustomError> {
+        let fulfilled = diesel::update(
+            holds::table
+                .filter(holds::member_id.eq(member_id))
+                .filter(holds::book_id.eq(book_id))
+                .filter(holds::status.eq(status::READY)),
+        )
+        .set(holds::status.eq(status::FULFILLED))
+        .execute(conn)?;
+        Ok(fulfilled > 0)
+    }
+
+    /// Expire ready holds whose pick up window has passed and pass their copies on.
+    pub fn expire(conn: &mut PgConnection) -> Result<usize, CustomError> {
+        conn.transaction(|conn| {
+            let expired = diesel::update(
+                holds::table
+                    .filter(holds::status.eq(status::READY))
+                    .filter(holds::expires_at.lt(Utc::now().naive_utc())),
+            )
+            .set(holds::status.eq(status::EXPIRED))
+            .returning(holds::book_id)
+            .get_results::<i32>(conn)?;
+
+            for book_id in &expired {
+                Self::release_copy(conn, *book_id, None)?;
+            }
+            Ok(expired.len())
+        })
+    }
+
+    fn find_with(conn: &mut PgConnection, id: i32) -> Result<Self, CustomError> {
+        holds::table
+            .filter(holds::id.eq(id))
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| CustomError::new(404, format!("Hold {id} not found")))
+    }
+
+    fn lock(conn: &mut PgConnection, id: i32) -> Result<Self, CustomError> {
+        holds::table
+            .filter(holds::id.eq(id))
+            .for_update()
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| CustomError::new(404, format!("Hold {id} not found")))
+    }
+}
@@ -0,0 +1,106 @@
+use actix_web::{get, post, web, HttpResponse};
+
+use crate::auth::RequireRole;
+use crate::error_handler::CustomError;
+use crate::holds::{Hold, HoldPosition, Holds};
+use crate::telemetry;
+use crate::utils::response;
+
+#[utoipa::path(
+    get,
+    path = "/holds",
+    responses(
+        (status = 200, description = "Get all holds", body = inline(response::HoldsResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/holds", wrap = "RequireRole::staff()")]
+async fn find_all() -> Result<HttpResponse, CustomError> {
+    let holds = telemetry::block(Holds::find_all).await.unwrap();
+    Ok(HttpResponse::Ok().json(holds))
+}
+
+#[utoipa::path(
+    get,
+    path = "/holds/{id}",
+    responses(
+        (status = 200, description = "Get a hold identified with id", body = inline(Holds)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/holds/{id}", wrap = "RequireRole::staff()")]
+async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let hold = Holds::find(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[utoipa::path(
+    get,
+    path = "/holds/{id}/position",
+    responses(
+        (status = 200, description = "Get the place of a hold in its book queue", body = inline(HoldPosition)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/holds/{id}/position", wrap = "RequireRole::staff()")]
+async fn position(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let position = Holds::position(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(position))
+}
+
+#[utoipa::path(
+    get,
+    path = "/books/{id}/holds",
+    responses(
+        (status = 200, description = "Get the active holds of a book in queue order", body = inline(response::HoldsResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/books/{id}/holds", wrap = "RequireRole::staff()")]
+async fn queue(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let book_id = id.into_inner();
+    let holds = telemetry::block(move || Holds::queue(book_id))
+        .await
+        .unwrap();
+    Ok(HttpResponse::Ok().json(holds))
+}
+
+#[utoipa::path(
+    post,
+    path = "/holds",
+    request_body = Hold,
+    responses(
+        (status = 200, description = "Queue a member for a book without copies available", body = inline(Holds)),
+        (status = 404, description = "Member or book not found", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Book available or hold already placed", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/holds", wrap = "RequireRole::staff()")]
+async fn place(hold: web::Json<Hold>) -> Result<HttpResponse, CustomError> {
+    let hold = Holds::place(hold.into_inner())?;
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[utoipa::path(
+    post,
+    path = "/holds/{id}/cancel",
+    responses(
+        (status = 200, description = "Cancel a hold", body = inline(Holds)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Hold no longer active", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/holds/{id}/cancel", wrap = "RequireRole::staff()")]
+async fn cancel(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let hold = Holds::cancel(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(find_all);
+    config.service(find);
+    config.service(position);
+    config.service(queue);
+    config.service(place);
+    config.service(cancel);
+}
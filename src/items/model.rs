@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::holds::Holds;
+use crate::schema::{books, items};
+use crate::utils::check::{self, filter_int, filter_text};
+use crate::utils::pagination::Pagination;
+
+pub mod status {
+    pub const AVAILABLE: &str = "available";
+    pub const ON_LOAN: &str = "on_loan";
+    pub const ON_HOLD: &str = "on_hold";
+    pub const DAMAGED: &str = "damaged";
+    pub const LOST: &str = "lost";
+    pub const WITHDRAWN: &str = "withdrawn";
+}
+
+/// Statuses staff can set by hand, `on_loan` and `on_hold` follow loans and holds.
+pub const EDITABLE_STATUSES: [&str; 4] = [
+    status::AVAILABLE,
+    status::DAMAGED,
+    status::LOST,
+    status::WITHDRAWN,
+];
+
+pub const CONDITIONS: [&str; 4] = ["new", "good", "fair", "poor"];
+
+/// Fields accepted by the `sort` param of the item listings.
+pub const ITEMS_SORTABLE: [&str; 6] = [
+    "id",
+    "barcode",
+    "book_id",
+    "status",
+    "shelf_location",
+    "acquired_at",
+];
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Item {
+    pub barcode: String,
+    pub book_id: i32,
+    /// available (default), damaged, lost or withdrawn.
+    pub status: Option<String>,
+    /// new, good (default), fair or poor.
+    pub condition: Option<String>,
+    pub shelf_location: Option<String>,
+    /// Defaults to now.
+    pub acquired_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, ToSchema)]
+#[diesel(table_name = items)]
+pub struct Items {
+    pub id: i32,
+    pub barcode: String,
+    pub book_id: i32,
+    pub status: String,
+    pub condition: String,
+    pub shelf_location: Option<String>,
+    pub acquired_at: NaiveDateTime,
+}
+
+impl Items {
+    /// Page of the items matching `params`, along with how many items match in total.
+    pub fn get(
+        params: HashMap<String, String>,
+        pagination: Pagination,
+    ) -> Result<(Vec<Self>, i64), CustomError> {
+        let mut conn = db::connection()?;
+        let total = Items::filtered(&params)?
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = Items::filtered(&params)?;
+        for sort in &pagination.sort {
+            query = match (sort.field.as_str(), sort.descending) {
+                ("id", false) => query.then_order_by(items::id.asc()),
+                ("id", true) => query.then_order_by(items::id.desc()),
+                ("barcode", false) => query.then_order_by(items::barcode.asc()),
+                ("barcode", true) => query.then_order_by(items::barcode.desc()),
+                ("book_id", false) => query.then_order_by(items::book_id.asc()),
+                ("book_id", true) => query.then_order_by(items::book_id.desc()),
+                ("status", false) => query.then_order_by(items::status.asc()),
+                ("status", true) => query.then_order_by(items::status.desc()),
+                ("shelf_location", false) => query.then_order_by(items::shelf_location.asc()),
+                ("shelf_location", true) => query.then_order_by(items::shelf_location.desc()),
+                ("acquired_at", false) => query.then_order_by(items::acquired_at.asc()),
+                ("acquired_at", true) => query.then_order_by(items::acquired_at.desc()),
+                _ => query,
+            };
+        }
+
+        let items = query
+            .then_order_by(items::id.asc())
+            .limit(pagination.limit)
+            .offset(pagination.offset)
+            .load::<Items>(&mut conn)?;
+        Ok((items, total))
+    }
+
+    fn filtered(
+        params: &HashMap<String, String>,
+    ) -> Result<items::BoxedQuery<'_, Pg>, CustomError> {
+        let mut query = items::table.into_boxed();
+
+        for (key, value) in params {
+            let (field, operator) = check::parse_filter(key)?;
+            query = match field {
+                "id" => filter_int!(query, items::id, operator, check::validate_int(value)?),
+                "book_id" => {
+                    filter_int!(query, items::book_id, operator, check::validate_int(value)?)
+                }
+                "barcode" => filter_text!(query, items::barcode, operator, value),
+                "status" => filter_text!(query, items::status, operator, value),
+                "condition" => filter_text!(query, items::condition, operator, value),
+                "shelf_location" => filter_text!(query, items::shelf_location, operator, value),
+                _ => {
+                    return Err(CustomError::new(
+                        400,
+                        format!("the parameter '{key}' is incorrect"),
+                    ))
+                }
+            };
+        }
+
+        Ok(query)
+    }
+
+    pub fn find(id: i32) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        let item = items::table
+            .filter(items::id.eq(id))
+            .first(&mut conn)
+            .optional()?
+            .ok_or_else(|| CustomError::new(404, format!("Item {id} not found")))?;
+        Ok(item)
+    }
+
+    /// Add a copy of a book. An available copy goes to the head of the book queue first, like a
+    /// returned one.
+    pub fn create(item: Item) -> Result<Self, CustomError> {
+        item.validate()?;
+        if let Some(status) = item
+            .status
+            .as_deref()
+            .filter(|s| !EDITABLE_STATUSES.contains(s))
+        {
+            return Err(CustomError::new(
+                400,
+                format!("new items can not be {status}, loans and holds set it"),
+            ));
+        }
+        let mut conn = db::connection()?;
+        conn.transaction(|conn| {
+            Items::check_book(conn, item.book_id)?;
+            let item = diesel::insert_into(items::table)
+                .values((
+                    items::barcode.eq(item.barcode.trim()),
+                    items::book_id.eq(item.book_id),
+                    items::status.eq(item.status.as_deref().unwrap_or(status::AVAILABLE)),
+                    items::condition.eq(item.condition.as_deref().unwrap_or("good")),
+                    items::shelf_location.eq(&item.shelf_location),
+                    items::acquired_at
+                        .eq(item.acquired_at.unwrap_or_else(|| Utc::now().naive_utc())),
+                ))
+                .get_result(conn)?;
+            Items::shelve(conn, item)
+        })
+    }
+
+    /// Change the item `id`. Items on loan or on hold can be relabelled or moved, but their
+    /// status and book only change through their loan or hold. A copy that becomes available
+    /// goes to the head of the book queue first.
+    pub fn update(id: i32, item: Item) -> Result<Self, CustomError> {
+        item.validate()?;
+        let mut conn = db::connection()?;
+        conn.transaction(|conn| {
+            let current = Items::lock(conn, id)?;
+            let status = item.status.as_deref().unwrap_or(&current.status);
+            let circulating = !EDITABLE_STATUSES.contains(&current.status.as_str());
+            if status != current.status && (circulating || !EDITABLE_STATUSES.contains(&status)) {
+                return Err(CustomError::new(
+                    409,
+                    format!(
+                        "Item {id} can not go from {} to {status}, loans and holds set it",
+                        current.status
+                    ),
+                ));
+            }
+            if circulating && item.book_id != current.book_id {
+                return Err(CustomError::new(
+                    409,
+                    format!(
+                        "Item {id} is {}, it can not move to another book",
+                        current.status
+                    ),
+                ));
+            }
+            Items::check_book(conn, item.book_id)?;
+
+            let item = diesel::update(items::table.filter(items::id.eq(id)))
+                .set((
+                    items::barcode.eq(item.barcode.trim()),
+                    items::book_id.eq(item.book_id),
+                    items::status.eq(status),
+                    items::condition.eq(item.condition.as_deref().unwrap_or(&current.condition)),
+                    items::shelf_location.eq(&item.shelf_location),
+                    items::acquired_at.eq(item.acquired_at.unwrap_or(current.acquired_at)),
+                ))
+                .get_result(conn)?;
+            Items::shelve(conn, item)
+        })
+    }
+
+    /// Delete the item `id`, unless it is out on loan or set aside for a hold.
+    pub fn delete(id: i32) -> Result<usize, CustomError> {
+        let mut conn = db::connection()?;
+        conn.transaction(|conn| {
+            let item = Items::lock(conn, id)?;
+            if !EDITABLE_STATUSES.contains(&item.status.as_str()) {
+                return Err(CustomError::new(
+                    409,
+                    format!("Item {id} is {}, it can not be deleted", item.status),
+                ));
+            }
+            let res = diesel::delete(items::table.filter(items::id.eq(id))).execute(conn)?;
+            Ok(res)
+        })
+    }
+
+    /// Lock a copy of `book_id` with the status `from` and give it the status `to`.
+    ///
+    /// With a `barcode` only that copy is taken. Without one the first copy with the status
+    /// `from` is taken, skipping the ones other transactions are already taking. Returns `None`
+    /// when there is no such copy.
+    pub fn take(
+        conn: &mut PgConnection,
+        book_id: i32,
+        barcode: Option<&str>,
+        from: &str,
+        to: &str,
+    ) -> Result<Option<Self>, CustomError> {
+        let query = items::table
+            .filter(items::book_id.eq(book_id))
+            .filter(items::status.eq(from))
+            .select(items::id)
+            .order(items::id.asc());
+        let id = match barcode {
+            Some(barcode) => query
+                .filter(items::barcode.eq(barcode))
+                .for_update()
+                .first::<i32>(conn)
+                .optional()?,
+            None => query
+                .for_update()
+                .skip_locked()
+                .first::<i32>(conn)
+                .optional()?,
+        };
+
+        match id {
+            Some(id) => Ok(Some(Items::set_status(conn, id, to)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_status(conn: &mut PgConnection, id: i32, status: &str) -> Result<Self, CustomError> {
+        let item = diesel::update(items::table.filter(items::id.eq(id)))
+            .set(items::status.eq(status))
+            .get_result(conn)?;
+        Ok(item)
+    }
+
+    /// Hand `item` to the oldest waiting hold of its book when it is available, so a walk-in
+    /// loan can not take it first.
+    fn shelve(conn: &mut PgConnection, item: Items) -> Result<Self, CustomError> {
+        if item.status != status::AVAILABLE {
+            return Ok(item);
+        }
+        match Holds::release_copy(conn, item.book_id, Some(item.id))? {
+            true => Items::lock(conn, item.id),
+            false => Ok(item),
+        }
+    }
+
+    fn check_book(conn: &mut PgConnection, book_id: i32) -> Result<(), CustomError> {
+        books::table
+            .filter(books::id.eq(book_id))
+            .filter(books::deleted_at.is_null())
+            .select(books::id)
+            .first::<i32>(conn)
+            .optional()?
+            .ok_or_else(|| CustomError::new(404, format!("Book {book_id} not found")))?;
+        Ok(())
+    }
+
+    fn lock(conn: &mut PgConnection, id: i32) -> Result<Self, CustomError> {
+        items::table
+            .filter(items::id.eq(id))
+            .for_update()
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| CustomError::new(404, format!("Item {id} not found")))
+    }
+}
+
+impl Item {
+    pub fn validate(&self) -> Result<(), CustomError> {
+        if self.barcode.trim().is_empty() {
+            return Err(CustomError::new(
+                400,
+                "barcode can not be empty".to_string(),
+            ));
+        }
+        if let Some(status) = &self.status {
+            let known = EDITABLE_STATUSES.contains(&status.as_str())
+                || [status::ON_LOAN, status::ON_HOLD].contains(&status.as_str());
+            if !known {
+                return Err(CustomError::new(
+                    400,
+                    format!(
+                        "unknown status '{status}', use {}",
+                        EDITABLE_STATUSES.join(", ")
+                    ),
+                ));
+            }
+        }
+        if let Some(condition) = &self.condition {
+            if !CONDITIONS.contains(&condition.as_str()) {
+                return Err(CustomError::new(
+                    400,
+                    format!(
+                        "unknown condition '{condition}', use {}",
+                        CONDITIONS.join(", ")
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+use crate::auth::RequireRole;
+use crate::error_handler::CustomError;
+use crate::items::{Item, Items, ITEMS_SORTABLE};
+use crate::telemetry;
+use crate::utils::check;
+use crate::utils::pagination::{Page, Pagination};
+use crate::utils::response;
+
+#[utoipa::path(
+    get,
+    path = "/items",
+    responses(
+        (status = 200, description = "Get the copies of books filtered with url params", body = inline(response::ItemsResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("id" = Option<i32>, Query, description = "Item database id, also as id__ne, __gt, __gte, __lt or __lte"),
+        ("book_id" = Option<i32>, Query, description = "Book of the items, also as book_id__ne, __gt, __gte, __lt or __lte"),
+        ("barcode" = Option<String>, Query, description = "Item barcode, also as barcode__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("status" = Option<String>, Query, description = "available, on_loan, on_hold, damaged, lost or withdrawn, also as status__ne"),
+        ("condition" = Option<String>, Query, description = "new, good, fair or poor, also as condition__ne"),
+        ("shelf_location" = Option<String>, Query, description = "Shelf of the items, also as shelf_location__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("limit" = Option<i64>, Query, description = "Max number of items per page, 50 by default and 500 at most"),
+        ("offset" = Option<i64>, Query, description = "Number of items to skip"),
+        ("sort" = Option<String>, Query, description = "Comma separated fields to sort by, prefix a field with '-' for descending order example (book_id,-acquired_at)"),
+    )
+)]
+#[get("/items", wrap = "RequireRole::staff()")]
+async fn find_all(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let pagination = Pagination::from_params(&mut params, &ITEMS_SORTABLE)?;
+    check::validate_filters(
+        &params,
+        &["id", "book_id"],
+        &["barcode", "status", "condition", "shelf_location"],
+    )?;
+
+    let (filters, page) = (params.clone(), pagination.clone());
+    let (items, total) = telemetry::block(move || Items::get(filters, page))
+        .await
+        .unwrap()?;
+
+    Ok(HttpResponse::Ok().json(Page::new(items, total, &pagination, req.path(), &params)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/items/{id}",
+    responses(
+        (status = 200, description = "Get an item identified with id", body = inline(Items)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/items/{id}", wrap = "RequireRole::staff()")]
+async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let item = Items::find(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(item))
+}
+
+#[utoipa::path(
+    post,
+    path = "/items",
+    request_body = Item,
+    responses(
+        (status = 200, description = "Add a copy of a book", body = inline(Items)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/items", wrap = "RequireRole::staff()")]
+async fn create(item: web::Json<Item>) -> Result<HttpResponse, CustomError> {
+    let item = Items::create(item.into_inner())?;
+    Ok(HttpResponse::Ok().json(item))
+}
+
+#[utoipa::path(
+    put,
+    path = "/items/{id}",
+    request_body = Item,
+    responses(
+        (status = 200, description = "Modify an item, its status can not change while it is on loan or on hold", body = inline(Items)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[put("/items/{id}", wrap = "RequireRole::staff()")]
+async fn update(id: web::Path<i32>, item: web::Json<Item>) -> Result<HttpResponse, CustomError> {
+    let item = Items::update(id.into_inner(), item.into_inner())?;
+    Ok(HttpResponse::Ok().json(item))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/items/{id}",
+    responses(
+        (status = 200, description = "Delete an item that is not on loan or on hold", body = inline(response::DeleteResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[delete("/items/{id}", wrap = "RequireRole::staff()")]
+async fn delete(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let deleted_item = Items::delete(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(json!({ "deleted": deleted_item })))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(find_all);
+    config.service(find);
+    config.service(create);
+    config.service(update);
+    config.service(delete);
+}
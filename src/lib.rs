@@ -1,7 +1,22 @@
+pub mod audit;
+pub mod auth;
+pub mod authors;
 pub mod books;
+pub mod config;
 pub mod db;
 pub mod error_handler;
+pub mod fines;
+pub mod health;
+pub mod holds;
+pub mod items;
+pub mod loans;
 pub mod members;
+pub mod metrics;
+pub mod publishers;
+pub mod repository;
 pub mod schema;
+pub mod search;
+pub mod subjects;
 pub mod swagger;
+pub mod telemetry;
 pub mod utils;
@@ -0,0 +1,175 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::fines::{FinePolicy, Fines};
+use crate::holds::Holds;
+use crate::items::{status, Items};
+use crate::schema::{books, loans, members};
+
+/// Days a book can be kept when the checkout does not set an explicit `due_at`.
+pub const LOAN_PERIOD_DAYS: i64 = 14;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Loan {
+    pub member_id: i32,
+    pub book_id: i32,
+    /// Copy to lend, any copy on the shelf when missing.
+    pub barcode: Option<String>,
+    pub due_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, ToSchema)]
+#[diesel(table_name = loans)]
+pub struct Loans {
+    pub id: i32,
+    pub member_id: i32,
+    pub book_id: i32,
+    pub loaned_at: NaiveDateTime,
+    pub due_at: NaiveDateTime,
+    pub returned_at: Option<NaiveDateTime>,
+    /// Copy lent, missing on loans made before copies were tracked.
+    pub item_id: Option<i32>,
+}
+
+impl Loans {
+    pub fn find_all() -> Result<Vec<Self>, CustomError> {
+        let mut conn = db::connection()?;
+        let loans = loans::table.load::<Loans>(&mut conn)?;
+        Ok(loans)
+    }
+
+    pub fn find(id: i32) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        let loan = loans::table
+            .filter(loans::id.eq(id))
+            .first(&mut conn)
+            .optional()?
+            .ok_or_else(|| CustomError::new(404, format!("Loan {id} not found")))?;
+        Ok(loan)
+    }
+
+    /// Lend a copy of `loan.book_id` to `loan.member_id`.
+    ///
+    /// A copy set aside for a ready hold of the member is used first. Otherwise the book row is
+    /// locked while a copy on the shelf is taken, so two concurrent checkouts can not take the
+    /// last copy twice.
+    pub fn checkout(loan: Loan) -> Result<Self, CustomError> {
+        let now = Utc::now().naive_utc();
+        let due_at = loan
+            .due_at
+            .unwrap_or_else(|| now + Duration::days(LOAN_PERIOD_DAYS));
+        if due_at <= now {
+            return Err(CustomError::new(
+                400,
+                "due_at must be in the future".to_string(),
+            ));
+        }
+
+        let mut conn = db::connection()?;
+        Holds::expire(&mut conn)?;
+        conn.transaction(|conn| {
+            members::table
+                .filter(members::id.eq(loan.member_id))
+                .filter(members::deleted_at.is_null())
+                .select(members::id)
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or_else(|| {
+                    CustomError::new(404, format!("Member {} not found", loan.member_id))
+                })?;
+
+            books::table
+                .filter(books::id.eq(loan.book_id))
+                .filter(books::deleted_at.is_null())
+                .select(books::id)
+                .for_update()
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or_else(|| CustomError::new(404, format!("Book {} not found", loan.book_id)))?;
+
+            let barcode = loan.barcode.as_deref();
+            let item = match Holds::fulfill(conn, loan.member_id, loan.book_id)? {
+                true => Items::take(
+                    conn,
+                    loan.book_id,
+                    barcode,
+                    status::ON_HOLD,
+                    status::ON_LOAN,
+                )?,
+                false => Items::take(
+                    conn,
+                    loan.book_id,
+                    barcode,
+                    status::AVAILABLE,
+                    status::ON_LOAN,
+                )?,
+            };
+            let item = item.ok_or_else(|| match barcode {
+                Some(barcode) => CustomError::new(
+                    409,
+                    format!("Copy {barcode} of book {} can not be lent", loan.book_id),
+                ),
+                None => CustomError::new(
+                    409,
+                    format!("No copies available for book {}", loan.book_id),
+                ),
+            })?;
+
+            let loan = diesel::insert_into(loans::table)
+                .values((
+                    loans::member_id.eq(loan.member_id),
+                    loans::book_id.eq(loan.book_id),
+                    loans::loaned_at.eq(now),
+                    loans::due_at.eq(due_at),
+                    loans::item_id.eq(item.id),
+                ))
+                .get_result(conn)?;
+            Ok(loan)
+        })
+    }
+
+    /// Close the loan `id`, charging the member when it comes back late. The copy goes to the
+    /// head of the hold queue of the book, or back on the shelf when nobody is waiting for it.
+    pub fn return_book(id: i32, policy: &FinePolicy) -> Result<Self, CustomError> {
+        let mut conn = db::connection()?;
+        conn.transaction(|conn| {
+            let loan: Loans = loans::table
+                .filter(loans::id.eq(id))
+                .for_update()
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| CustomError::new(404, format!("Loan {id} not found")))?;
+
+            if loan.returned_at.is_some() {
+                return Err(CustomError::new(
+                    409,
+                    format!("Loan {id} has already been returned"),
+                ));
+            }
+
+            let returned_at = Utc::now().naive_utc();
+            let loan = diesel::update(loans::table.filter(loans::id.eq(id)))
+                .set(loans::returned_at.eq(returned_at))
+                .get_result::<Loans>(conn)?;
+
+            Fines::charge_overdue(
+                conn,
+                loan.member_id,
+                loan.id,
+                loan.due_at,
+                returned_at,
+                policy,
+            )?;
+
+            if let Some(item_id) = loan.item_id {
+                Holds::release_copy(conn, loan.book_id, Some(item_id))?;
+            }
+
+            Ok(loan)
+        })
+    }
+}
@@ -0,0 +1,79 @@
+use actix_web::{get, post, web, HttpResponse};
+
+use crate::auth::RequireRole;
+use crate::config::Config;
+use crate::error_handler::CustomError;
+use crate::loans::{Loan, Loans};
+use crate::telemetry;
+use crate::utils::response;
+
+#[utoipa::path(
+    get,
+    path = "/loans",
+    responses(
+        (status = 200, description = "Get all loans", body = inline(response::LoansResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/loans", wrap = "RequireRole::staff()")]
+async fn find_all() -> Result<HttpResponse, CustomError> {
+    let loans = telemetry::block(Loans::find_all).await.unwrap();
+    Ok(HttpResponse::Ok().json(loans))
+}
+
+#[utoipa::path(
+    get,
+    path = "/loans/{id}",
+    responses(
+        (status = 200, description = "Get a loan identified with id", body = inline(Loans)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    )
+)]
+#[get("/loans/{id}", wrap = "RequireRole::staff()")]
+async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+    let loan = Loans::find(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(loan))
+}
+
+#[utoipa::path(
+    post,
+    path = "/loans",
+    request_body = Loan,
+    responses(
+        (status = 200, description = "Check out a copy of a book to a member", body = inline(Loans)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Member or book not found", body = inline(response::ErrorResponse)),
+        (status = 409, description = "No copies available", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/loans", wrap = "RequireRole::staff()")]
+async fn checkout(loan: web::Json<Loan>) -> Result<HttpResponse, CustomError> {
+    let loan = Loans::checkout(loan.into_inner())?;
+    Ok(HttpResponse::Ok().json(loan))
+}
+
+#[utoipa::path(
+    post,
+    path = "/loans/{id}/return",
+    responses(
+        (status = 200, description = "Return the copy lent with a loan", body = inline(Loans)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "Loan already returned", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/loans/{id}/return", wrap = "RequireRole::staff()")]
+async fn return_book(
+    id: web::Path<i32>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, CustomError> {
+    let loan = Loans::return_book(id.into_inner(), &config.fines)?;
+    Ok(HttpResponse::Ok().json(loan))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(find_all);
+    config.service(find);
+    config.service(checkout);
+    config.service(return_book);
+}
@@ -1,43 +1,181 @@
 extern crate diesel;
 
-// #[macro_use]
-// extern crate diesel_migrations;
-
 use std::env;
+use std::process;
 
 use actix_web::{web, App, HttpServer};
 use dotenv::dotenv;
 use listenfd::ListenFd;
 
+mod audit;
+mod auth;
+mod authors;
 mod books;
+mod config;
 mod db;
 mod error_handler;
+mod fines;
+mod health;
+mod holds;
+mod items;
+mod loans;
 mod members;
+mod metrics;
+mod publishers;
+mod repository;
 mod schema;
+mod search;
+mod subjects;
 mod swagger;
+mod telemetry;
 pub mod utils;
 
 fn set_routes(config: &mut web::ServiceConfig) {
-    swagger::init_swagger(config);
+    if config::get().features.swagger_ui {
+        swagger::init_swagger(config);
+    }
+    health::init_routes(config);
+    metrics::init_routes(config);
+    auth::init_routes(config);
     members::init_routes(config);
     books::init_routes(config);
+    authors::init_routes(config);
+    publishers::init_routes(config);
+    subjects::init_routes(config);
+    items::init_routes(config);
+    loans::init_routes(config);
+    holds::init_routes(config);
+    fines::init_routes(config);
+    search::init_routes(config);
+    audit::init_routes(config);
+}
+
+/// `api_rust migrate up|down|status`, exits with 1 when the migrations fail.
+fn migrate(command: Option<&str>) {
+    let result = match command {
+        Some("up") => utils::migrate::up().map(|applied| {
+            for name in &applied {
+                println!("applied  {name}");
+            }
+            if applied.is_empty() {
+                println!("no pending migration");
+            }
+        }),
+        Some("down") => utils::migrate::down().map(|name| println!("reverted {name}")),
+        Some("status") => db::connection()
+            .and_then(|mut conn| utils::migrate::status(&mut conn))
+            .map(|statuses| {
+                for (name, status) in statuses {
+                    println!("{status:<8} {name}");
+                }
+            }),
+        _ => {
+            eprintln!("usage: api_rust migrate up|down|status");
+            process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+}
+
+/// Log why the server can not start and exit with 1, as on an invalid configuration.
+fn fail(message: String) -> ! {
+    tracing::error!("{message}");
+    process::exit(1)
 }
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    //db::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (command, flags) = match args.first().map(String::as_str) {
+        Some("migrate") => (
+            args.get(1).map(String::as_str),
+            args.get(2..).unwrap_or(&[]),
+        ),
+        _ => (None, &args[..]),
+    };
+    let config = match config::Config::load(flags) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    };
+    let _telemetry = match telemetry::init(&config.log) {
+        Ok(telemetry) => telemetry,
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    };
+    config::init(config.clone());
+
+    if args.first().map(String::as_str) == Some("migrate") {
+        migrate(command);
+        return Ok(());
+    }
+
+    let repositories = match repository::Repositories::from_config(&config) {
+        Ok(repositories) => repositories,
+        Err(e) => fail(format!("Failed opening the database: {e}")),
+    };
+    match repository::sqlite_path(&config.database.url) {
+        Some(path) => tracing::warn!(
+            "Books, members and users are kept in {path}, other routes need Postgres"
+        ),
+        None => {
+            let workers = config.server.worker_count();
+            tracing::info!(
+                "Opening up to {} database connections, {} blocking and {} async per worker",
+                config.database.pool_size as usize
+                    + config.database.async_pool_size as usize * workers,
+                config.database.pool_size,
+                config.database.async_pool_size,
+            );
+            if config.features.run_migrations {
+                match utils::migrate::up() {
+                    Ok(applied) => tracing::info!("Applied {} migrations", applied.len()),
+                    Err(e) => fail(format!("Failed migrating the database: {e}")),
+                }
+            }
+        }
+    }
+    if let Err(e) = auth::Users::bootstrap(repositories.users.as_ref(), &config.auth).await {
+        fail(format!("Failed creating the admin user: {e}"));
+    }
+
+    if config.features.purge {
+        actix_rt::spawn(utils::purge::schedule(repositories.clone()));
+    }
 
     let mut listenfd = ListenFd::from_env();
-    let mut server = HttpServer::new(move || App::new().configure(set_routes));
+    let (host, port, workers) = (
+        config.server.host.clone(),
+        config.server.port,
+        config.server.workers,
+    );
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .wrap(auth::Authentication)
+            .wrap(metrics::Metrics)
+            .wrap(telemetry::RequestTracing)
+            .configure(|service| {
+                config.init(service);
+                repositories.init(service);
+            })
+            .configure(set_routes)
+    });
+    if workers > 0 {
+        server = server.workers(workers);
+    }
 
     server = match listenfd.take_tcp_listener(0)? {
         Some(listener) => server.listen(listener)?,
-        None => {
-            let host = env::var("HOST").expect("Please set host in .env");
-            let port = env::var("PORT").expect("Please set port in .env");
-            server.bind(format!("{host}:{port}"))?
-        }
+        None => server.bind((host, port))?,
     };
 
     server.run().await
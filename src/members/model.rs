@@ -1,15 +1,29 @@
 use std::collections::{HashMap, HashSet};
 
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::{exists, not};
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use futures_util::FutureExt;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use utoipa::ToSchema;
 
+use crate::audit::{action, Audit};
+use crate::auth::Claims;
 use crate::db;
 use crate::error_handler::CustomError;
-use crate::schema::members;
-use crate::utils::check;
+use crate::holds::status as hold_status;
+use crate::schema::{fines, holds, loans, members, payments};
+use crate::utils::batch::{self, Batch, BatchReport, Operation};
+use crate::utils::check::{self, filter_int, filter_text};
+use crate::utils::etag::Precondition;
+use crate::utils::import::{ImportReport, Outcome};
+use crate::utils::pagination::Pagination;
+use crate::utils::patch::Patch;
 
-#[derive(Serialize, Deserialize, AsChangeset, Insertable)]
+#[derive(Serialize, Deserialize, PartialEq, AsChangeset, Insertable)]
 #[diesel(table_name = members)]
 pub struct Member {
     pub first_name: String,
@@ -19,6 +33,24 @@ pub struct Member {
     pub age: i32,
 }
 
+/// The fields of a member a PATCH changes, the ones left to `None` are kept as they are.
+#[derive(Default, AsChangeset)]
+#[diesel(table_name = members)]
+pub struct MemberPatch {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub address: Option<String>,
+    pub age: Option<i32>,
+}
+
+/// Name of the members in the audit log.
+const ENTITY: &str = "members";
+
+/// Fields accepted by the `sort` param of the member listings.
+pub const MEMBERS_SORTABLE: [&str; 6] =
+    ["id", "first_name", "last_name", "email", "address", "age"];
+
 #[derive(Serialize, Deserialize, Queryable, Insertable, ToSchema)]
 #[diesel(table_name = members)]
 pub struct Members {
@@ -28,93 +60,464 @@ pub struct Members {
     pub email: String,
     pub address: String,
     pub age: i32,
+    /// When the member was deleted, only shown on deleted members.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Bumped on every change, sent as the `ETag` of the member.
+    pub version: i32,
+    pub updated_at: NaiveDateTime,
 }
 
 impl Members {
-    pub fn find_all() -> Result<Vec<Self>, CustomError> {
-        let mut conn = db::connection()?;
-        let members = members::table.load::<Members>(&mut conn)?;
+    pub async fn find_all(
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Self>, i64), CustomError> {
+        Members::get(&HashMap::new(), pagination, include_deleted).await
+    }
+
+    /// Page of the members matching `params`, along with how many members match in total.
+    /// Deleted members are left out unless `include_deleted` is set.
+    pub async fn get(
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Self>, i64), CustomError> {
+        let mut conn = db::async_connection().await?;
+        let total = Members::filtered(params, include_deleted)?
+            .count()
+            .get_result::<i64>(&mut conn)
+            .await?;
+
+        let mut query = Members::filtered(params, include_deleted)?;
+        for sort in &pagination.sort {
+            query = match (sort.field.as_str(), sort.descending) {
+                ("id", false) => query.then_order_by(members::id.asc()),
+                ("id", true) => query.then_order_by(members::id.desc()),
+                ("first_name", false) => query.then_order_by(members::first_name.asc()),
+                ("first_name", true) => query.then_order_by(members::first_name.desc()),
+                ("last_name", false) => query.then_order_by(members::last_name.asc()),
+                ("last_name", true) => query.then_order_by(members::last_name.desc()),
+                ("email", false) => query.then_order_by(members::email.asc()),
+                ("email", true) => query.then_order_by(members::email.desc()),
+                ("address", false) => query.then_order_by(members::address.asc()),
+                ("address", true) => query.then_order_by(members::address.desc()),
+                ("age", false) => query.then_order_by(members::age.asc()),
+                ("age", true) => query.then_order_by(members::age.desc()),
+                _ => query,
+            };
+        }
+
+        let members = query
+            .then_order_by(members::id.asc())
+            .limit(pagination.limit)
+            .offset(pagination.offset)
+            .load::<Members>(&mut conn)
+            .await?;
+        Ok((members, total))
+    }
+
+    /// Up to `limit` of the members matching `params` with an id above `after`, in id order.
+    pub async fn chunk(
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Self>, CustomError> {
+        let mut conn = db::async_connection().await?;
+        let members = Members::filtered(params, false)?
+            .filter(members::id.gt(after))
+            .order(members::id.asc())
+            .limit(limit)
+            .load::<Members>(&mut conn)
+            .await?;
         Ok(members)
     }
 
-    pub fn get(params: HashMap<String, String>) -> Result<Vec<Self>, CustomError> {
+    fn filtered(
+        params: &HashMap<String, String>,
+        include_deleted: bool,
+    ) -> Result<members::BoxedQuery<'_, Pg>, CustomError> {
         let mut query = members::table.into_boxed();
+        if !include_deleted {
+            query = query.filter(members::deleted_at.is_null());
+        }
 
-        if params.contains_key("id") {
-            match check::validate_int(params.get("id").unwrap()) {
-                Ok(id) => query = query.filter(members::id.eq(id)),
-                Err(error) => return Err(error),
+        for (key, value) in params {
+            if key == "ids" {
+                let ids_clean: HashSet<i32> = check::parse_ids(value)?.into_iter().collect();
+                query = query.filter(members::id.eq_any(ids_clean));
+                continue;
             }
-        }
-        if params.contains_key("ids") {
-            match check::parse_ids(params.get("ids").unwrap()) {
-                Ok(ids) => {
-                    let ids_clean: HashSet<i32> = ids.into_iter().collect();
-                    query = query.filter(members::id.eq_any(ids_clean));
+
+            let (field, operator) = check::parse_filter(key)?;
+            query = match field {
+                "id" => filter_int!(query, members::id, operator, check::validate_int(value)?),
+                "age" => filter_int!(query, members::age, operator, check::validate_int(value)?),
+                "first_name" => filter_text!(query, members::first_name, operator, value),
+                "last_name" => filter_text!(query, members::last_name, operator, value),
+                "email" => filter_text!(query, members::email, operator, value),
+                "address" => filter_text!(query, members::address, operator, value),
+                _ => {
+                    return Err(CustomError::new(
+                        400,
+                        format!("the parameter '{key}' is incorrect"),
+                    ))
                 }
-                Err(error) => return Err(error),
-            }
-        }
-        if let Some(first_name) = params.get("first_name") {
-            query = query.filter(members::first_name.eq(first_name))
+            };
         }
-        if let Some(last_name) = params.get("last_name") {
-            query = query.filter(members::last_name.eq(last_name))
-        }
-        if let Some(email) = params.get("email") {
-            query = query.filter(members::email.eq(email))
-        }
-        if let Some(address) = params.get("address") {
-            query = query.filter(members::address.eq(address));
+
+        Ok(query)
+    }
+
+    pub async fn find(id: i32, include_deleted: bool) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        let mut query = members::table.filter(members::id.eq(id)).into_boxed();
+        if !include_deleted {
+            query = query.filter(members::deleted_at.is_null());
         }
-        if let Some(age) = params.get("age") {
-            match check::validate_int(age) {
-                Ok(n) => query = query.filter(members::age.eq(n)),
-                Err(err) => return Err(err),
+        let member = query.first(&mut conn).await?;
+        Ok(member)
+    }
+
+    pub async fn create(member: Member, actor: &Claims) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| Members::insert(conn, member, actor).await)
+            .await
+    }
+
+    /// Save `member` over the member `id` if it is still at the version `precondition` expects.
+    pub async fn update(
+        id: i32,
+        member: Member,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| Members::save(conn, id, member, precondition, actor).await)
+            .await
+    }
+
+    /// Apply `patch` to the member `id` if it is still at the version `precondition` expects. The
+    /// patched member is checked like a whole one, and only the fields that changed are written.
+    pub async fn patch(
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| {
+            let before = Members::lock(conn, id)
+                .await?
+                .filter(|member| member.deleted_at.is_none())
+                .ok_or_else(|| CustomError::new(404, format!("Member {id} not found")))?;
+            precondition.check(before.version)?;
+            let member = patch.apply(&Member::of(&before))?;
+            member.validate()?;
+            let Some(changes) = MemberPatch::between(&before, member) else {
+                return Ok(before);
+            };
+
+            let member: Members = diesel::update(members::table)
+                .filter(members::id.eq(id))
+                .set((changes, members::version.eq(members::version + 1)))
+                .get_result(conn)
+                .await?;
+            Audit::record(
+                conn,
+                actor,
+                action::UPDATE,
+                ENTITY,
+                id,
+                Some(&before),
+                Some(&member),
+            )
+            .await?;
+            Ok(member)
+        })
+        .await
+    }
+
+    /// Insert the rows of an upload in one transaction, checked like [`Members::create`]. A row
+    /// with the email of an existing member is skipped.
+    pub async fn import(
+        rows: Vec<(u64, Result<Member, String>)>,
+        dry_run: bool,
+        actor: &Claims,
+    ) -> Result<ImportReport, CustomError> {
+        let mut conn = db::async_connection().await?;
+        ImportReport::run(&mut conn, rows, dry_run, |conn, member| {
+            let actor = actor.clone();
+            async move {
+                member.validate()?;
+                let existing = members::table
+                    .filter(members::email.eq(&member.email))
+                    .select(members::id)
+                    .first::<i32>(conn)
+                    .await
+                    .optional()?;
+                if let Some(id) = existing {
+                    return Ok(Outcome::Skipped(
+                        id,
+                        format!("email {} is already member {id}", member.email),
+                    ));
+                }
+
+                let member = Members::insert(conn, member, &actor).await?;
+                Ok(Outcome::Created(member.id))
             }
-        }
+            .boxed()
+        })
+        .await
+    }
 
-        let mut conn = db::connection()?;
-        let members = match query.get_results(&mut conn) {
-            Ok(members) => members,
-            Err(e) => return Err(CustomError::from(e)),
-        };
+    /// Run a batch of creates, updates and deletes, each one checked and audited like its own
+    /// route.
+    pub async fn batch(batch: Batch, actor: &Claims) -> Result<BatchReport, CustomError> {
+        let mut conn = db::async_connection().await?;
+        BatchReport::run(&mut conn, batch, |conn, operation| {
+            let actor = actor.clone();
+            async move {
+                match operation {
+                    Operation::Create { data } => {
+                        batch::ok(&Members::insert(conn, data, &actor).await?)
+                    }
+                    Operation::Update { id, data, version } => {
+                        let precondition = Precondition::required(version)?;
+                        batch::ok(&Members::save(conn, id, data, &precondition, &actor).await?)
+                    }
+                    Operation::Delete { id } => {
+                        Ok(json!({ "deleted": Members::remove(conn, id, &actor).await? }))
+                    }
+                }
+            }
+            .boxed()
+        })
+        .await
+    }
 
-        Ok(members)
+    /// Mark a member as deleted, it is hidden from the listings until it is restored or purged.
+    /// A member with a book still out or a hold still active can not be deleted.
+    pub async fn delete(id: i32, actor: &Claims) -> Result<usize, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| Members::remove(conn, id, actor).await)
+            .await
     }
 
-    pub fn find(id: i32) -> Result<Self, CustomError> {
-        let mut conn = db::connection()?;
-        let member = members::table.filter(members::id.eq(id)).first(&mut conn)?;
-        Ok(member)
+    /// Bring back a deleted member.
+    pub async fn restore(id: i32, actor: &Claims) -> Result<Self, CustomError> {
+        let mut conn = db::async_connection().await?;
+        conn.transaction(async |conn| {
+            let before = Members::lock(conn, id)
+                .await?
+                .ok_or_else(|| CustomError::new(404, format!("Member {id} not found")))?;
+            if before.deleted_at.is_none() {
+                return Err(CustomError::new(409, format!("Member {id} is not deleted")));
+            }
+            let member: Members = diesel::update(members::table.filter(members::id.eq(id)))
+                .set((
+                    members::deleted_at.eq(None::<NaiveDateTime>),
+                    members::version.eq(members::version + 1),
+                ))
+                .get_result(conn)
+                .await?;
+            Audit::record(
+                conn,
+                actor,
+                action::RESTORE,
+                ENTITY,
+                id,
+                Some(&before),
+                Some(&member),
+            )
+            .await?;
+            Ok(member)
+        })
+        .await
     }
 
-    pub fn create(member: Member) -> Result<Self, CustomError> {
-        let mut conn = db::connection()?;
+    /// Remove for good the members deleted before `deleted_before`. Members with loans, holds,
+    /// fines or payments are kept for their history.
+    pub async fn purge(deleted_before: NaiveDateTime) -> Result<usize, CustomError> {
+        let mut conn = db::async_connection().await?;
+        let res = diesel::delete(
+            members::table
+                .filter(members::deleted_at.lt(deleted_before))
+                .filter(not(exists(
+                    loans::table.filter(loans::member_id.eq(members::id)),
+                )))
+                .filter(not(exists(
+                    holds::table.filter(holds::member_id.eq(members::id)),
+                )))
+                .filter(not(exists(
+                    fines::table.filter(fines::member_id.eq(members::id)),
+                )))
+                .filter(not(exists(
+                    payments::table.filter(payments::member_id.eq(members::id)),
+                ))),
+        )
+        .execute(&mut conn)
+        .await?;
+        Ok(res)
+    }
+
+    async fn insert(
+        conn: &mut AsyncPgConnection,
+        member: Member,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        member.validate()?;
         let member = Member::from(member);
-        let member = diesel::insert_into(members::table)
+        let member: Members = diesel::insert_into(members::table)
             .values(member)
-            .get_result(&mut conn)?;
+            .get_result(conn)
+            .await?;
+        Audit::record(
+            conn,
+            actor,
+            action::CREATE,
+            ENTITY,
+            member.id,
+            None,
+            Some(&member),
+        )
+        .await?;
         Ok(member)
     }
 
-    pub fn update(id: i32, member: Member) -> Result<Self, CustomError> {
-        let mut conn = db::connection()?;
-        let member = diesel::update(members::table)
+    async fn save(
+        conn: &mut AsyncPgConnection,
+        id: i32,
+        member: Member,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Self, CustomError> {
+        member.validate()?;
+        let before = Members::lock(conn, id)
+            .await?
+            .filter(|member| member.deleted_at.is_none())
+            .ok_or_else(|| CustomError::new(404, format!("Member {id} not found")))?;
+        precondition.check(before.version)?;
+        let member: Members = diesel::update(members::table)
             .filter(members::id.eq(id))
-            .set(member)
-            .get_result(&mut conn)?;
+            .set((member, members::version.eq(members::version + 1)))
+            .get_result(conn)
+            .await?;
+        Audit::record(
+            conn,
+            actor,
+            action::UPDATE,
+            ENTITY,
+            id,
+            Some(&before),
+            Some(&member),
+        )
+        .await?;
         Ok(member)
     }
 
-    pub fn delete(id: i32) -> Result<usize, CustomError> {
-        let mut conn = db::connection()?;
-        let res = diesel::delete(members::table.filter(members::id.eq(id))).execute(&mut conn)?;
+    async fn remove(
+        conn: &mut AsyncPgConnection,
+        id: i32,
+        actor: &Claims,
+    ) -> Result<usize, CustomError> {
+        let before = Members::lock(conn, id).await?;
+        let Some(before) = before.filter(|member| member.deleted_at.is_none()) else {
+            return Ok(0);
+        };
+        let in_use = diesel::select(
+            exists(
+                loans::table
+                    .filter(loans::member_id.eq(id))
+                    .filter(loans::returned_at.is_null()),
+            )
+            .or(exists(holds::table.filter(holds::member_id.eq(id)).filter(
+                holds::status.eq_any([hold_status::WAITING, hold_status::READY]),
+            ))),
+        )
+        .get_result::<bool>(conn)
+        .await?;
+        if in_use {
+            return Err(CustomError::new(
+                409,
+                format!("Member {id} has open loans or active holds"),
+            ));
+        }
+        let res = diesel::update(members::table.filter(members::id.eq(id)))
+            .set((
+                members::deleted_at.eq(Utc::now().naive_utc()),
+                members::version.eq(members::version + 1),
+            ))
+            .execute(conn)
+            .await?;
+        Audit::record(conn, actor, action::DELETE, ENTITY, id, Some(&before), None).await?;
         Ok(res)
     }
+
+    async fn lock(conn: &mut AsyncPgConnection, id: i32) -> Result<Option<Self>, CustomError> {
+        let member = members::table
+            .filter(members::id.eq(id))
+            .for_update()
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(member)
+    }
 }
 
 impl Member {
+    /// Check the fields of a member before it is created, saved or imported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::members::Member;
+    ///
+    /// let mut member = Member {
+    ///     first_name: "Ada".to_string(),
+    ///     last_name: "Lovelace".to_string(),
+    ///     email: "ada@example.com".to_string(),
+    ///     address: "12 St James's Square".to_string(),
+    ///     age: 36,
+    /// };
+    /// assert!(member.validate().is_ok());
+    ///
+    /// member.email = "ada".to_string();
+    /// match member.validate() {
+    ///     Err(e) if e.to_string() == "email 'ada' is not valid" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    pub fn validate(&self) -> Result<(), CustomError> {
+        let error = |message: String| Err(CustomError::new(400, message));
+        if self.first_name.trim().is_empty() || self.last_name.trim().is_empty() {
+            return error("first_name and last_name can not be empty".to_string());
+        }
+        let valid_email = match self.email.split_once('@') {
+            Some((user, domain)) => !user.is_empty() && domain.contains('.'),
+            None => false,
+        };
+        if !valid_email || self.email.contains(char::is_whitespace) {
+            return error(format!("email '{}' is not valid", self.email));
+        }
+        if !(0..=150).contains(&self.age) {
+            return error("age must be between 0 and 150".to_string());
+        }
+        Ok(())
+    }
+
+    /// The editable fields of a saved member.
+    pub(crate) fn of(member: &Members) -> Member {
+        Member {
+            first_name: member.first_name.clone(),
+            last_name: member.last_name.clone(),
+            email: member.email.clone(),
+            address: member.address.clone(),
+            age: member.age,
+        }
+    }
+
     fn from(member: Member) -> Member {
         Member {
             first_name: member.first_name,
@@ -125,3 +528,25 @@ impl Member {
         }
     }
 }
+
+impl MemberPatch {
+    /// The fields of `member` that differ from `before`, `None` when there are none.
+    fn between(before: &Members, member: Member) -> Option<Self> {
+        let changes = MemberPatch {
+            first_name: (member.first_name != before.first_name).then_some(member.first_name),
+            last_name: (member.last_name != before.last_name).then_some(member.last_name),
+            email: (member.email != before.email).then_some(member.email),
+            address: (member.address != before.address).then_some(member.address),
+            age: (member.age != before.age).then_some(member.age),
+        };
+        let unchanged = changes.first_name.is_none()
+            && changes.last_name.is_none()
+            && changes.email.is_none()
+            && changes.address.is_none()
+            && changes.age.is_none();
+        match unchanged {
+            true => None,
+            false => Some(changes),
+        }
+    }
+}
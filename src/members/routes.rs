@@ -1,25 +1,54 @@
 use std::collections::HashMap;
 
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
 use serde_json::json;
 
+use crate::auth::{Claims, RequireRole};
 use crate::error_handler::CustomError;
-use crate::members::{Member, Members};
+use crate::members::{Member, Members, MEMBERS_SORTABLE};
+use crate::repository::MemberRepository;
+use crate::utils;
+use crate::utils::batch::{Batch, BatchReport};
 use crate::utils::check;
+use crate::utils::etag::{self, Precondition};
+use crate::utils::import::{parse, read_upload, ImportReport, Options};
+use crate::utils::pagination::{Page, Pagination};
+use crate::utils::patch::Patch;
 use crate::utils::response;
 
 #[utoipa::path(
     get,
     path = "/members",
     responses(
-        (status = 200, description = "Get all members", body = inline(response::MembersResponse)),
+        (status = 200, description = "Get a page of all members", body = inline(response::MembersResponse)),
         (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("limit" = Option<i64>, Query, description = "Max number of members per page, 50 by default and 500 at most"),
+        ("offset" = Option<i64>, Query, description = "Number of members to skip"),
+        ("sort" = Option<String>, Query, description = "Comma separated fields to sort by, prefix a field with '-' for descending order example (last_name,-age)"),
+        ("include_deleted" = Option<bool>, Query, description = "List the deleted members too, admins only"),
     )
 )]
-#[get("/members")]
-async fn find_all() -> Result<HttpResponse, CustomError> {
-    let members = web::block(Members::find_all).await.unwrap();
-    Ok(HttpResponse::Ok().json(members))
+#[get("/members", wrap = "RequireRole::staff()")]
+async fn find_all(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let pagination = Pagination::from_params(&mut params, &MEMBERS_SORTABLE)?;
+    let include_deleted = check::include_deleted(&mut params, claims.role)?;
+
+    let (members, total) = repository.find_all(&pagination, include_deleted).await?;
+
+    let mut links = HashMap::new();
+    if include_deleted {
+        links.insert("include_deleted".to_string(), "true".to_string());
+    }
+    Ok(HttpResponse::Ok().json(Page::new(members, total, &pagination, req.path(), &links)))
 }
 
 #[utoipa::path(
@@ -30,44 +59,162 @@ async fn find_all() -> Result<HttpResponse, CustomError> {
         (status = 400, description = "Error", body = inline(response::ErrorResponse))
     ),
     params(
-        ("id" = Option<i32>, Query, description = "Member database id"),
+        ("id" = Option<i32>, Query, description = "Member database id, also as id__ne, __gt, __gte, __lt or __lte"),
+        ("ids" = Option<String>, Query, description = "Members database comma separated ids example (1,2,3)"),
+        ("first_name" = Option<String>, Query,  description = "Member name, also as first_name__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("last_name" = Option<String>, Query,  description = "Member last_name, also as last_name__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("email" = Option<String>, Query,  description = "Member email, also as email__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("address" = Option<String>, Query, description = "Member address, also as address__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("age" = Option<i32>, Query, description = "Member age, also as age__ne, __gt, __gte, __lt or __lte"),
+        ("limit" = Option<i64>, Query, description = "Max number of members per page, 50 by default and 500 at most"),
+        ("offset" = Option<i64>, Query, description = "Number of members to skip"),
+        ("sort" = Option<String>, Query, description = "Comma separated fields to sort by, prefix a field with '-' for descending order example (last_name,-age)"),
+        ("include_deleted" = Option<bool>, Query, description = "List the deleted members too, admins only"),
+    )
+)]
+#[get("/members/filter", wrap = "RequireRole::staff()")]
+async fn filter(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let pagination = Pagination::from_params(&mut params, &MEMBERS_SORTABLE)?;
+    let include_deleted = check::include_deleted(&mut params, claims.role)?;
+    check::validate_members_params(&params)?;
+
+    let (members, total) = repository
+        .get(&params, &pagination, include_deleted)
+        .await?;
+
+    if include_deleted {
+        params.insert("include_deleted".to_string(), "true".to_string());
+    }
+    Ok(HttpResponse::Ok().json(Page::new(members, total, &pagination, req.path(), &params)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/members/export",
+    responses(
+        (status = 200, description = "Download every member matching the url params, in id order", content_type = "text/csv"),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("format" = Option<String>, Query, description = "csv (default) or ndjson"),
+        ("id" = Option<i32>, Query, description = "Member database id, also as id__ne, __gt, __gte, __lt or __lte"),
         ("ids" = Option<String>, Query, description = "Members database comma separated ids example (1,2,3)"),
-        ("first_name" = Option<String>, Query,  description = "Member name"),
-        ("last_name" = Option<String>, Query,  description = "Member last_name"),
-        ("email" = Option<String>, Query,  description = "Member email"),
-        ("address" = Option<String>, Query, description = "Member address"),
-        ("age" = Option<i32>, Query, description = "Member age"),
+        ("first_name" = Option<String>, Query,  description = "Member name, also as first_name__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("last_name" = Option<String>, Query,  description = "Member last_name, also as last_name__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("email" = Option<String>, Query,  description = "Member email, also as email__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("address" = Option<String>, Query, description = "Member address, also as address__ne, __contains, __icontains, __startswith, __endswith or __iexact"),
+        ("age" = Option<i32>, Query, description = "Member age, also as age__ne, __gt, __gte, __lt or __lte"),
     )
 )]
-#[get("/members/filter")]
-async fn filter(_param: web::Query<HashMap<String, String>>) -> Result<HttpResponse, CustomError> {
-    let params = _param.into_inner();
-
-    let members = if params.is_empty() {
-        web::block(Members::find_all).await.unwrap()
-    } else {
-        match check::validate_members_params(&params) {
-            Ok(..) => web::block(move || Members::get(params)).await.unwrap(),
-            Err(err) => return Err(err),
+#[get("/members/export", wrap = "RequireRole::staff()")]
+async fn export(
+    _param: web::Query<HashMap<String, String>>,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let format = utils::export::format(&mut params)?;
+    check::validate_members_params(&params)?;
+
+    let fetch = move |after| {
+        let (params, repository) = (params.clone(), repository.clone());
+        async move {
+            repository
+                .chunk(&params, after, utils::export::CHUNK_ROWS)
+                .await
         }
     };
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, utils::export::content_type(format)))
+        .insert_header((
+            CONTENT_DISPOSITION,
+            utils::export::content_disposition("members", format),
+        ))
+        .streaming(utils::export::rows(format, fetch, |member: &Members| {
+            member.id
+        })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/members/import",
+    request_body(content = String, content_type = "text/csv", description = "CSV with a header (first_name,last_name,email,address,age), or NDJSON with one member per line sent as application/x-ndjson"),
+    responses(
+        (status = 200, description = "Import members, rows with the email of an existing member are skipped", body = inline(ImportReport)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 413, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 415, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 422, description = "Some rows failed and nothing was saved", body = inline(ImportReport))
+    ),
+    params(
+        ("format" = Option<String>, Query, description = "csv or ndjson, taken from the Content-Type when missing"),
+        ("dry_run" = Option<bool>, Query, description = "Report what the import would do without saving anything"),
+    )
+)]
+#[post("/members/import", wrap = "RequireRole::staff()")]
+async fn import(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+    payload: web::Payload,
+    claims: Claims,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let options = Options::from_request(&req, &mut params)?;
+    let body = read_upload(payload).await?;
+    let rows = parse::<Member>(options.format, &body)?;
 
-    Ok(HttpResponse::Ok().json(members))
+    let report = Members::import(rows, options.dry_run, &claims).await?;
+    Ok(HttpResponse::build(report.status_code()).json(report))
+}
+
+#[utoipa::path(
+    post,
+    path = "/members/batch",
+    request_body(content = String, content_type = "application/json", description = "{\"mode\": \"atomic\" (default) or \"best_effort\", \"operations\": [{\"op\": \"create\", \"data\": {..}}, {\"op\": \"update\", \"id\": 1, \"version\": 2, \"data\": {..}}, {\"op\": \"delete\", \"id\": 1}]}, 1000 operations at most"),
+    responses(
+        (status = 200, description = "Create, update and delete members, with the status and the response of every operation", body = inline(BatchReport)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 413, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 422, description = "An operation failed and nothing was saved", body = inline(BatchReport))
+    )
+)]
+#[post("/members/batch", wrap = "RequireRole::staff()")]
+async fn batch(batch: web::Json<Batch>, claims: Claims) -> Result<HttpResponse, CustomError> {
+    let report = Members::batch(batch.into_inner(), &claims).await?;
+    Ok(HttpResponse::build(report.status_code()).json(report))
 }
 
 #[utoipa::path(
     get,
     path = "/members/{id}",
     responses(
-        (status = 200, description = "Get a member identifies with id", body = inline(Members)),
+        (status = 200, description = "Get a member identifies with id, along with its ETag", body = inline(Members)),
         (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 403, description = "Error", body = inline(response::ErrorResponse)),
         (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("include_deleted" = Option<bool>, Query, description = "Find the member even if it is deleted, admins only"),
     )
 )]
-#[get("/members/{id}")]
-async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
-    let member = Members::find(id.into_inner())?;
-    Ok(HttpResponse::Ok().json(member))
+#[get("/members/{id}", wrap = "RequireRole::staff()")]
+async fn find(
+    id: web::Path<i32>,
+    _param: web::Query<HashMap<String, String>>,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let include_deleted = check::include_deleted(&mut params, claims.role)?;
+    let member = repository.find(id.into_inner(), include_deleted).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(member.version))
+        .json(member))
 }
 
 #[utoipa::path(
@@ -78,51 +225,136 @@ async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
         (status = 400, description = "Error", body = inline(response::ErrorResponse))
     )
 )]
-#[post("/members")]
-async fn create(member: web::Json<Member>) -> Result<HttpResponse, CustomError> {
-    let member = Members::create(member.into_inner())?;
-    Ok(HttpResponse::Ok().json(member))
+#[post("/members", wrap = "RequireRole::staff()")]
+async fn create(
+    member: web::Json<Member>,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let member = repository.create(member.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(member.version))
+        .json(member))
 }
 
 #[utoipa::path(
     put,
     path = "/members{id}",
     responses(
-    (status = 200, description = "Modify a new member", body = inline(response::MemberResponse)),
+    (status = 200, description = "Modify a member, the new ETag is sent back", body = inline(response::MemberResponse)),
     (status = 400, description = "Error", body = inline(response::ErrorResponse)),
-    (status = 404, description = "Error", body = inline(response::ErrorResponse))
+    (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+    (status = 412, description = "The member changed since its ETag was read", body = inline(response::ErrorResponse)),
+    (status = 428, description = "The If-Match header is missing", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("If-Match" = String, Header, description = "ETag of the member as it was read"),
     )
 )]
-#[put("/members/{id}")]
+#[put("/members/{id}", wrap = "RequireRole::staff()")]
 async fn update(
     id: web::Path<i32>,
     member: web::Json<Member>,
+    precondition: Precondition,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let member = repository
+        .update(id.into_inner(), member.into_inner(), &precondition, &claims)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(member.version))
+        .json(member))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/members/{id}",
+    request_body(content = String, content_type = "application/merge-patch+json", description = "The fields to change as a JSON Merge Patch, or a list of operations sent as application/json-patch+json"),
+    responses(
+        (status = 200, description = "Change some fields of a member, the new ETag is sent back", body = inline(response::MemberResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "A test operation of the JSON Patch failed", body = inline(response::ErrorResponse)),
+        (status = 412, description = "The member changed since its ETag was read", body = inline(response::ErrorResponse)),
+        (status = 415, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 422, description = "The patch can not be applied to the member", body = inline(response::ErrorResponse)),
+        (status = 428, description = "The If-Match header is missing", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("If-Match" = String, Header, description = "ETag of the member as it was read"),
+    )
+)]
+#[patch("/members/{id}", wrap = "RequireRole::staff()")]
+async fn patch(
+    id: web::Path<i32>,
+    req: HttpRequest,
+    body: web::Bytes,
+    precondition: Precondition,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
 ) -> Result<HttpResponse, CustomError> {
-    let member = Members::update(id.into_inner(), member.into_inner())?;
-    Ok(HttpResponse::Ok().json(member))
+    let patch = Patch::from_request(&req, &body)?;
+    let member = repository
+        .patch(id.into_inner(), patch, &precondition, &claims)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(member.version))
+        .json(member))
 }
 
 #[utoipa::path(
     delete,
     path = "/members{id}",
     responses(
-        (status = 200, description = "Delete a new member", body = inline(response::DeleteResponse)),
+        (status = 200, description = "Delete a member, it can be restored until it is purged", body = inline(response::DeleteResponse)),
         (status = 400, description = "Error", body = inline(response::ErrorResponse)),
-        (status = 404, description = "Error", body = inline(response::ErrorResponse))
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "The member has open loans or active holds", body = inline(response::ErrorResponse))
     )
 )]
-#[delete("/members/{id}")]
-async fn delete(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
-    let deleted_member = Members::delete(id.into_inner())?;
+#[delete("/members/{id}", wrap = "RequireRole::staff()")]
+async fn delete(
+    id: web::Path<i32>,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let deleted_member = repository.delete(id.into_inner(), &claims).await?;
     Ok(HttpResponse::Ok().json(json!({ "deleted": deleted_member })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/members/{id}/restore",
+    responses(
+        (status = 200, description = "Restore a deleted member", body = inline(response::MemberResponse)),
+        (status = 404, description = "Error", body = inline(response::ErrorResponse)),
+        (status = 409, description = "The member is not deleted", body = inline(response::ErrorResponse))
+    )
+)]
+#[post("/members/{id}/restore", wrap = "RequireRole::staff()")]
+async fn restore(
+    id: web::Path<i32>,
+    claims: Claims,
+    repository: web::Data<dyn MemberRepository>,
+) -> Result<HttpResponse, CustomError> {
+    let member = repository.restore(id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag::header(member.version))
+        .json(member))
+}
+
 pub fn init_routes(config: &mut web::ServiceConfig) {
     config.service(find_all);
     config.service(filter);
+    config.service(import);
+    config.service(export);
+    config.service(batch);
     config.service(find);
     config.service(create);
     config.service(update);
+    config.service(patch);
     config.service(delete);
     config.service(delete);
+    config.service(restore);
 }
@@ -0,0 +1,71 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+use crate::metrics::observe_request;
+
+/// Route of the requests that matched none, so unknown paths do not each get a series.
+const UNMATCHED: &str = "unmatched";
+
+/// Count every request by method, route and status code, and time it.
+///
+/// Wrap it around [`Authentication`](crate::auth::Authentication) so the rejected requests are
+/// counted too.
+pub struct Metrics;
+
+impl<S, B> Transform<S, ServiceRequest> for Metrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = MetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MetricsMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct MetricsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for MetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let started_at = Instant::now();
+            let method = req.method().to_string();
+            let res = service.call(req).await?;
+
+            let route = res.request().match_pattern();
+            observe_request(
+                &method,
+                route.as_deref().unwrap_or(UNMATCHED),
+                res.status().as_u16(),
+                started_at.elapsed().as_secs_f64(),
+            );
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,7 @@
+pub use middleware::*;
+pub use model::*;
+pub use routes::*;
+
+mod middleware;
+mod model;
+mod routes;
@@ -0,0 +1,91 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+
+use crate::db;
+use crate::error_handler::CustomError;
+
+lazy_static! {
+    static ref REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "http_requests_total",
+        "Requests handled, by method, route and status code",
+        &["method", "route", "status"]
+    )
+    .expect("Failed to register http_requests_total");
+    static ref DURATION: HistogramVec = register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time taken to answer a request, by method and route",
+        &["method", "route"]
+    )
+    .expect("Failed to register http_request_duration_seconds");
+    static ref ERRORS: IntCounterVec = register_int_counter_vec!(
+        "api_errors_total",
+        "Error responses, by status code",
+        &["status"]
+    )
+    .expect("Failed to register api_errors_total");
+    static ref POOL_IDLE: IntGauge = register_int_gauge!(
+        "db_pool_idle_connections",
+        "Open connections of the blocking pool waiting for a query"
+    )
+    .expect("Failed to register db_pool_idle_connections");
+    static ref POOL_ACTIVE: IntGauge = register_int_gauge!(
+        "db_pool_active_connections",
+        "Connections of the blocking pool running a query"
+    )
+    .expect("Failed to register db_pool_active_connections");
+    static ref ASYNC_POOL_IDLE: IntGaugeVec = register_int_gauge_vec!(
+        "db_async_pool_idle_connections",
+        "Open connections of the async pool of a worker waiting for a query, by worker",
+        &["worker"]
+    )
+    .expect("Failed to register db_async_pool_idle_connections");
+    static ref ASYNC_POOL_ACTIVE: IntGaugeVec = register_int_gauge_vec!(
+        "db_async_pool_active_connections",
+        "Connections of the async pool of a worker running a query, by worker",
+        &["worker"]
+    )
+    .expect("Failed to register db_async_pool_active_connections");
+}
+
+/// Count a request answered with `status` after `seconds`. `route` is the pattern the request
+/// matched, such as `/books/{id}`, so every book counts towards the same series.
+pub fn observe_request(method: &str, route: &str, status: u16, seconds: f64) {
+    REQUESTS
+        .with_label_values(&[method, route, &status.to_string()])
+        .inc();
+    DURATION
+        .with_label_values(&[method, route])
+        .observe(seconds);
+}
+
+/// Count an error answered with `status`.
+pub fn observe_error(status: u16) {
+    ERRORS.with_label_values(&[&status.to_string()]).inc();
+}
+
+/// Every metric in the Prometheus text format, with the gauges of the blocking pool and of the
+/// async pool of each worker read now.
+pub fn render() -> Result<String, CustomError> {
+    let pool = db::pool_state();
+    POOL_IDLE.set(pool.idle.into());
+    POOL_ACTIVE.set((pool.size - pool.idle).into());
+    for (worker, pool) in db::async_pool_states().iter().enumerate() {
+        let worker = worker.to_string();
+        ASYNC_POOL_IDLE
+            .with_label_values(&[&worker])
+            .set(pool.idle.into());
+        ASYNC_POOL_ACTIVE
+            .with_label_values(&[&worker])
+            .set((pool.size - pool.idle).into());
+    }
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .map_err(|e| CustomError::new(500, format!("Failed encoding the metrics: {e}")))?;
+    String::from_utf8(buffer)
+        .map_err(|e| CustomError::new(500, format!("Failed encoding the metrics: {e}")))
+}
@@ -0,0 +1,23 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::error_handler::CustomError;
+use crate::metrics;
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    security(()),
+    responses(
+        (status = 200, description = "Request, error and connection pool metrics in the Prometheus text format", body = String)
+    )
+)]
+#[get("/metrics")]
+async fn find_all() -> Result<HttpResponse, CustomError> {
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(metrics::render()?))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(find_all);
+}
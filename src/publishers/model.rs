@@ -0,0 +1,13 @@
+//! Publishers of books, each a name linked to its books through `book_publishers`. The listing,
+//! lookup, create, update and delete are generated by [`named_model`]. Names are unique, so a
+//! second publisher with the same name answers 409.
+
+use crate::utils::named::named_model;
+
+named_model!(
+    publishers,
+    book_publishers,
+    Publisher,
+    Publishers,
+    PUBLISHERS_SORTABLE
+);
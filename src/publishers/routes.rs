@@ -0,0 +1,24 @@
+//! Routes generated by [`named_routes`]: `GET /publishers` and `GET /publishers/{id}` for any
+//! signed-in user, kiosks included, and `POST /publishers`, `PUT /publishers/{id}` and
+//! `DELETE /publishers/{id}` for admins and librarians. Their queries run on the blocking thread
+//! pool.
+
+use crate::publishers::{Publisher, Publishers, PUBLISHERS_SORTABLE};
+use crate::utils::named::named_routes;
+
+named_routes! {
+    records: Publishers,
+    record: Publisher,
+    sortable: PUBLISHERS_SORTABLE,
+    page: PublishersResponse,
+    list: "/publishers",
+    one: "/publishers/{id}",
+    find_all: "Get a page of all publishers",
+    limit: "Max number of publishers per page, 50 by default and 500 at most",
+    offset: "Number of publishers to skip",
+    find: "Get a publisher identified with id",
+    create: "Create a new publisher",
+    update: "Modify a publisher",
+    delete: "Delete a publisher and remove it from its books",
+    conflict: "A publisher with this name already exists",
+}
@@ -0,0 +1,7 @@
+pub use model::*;
+pub use postgres::*;
+pub use sqlite::*;
+
+mod model;
+mod postgres;
+mod sqlite;
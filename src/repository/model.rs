@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+
+use crate::auth::{Claims, Role, Users};
+use crate::books::{Book, Books};
+use crate::config::Config;
+use crate::error_handler::CustomError;
+use crate::members::{Member, Members};
+use crate::repository::{PgBookRepository, PgMemberRepository, PgUserRepository, SqliteRepository};
+use crate::utils::etag::Precondition;
+use crate::utils::pagination::Pagination;
+use crate::utils::patch::Patch;
+
+/// Where the books are kept, handed to the routes as `web::Data<dyn BookRepository>`.
+///
+/// Imports, batches and the authors, publishers and subjects of a book are only kept in
+/// Postgres, through [`Books`].
+#[async_trait]
+pub trait BookRepository: Send + Sync {
+    /// Page of the books matching `params`, along with how many books match in total. Deleted
+    /// books are left out unless `include_deleted` is set.
+    async fn get(
+        &self,
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Books>, i64), CustomError>;
+
+    async fn find_all(
+        &self,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Books>, i64), CustomError> {
+        self.get(&HashMap::new(), pagination, include_deleted).await
+    }
+
+    /// Up to `limit` of the books matching `params` with an id above `after`, in id order.
+    async fn chunk(
+        &self,
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Books>, CustomError>;
+
+    async fn find(&self, id: i32, include_deleted: bool) -> Result<Books, CustomError>;
+
+    async fn create(&self, book: Book, actor: &Claims) -> Result<Books, CustomError>;
+
+    /// Save `book` over the book `id` if it is still at the version `precondition` expects.
+    async fn update(
+        &self,
+        id: i32,
+        book: Book,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Books, CustomError>;
+
+    /// Apply `patch` to the book `id` if it is still at the version `precondition` expects.
+    async fn patch(
+        &self,
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Books, CustomError>;
+
+    /// Mark a book as deleted, and return how many books were.
+    async fn delete(&self, id: i32, actor: &Claims) -> Result<usize, CustomError>;
+
+    /// Bring back a deleted book.
+    async fn restore(&self, id: i32, actor: &Claims) -> Result<Books, CustomError>;
+
+    /// Remove for good the books deleted before `deleted_before`.
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<usize, CustomError>;
+}
+
+/// Where the members are kept, handed to the routes as `web::Data<dyn MemberRepository>`.
+///
+/// Imports and batches are only kept in Postgres, through [`Members`].
+#[async_trait]
+pub trait MemberRepository: Send + Sync {
+    /// Page of the members matching `params`, along with how many members match in total.
+    /// Deleted members are left out unless `include_deleted` is set.
+    async fn get(
+        &self,
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Members>, i64), CustomError>;
+
+    async fn find_all(
+        &self,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Members>, i64), CustomError> {
+        self.get(&HashMap::new(), pagination, include_deleted).await
+    }
+
+    /// Up to `limit` of the members matching `params` with an id above `after`, in id order.
+    async fn chunk(
+        &self,
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Members>, CustomError>;
+
+    async fn find(&self, id: i32, include_deleted: bool) -> Result<Members, CustomError>;
+
+    async fn create(&self, member: Member, actor: &Claims) -> Result<Members, CustomError>;
+
+    /// Save `member` over the member `id` if it is still at the version `precondition` expects.
+    async fn update(
+        &self,
+        id: i32,
+        member: Member,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Members, CustomError>;
+
+    /// Apply `patch` to the member `id` if it is still at the version `precondition` expects.
+    async fn patch(
+        &self,
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Members, CustomError>;
+
+    /// Mark a member as deleted, and return how many members were.
+    async fn delete(&self, id: i32, actor: &Claims) -> Result<usize, CustomError>;
+
+    /// Bring back a deleted member.
+    async fn restore(&self, id: i32, actor: &Claims) -> Result<Members, CustomError>;
+
+    /// Remove for good the members deleted before `deleted_before`.
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<usize, CustomError>;
+}
+
+/// Where the staff users are kept, handed to the login routes as `web::Data<dyn UserRepository>`.
+///
+/// Passwords are hashed and checked by [`Users`], the repository only stores them.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_by_username(&self, username: &str) -> Result<Option<Users>, CustomError>;
+
+    /// How many users there are.
+    async fn count(&self) -> Result<i64, CustomError>;
+
+    /// Save a user whose password is already hashed.
+    async fn insert(
+        &self,
+        username: &str,
+        password_hash: &str,
+        role: Role,
+    ) -> Result<Users, CustomError>;
+}
+
+/// The repositories of the books, the members and the users, shared by every worker.
+#[derive(Clone)]
+pub struct Repositories {
+    pub books: Arc<dyn BookRepository>,
+    pub members: Arc<dyn MemberRepository>,
+    pub users: Arc<dyn UserRepository>,
+}
+
+impl Repositories {
+    pub fn postgres() -> Self {
+        Repositories {
+            books: Arc::new(PgBookRepository),
+            members: Arc::new(PgMemberRepository),
+            users: Arc::new(PgUserRepository),
+        }
+    }
+
+    /// Books, members and users kept in the SQLite file at `path`, or in memory for `:memory:`.
+    pub fn sqlite(path: &str) -> Result<Self, CustomError> {
+        let repository = Arc::new(SqliteRepository::open(path)?);
+        Ok(Repositories {
+            books: repository.clone(),
+            members: repository.clone(),
+            users: repository,
+        })
+    }
+
+    /// SQLite when the database url is a `sqlite:` one, Postgres otherwise.
+    pub fn from_config(config: &Config) -> Result<Self, CustomError> {
+        match sqlite_path(&config.database.url) {
+            Some(path) => Repositories::sqlite(path),
+            None => Ok(Repositories::postgres()),
+        }
+    }
+
+    /// Give the routes the repositories as `web::Data`.
+    pub fn init(&self, config: &mut web::ServiceConfig) {
+        config.app_data(web::Data::from(self.books.clone()));
+        config.app_data(web::Data::from(self.members.clone()));
+        config.app_data(web::Data::from(self.users.clone()));
+    }
+}
+
+/// Path of the SQLite database when `url` is a `sqlite:` url.
+///
+/// # Examples
+///
+/// ```
+/// use lib_api::repository::sqlite_path;
+///
+/// assert_eq!(Some("library.db"), sqlite_path("sqlite:library.db"));
+/// assert_eq!(Some("/data/library.db"), sqlite_path("sqlite:///data/library.db"));
+/// assert_eq!(None, sqlite_path("postgres://localhost/library"));
+/// ```
+pub fn sqlite_path(url: &str) -> Option<&str> {
+    let path = url.strip_prefix("sqlite:")?;
+    Some(path.strip_prefix("//").unwrap_or(path))
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+
+use crate::auth::{Claims, Role, Users};
+use crate::books::{Book, Books};
+use crate::error_handler::CustomError;
+use crate::members::{Member, Members};
+use crate::repository::{BookRepository, MemberRepository, UserRepository};
+use crate::telemetry;
+use crate::utils::etag::Precondition;
+use crate::utils::pagination::Pagination;
+use crate::utils::patch::Patch;
+
+/// Books kept in Postgres, every change recorded in the audit log.
+pub struct PgBookRepository;
+
+/// Members kept in Postgres, every change recorded in the audit log.
+pub struct PgMemberRepository;
+
+/// Users kept in Postgres.
+pub struct PgUserRepository;
+
+#[async_trait]
+impl BookRepository for PgBookRepository {
+    async fn get(
+        &self,
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Books>, i64), CustomError> {
+        Books::get(params, pagination, include_deleted).await
+    }
+
+    async fn chunk(
+        &self,
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Books>, CustomError> {
+        Books::chunk(params, after, limit).await
+    }
+
+    async fn find(&self, id: i32, include_deleted: bool) -> Result<Books, CustomError> {
+        Books::find(id, include_deleted).await
+    }
+
+    async fn create(&self, book: Book, actor: &Claims) -> Result<Books, CustomError> {
+        Books::create(book, actor).await
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        book: Book,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Books, CustomError> {
+        Books::update(id, book, precondition, actor).await
+    }
+
+    async fn patch(
+        &self,
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Books, CustomError> {
+        Books::patch(id, patch, precondition, actor).await
+    }
+
+    async fn delete(&self, id: i32, actor: &Claims) -> Result<usize, CustomError> {
+        Books::delete(id, actor).await
+    }
+
+    async fn restore(&self, id: i32, actor: &Claims) -> Result<Books, CustomError> {
+        Books::restore(id, actor).await
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<usize, CustomError> {
+        Books::purge(deleted_before).await
+    }
+}
+
+#[async_trait]
+impl MemberRepository for PgMemberRepository {
+    async fn get(
+        &self,
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Members>, i64), CustomError> {
+        Members::get(params, pagination, include_deleted).await
+    }
+
+    async fn chunk(
+        &self,
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Members>, CustomError> {
+        Members::chunk(params, after, limit).await
+    }
+
+    async fn find(&self, id: i32, include_deleted: bool) -> Result<Members, CustomError> {
+        Members::find(id, include_deleted).await
+    }
+
+    async fn create(&self, member: Member, actor: &Claims) -> Result<Members, CustomError> {
+        Members::create(member, actor).await
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        member: Member,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Members, CustomError> {
+        Members::update(id, member, precondition, actor).await
+    }
+
+    async fn patch(
+        &self,
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        actor: &Claims,
+    ) -> Result<Members, CustomError> {
+        Members::patch(id, patch, precondition, actor).await
+    }
+
+    async fn delete(&self, id: i32, actor: &Claims) -> Result<usize, CustomError> {
+        Members::delete(id, actor).await
+    }
+
+    async fn restore(&self, id: i32, actor: &Claims) -> Result<Members, CustomError> {
+        Members::restore(id, actor).await
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<usize, CustomError> {
+        Members::purge(deleted_before).await
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgUserRepository {
+    async fn find_by_username(&self, username: &str) -> Result<Option<Users>, CustomError> {
+        let username = username.to_string();
+        telemetry::block(move || Users::find_by_username(&username))
+            .await
+            .map_err(|e| CustomError::new(500, e.to_string()))?
+    }
+
+    async fn count(&self) -> Result<i64, CustomError> {
+        telemetry::block(Users::count)
+            .await
+            .map_err(|e| CustomError::new(500, e.to_string()))?
+    }
+
+    async fn insert(
+        &self,
+        username: &str,
+        password_hash: &str,
+        role: Role,
+    ) -> Result<Users, CustomError> {
+        let (username, password_hash) = (username.to_string(), password_hash.to_string());
+        telemetry::block(move || Users::insert(&username, &password_hash, role))
+            .await
+            .map_err(|e| CustomError::new(500, e.to_string()))?
+    }
+}
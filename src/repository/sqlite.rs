@@ -0,0 +1,638 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+
+use crate::auth::{Claims, Role, Users};
+use crate::books::{Book, Books};
+use crate::error_handler::CustomError;
+use crate::members::{Member, Members};
+use crate::repository::{BookRepository, MemberRepository, UserRepository};
+use crate::telemetry;
+use crate::utils::check::{self, like_pattern, Isbn, Operator};
+use crate::utils::etag::Precondition;
+use crate::utils::pagination::Pagination;
+use crate::utils::patch::Patch;
+
+/// Tables of the books, the members and the users, with the same columns as in Postgres.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS books (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title VARCHAR NOT NULL,
+    isbn VARCHAR NOT NULL UNIQUE,
+    copies_available INTEGER NOT NULL DEFAULT 0,
+    copies INTEGER NOT NULL DEFAULT 0,
+    deleted_at TIMESTAMP,
+    version INTEGER NOT NULL DEFAULT 1,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE TABLE IF NOT EXISTS members (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    first_name VARCHAR NOT NULL,
+    last_name VARCHAR NOT NULL,
+    email VARCHAR NOT NULL,
+    address VARCHAR NOT NULL,
+    age INTEGER NOT NULL,
+    deleted_at TIMESTAMP,
+    version INTEGER NOT NULL DEFAULT 1,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username VARCHAR NOT NULL UNIQUE,
+    password_hash VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    role VARCHAR NOT NULL CHECK (role IN ('admin', 'librarian', 'kiosk'))
+);
+";
+
+/// Books, members and users kept in a SQLite database, to run without a Postgres server.
+///
+/// Changes are not recorded in the audit log, which is kept in Postgres, and books have no
+/// items so their copies stay at 0.
+pub struct SqliteRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteRepository {
+    /// Open the database at `path`, `:memory:` for one that lives as long as the repository,
+    /// and create its tables when they are missing.
+    pub fn open(path: &str) -> Result<Self, CustomError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        // LIKE tells the case apart as in Postgres, the case insensitive filters use lower().
+        conn.pragma_update(None, "case_sensitive_like", true)?;
+        Ok(SqliteRepository {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run `query` on the blocking pool, SQLite has no async driver.
+    async fn run<T, F>(&self, query: F) -> Result<T, CustomError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, CustomError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        telemetry::block(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|e| CustomError::new(500, format!("Failed getting db connection: {e}")))?;
+            query(&mut conn)
+        })
+        .await
+        .map_err(|e| CustomError::new(500, e.to_string()))?
+    }
+}
+
+/// A record kept in a table of the SQLite database.
+trait Record: Sized {
+    const TABLE: &'static str;
+    /// Name of a record in error messages.
+    const NAME: &'static str;
+    /// Columns read into a record, in the order of [`Record::read`].
+    const COLUMNS: &'static str;
+    /// Columns written by a create or an update, in the order of [`Record::values`].
+    const FIELDS: &'static [&'static str];
+    /// Integer columns the filters and the sort can use.
+    const INTS: &'static [&'static str];
+    /// Text columns the filters and the sort can use.
+    const TEXTS: &'static [&'static str];
+
+    /// The editable fields of a record.
+    type Fields;
+
+    fn read(row: &Row) -> rusqlite::Result<Self>;
+
+    fn values(fields: Self::Fields) -> Vec<Value>;
+
+    fn version(&self) -> i32;
+}
+
+impl Record for Books {
+    const TABLE: &'static str = "books";
+    const NAME: &'static str = "Book";
+    const COLUMNS: &'static str =
+        "id, title, isbn, copies_available, copies, deleted_at, version, updated_at";
+    const FIELDS: &'static [&'static str] = &["title", "isbn"];
+    const INTS: &'static [&'static str] = &["id", "copies_available", "copies"];
+    const TEXTS: &'static [&'static str] = &["title", "isbn"];
+
+    type Fields = Book;
+
+    fn read(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Books {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            isbn: row.get(2)?,
+            copies_available: row.get(3)?,
+            copies: row.get(4)?,
+            deleted_at: row.get(5)?,
+            version: row.get(6)?,
+            updated_at: row.get(7)?,
+        })
+    }
+
+    fn values(book: Book) -> Vec<Value> {
+        vec![Value::Text(book.title), Value::Text(book.isbn)]
+    }
+
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+impl Record for Members {
+    const TABLE: &'static str = "members";
+    const NAME: &'static str = "Member";
+    const COLUMNS: &'static str =
+        "id, first_name, last_name, email, address, age, deleted_at, version, updated_at";
+    const FIELDS: &'static [&'static str] = &["first_name", "last_name", "email", "address", "age"];
+    const INTS: &'static [&'static str] = &["id", "age"];
+    const TEXTS: &'static [&'static str] = &["first_name", "last_name", "email", "address"];
+
+    type Fields = Member;
+
+    fn read(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Members {
+            id: row.get(0)?,
+            first_name: row.get(1)?,
+            last_name: row.get(2)?,
+            email: row.get(3)?,
+            address: row.get(4)?,
+            age: row.get(5)?,
+            deleted_at: row.get(6)?,
+            version: row.get(7)?,
+            updated_at: row.get(8)?,
+        })
+    }
+
+    fn values(member: Member) -> Vec<Value> {
+        vec![
+            Value::Text(member.first_name),
+            Value::Text(member.last_name),
+            Value::Text(member.email),
+            Value::Text(member.address),
+            Value::Integer(member.age.into()),
+        ]
+    }
+
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+/// Conditions on the records matching `params`, along with the values they bind.
+fn filtered<R: Record>(
+    params: &HashMap<String, String>,
+    include_deleted: bool,
+) -> Result<(Vec<String>, Vec<Value>), CustomError> {
+    let (mut conditions, mut values) = (Vec::new(), Vec::new());
+    if !include_deleted {
+        conditions.push("deleted_at IS NULL".to_string());
+    }
+
+    for (key, value) in params {
+        if key == "ids" {
+            let ids: HashSet<i32> = check::parse_ids(value)?.into_iter().collect();
+            conditions.push(format!("id IN ({})", vec!["?"; ids.len()].join(", ")));
+            values.extend(ids.into_iter().map(|id| Value::Integer(id.into())));
+            continue;
+        }
+
+        let (field, operator) = check::parse_filter(key)?;
+        if R::INTS.contains(&field) {
+            let sign = match operator {
+                Operator::Ne => "<>",
+                Operator::Gt => ">",
+                Operator::Gte => ">=",
+                Operator::Lt => "<",
+                Operator::Lte => "<=",
+                _ => "=",
+            };
+            conditions.push(format!("{field} {sign} ?"));
+            values.push(Value::Integer(check::validate_int(value)?.into()));
+        } else if R::TEXTS.contains(&field) {
+            let value = match field {
+                "isbn" => Isbn::filter_value(operator, value),
+                _ => value.to_string(),
+            };
+            let (condition, value) = match operator {
+                Operator::Ne => (format!("{field} <> ?"), value),
+                Operator::Contains | Operator::StartsWith | Operator::EndsWith => (
+                    format!("{field} LIKE ? ESCAPE '\\'"),
+                    like_pattern(operator, &value),
+                ),
+                Operator::IContains | Operator::IExact => (
+                    format!("lower({field}) LIKE lower(?) ESCAPE '\\'"),
+                    like_pattern(operator, &value),
+                ),
+                _ => (format!("{field} = ?"), value),
+            };
+            conditions.push(condition);
+            values.push(Value::Text(value));
+        } else {
+            return Err(CustomError::new(
+                400,
+                format!("the parameter '{key}' is not supported on SQLite"),
+            ));
+        }
+    }
+
+    Ok((conditions, values))
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    match conditions.is_empty() {
+        true => String::new(),
+        false => format!(" WHERE {}", conditions.join(" AND ")),
+    }
+}
+
+fn not_found<R: Record>(id: i32) -> CustomError {
+    CustomError::new(404, format!("{} {id} not found", R::NAME))
+}
+
+fn now() -> Value {
+    Value::Text(Utc::now().naive_utc().format("%F %T%.f").to_string())
+}
+
+fn select_page<R: Record>(
+    conn: &Connection,
+    params: &HashMap<String, String>,
+    pagination: &Pagination,
+    include_deleted: bool,
+) -> Result<(Vec<R>, i64), CustomError> {
+    let (conditions, mut values) = filtered::<R>(params, include_deleted)?;
+    let filter = where_clause(&conditions);
+    let total = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {}{filter}", R::TABLE),
+        params_from_iter(values.iter()),
+        |row| row.get(0),
+    )?;
+
+    let mut order: Vec<String> = pagination
+        .sort
+        .iter()
+        .filter(|sort| {
+            R::INTS.contains(&sort.field.as_str()) || R::TEXTS.contains(&sort.field.as_str())
+        })
+        .map(|sort| match sort.descending {
+            true => format!("{} DESC", sort.field),
+            false => format!("{} ASC", sort.field),
+        })
+        .collect();
+    order.push("id ASC".to_string());
+
+    values.push(Value::Integer(pagination.limit));
+    values.push(Value::Integer(pagination.offset));
+    let mut statement = conn.prepare(&format!(
+        "SELECT {} FROM {}{filter} ORDER BY {} LIMIT ? OFFSET ?",
+        R::COLUMNS,
+        R::TABLE,
+        order.join(", ")
+    ))?;
+    let records = statement
+        .query_map(params_from_iter(values), R::read)?
+        .collect::<Result<Vec<R>, _>>()?;
+    Ok((records, total))
+}
+
+fn select_chunk<R: Record>(
+    conn: &Connection,
+    params: &HashMap<String, String>,
+    after: i32,
+    limit: i64,
+) -> Result<Vec<R>, CustomError> {
+    let (mut conditions, mut values) = filtered::<R>(params, false)?;
+    conditions.push("id > ?".to_string());
+    values.push(Value::Integer(after.into()));
+    values.push(Value::Integer(limit));
+    let mut statement = conn.prepare(&format!(
+        "SELECT {} FROM {}{} ORDER BY id ASC LIMIT ?",
+        R::COLUMNS,
+        R::TABLE,
+        where_clause(&conditions)
+    ))?;
+    let records = statement
+        .query_map(params_from_iter(values), R::read)?
+        .collect::<Result<Vec<R>, _>>()?;
+    Ok(records)
+}
+
+fn select_one<R: Record>(
+    conn: &Connection,
+    id: i32,
+    include_deleted: bool,
+) -> Result<Option<R>, CustomError> {
+    let deleted = match include_deleted {
+        true => "",
+        false => " AND deleted_at IS NULL",
+    };
+    let sql = format!(
+        "SELECT {} FROM {} WHERE id = ?{deleted}",
+        R::COLUMNS,
+        R::TABLE
+    );
+    Ok(conn.query_row(&sql, [id], R::read).optional()?)
+}
+
+fn insert<R: Record>(conn: &Connection, fields: R::Fields) -> Result<R, CustomError> {
+    let sql = format!(
+        "INSERT INTO {} ({}, updated_at) VALUES ({}) RETURNING {}",
+        R::TABLE,
+        R::FIELDS.join(", "),
+        vec!["?"; R::FIELDS.len() + 1].join(", "),
+        R::COLUMNS
+    );
+    let mut values = R::values(fields);
+    values.push(now());
+    Ok(conn.query_row(&sql, params_from_iter(values), R::read)?)
+}
+
+/// Write the fields `change` returns over the record `id`, if it is still at the version
+/// `precondition` expects. Nothing is written when `change` returns `None`.
+fn save<R, F>(
+    conn: &mut Connection,
+    id: i32,
+    precondition: &Precondition,
+    change: F,
+) -> Result<R, CustomError>
+where
+    R: Record,
+    F: FnOnce(&R) -> Result<Option<R::Fields>, CustomError>,
+{
+    let tx = conn.transaction()?;
+    let before = select_one::<R>(&tx, id, false)?.ok_or_else(|| not_found::<R>(id))?;
+    precondition.check(before.version())?;
+    let Some(fields) = change(&before)? else {
+        return Ok(before);
+    };
+
+    let set: Vec<String> = R::FIELDS
+        .iter()
+        .map(|field| format!("{field} = ?"))
+        .collect();
+    let sql = format!(
+        "UPDATE {} SET {}, version = version + 1, updated_at = ? WHERE id = ? RETURNING {}",
+        R::TABLE,
+        set.join(", "),
+        R::COLUMNS
+    );
+    let mut values = R::values(fields);
+    values.push(now());
+    values.push(Value::Integer(id.into()));
+    let record = tx.query_row(&sql, params_from_iter(values), R::read)?;
+    tx.commit()?;
+    Ok(record)
+}
+
+fn soft_delete<R: Record>(conn: &Connection, id: i32) -> Result<usize, CustomError> {
+    let sql = format!(
+        "UPDATE {} SET deleted_at = ?1, version = version + 1, updated_at = ?1 \
+         WHERE id = ?2 AND deleted_at IS NULL",
+        R::TABLE
+    );
+    Ok(conn.execute(&sql, params![now(), id])?)
+}
+
+fn undelete<R: Record>(conn: &Connection, id: i32) -> Result<R, CustomError> {
+    let sql = format!(
+        "UPDATE {} SET deleted_at = NULL, version = version + 1, updated_at = ? \
+         WHERE id = ? AND deleted_at IS NOT NULL RETURNING {}",
+        R::TABLE,
+        R::COLUMNS
+    );
+    match conn
+        .query_row(&sql, params![now(), id], R::read)
+        .optional()?
+    {
+        Some(record) => Ok(record),
+        None => match select_one::<R>(conn, id, true)? {
+            Some(_) => Err(CustomError::new(
+                409,
+                format!("{} {id} is not deleted", R::NAME),
+            )),
+            None => Err(not_found::<R>(id)),
+        },
+    }
+}
+
+fn purge_deleted<R: Record>(
+    conn: &Connection,
+    deleted_before: NaiveDateTime,
+) -> Result<usize, CustomError> {
+    let sql = format!("DELETE FROM {} WHERE deleted_at < ?", R::TABLE);
+    Ok(conn.execute(&sql, [deleted_before])?)
+}
+
+#[async_trait]
+impl BookRepository for SqliteRepository {
+    async fn get(
+        &self,
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Books>, i64), CustomError> {
+        let (params, pagination) = (params.clone(), pagination.clone());
+        self.run(move |conn| select_page(conn, &params, &pagination, include_deleted))
+            .await
+    }
+
+    async fn chunk(
+        &self,
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Books>, CustomError> {
+        let params = params.clone();
+        self.run(move |conn| select_chunk(conn, &params, after, limit))
+            .await
+    }
+
+    async fn find(&self, id: i32, include_deleted: bool) -> Result<Books, CustomError> {
+        self.run(move |conn| {
+            select_one(conn, id, include_deleted)?.ok_or_else(|| not_found::<Books>(id))
+        })
+        .await
+    }
+
+    async fn create(&self, book: Book, _actor: &Claims) -> Result<Books, CustomError> {
+        let book = book.normalize()?;
+        self.run(move |conn| insert(conn, book)).await
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        book: Book,
+        precondition: &Precondition,
+        _actor: &Claims,
+    ) -> Result<Books, CustomError> {
+        let (book, precondition) = (book.normalize()?, precondition.clone());
+        self.run(move |conn| save(conn, id, &precondition, |_| Ok(Some(book))))
+            .await
+    }
+
+    async fn patch(
+        &self,
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        _actor: &Claims,
+    ) -> Result<Books, CustomError> {
+        let precondition = precondition.clone();
+        self.run(move |conn| {
+            save(conn, id, &precondition, |before: &Books| {
+                let book = patch.apply(&Book::of(before))?.normalize()?;
+                Ok((book != Book::of(before)).then_some(book))
+            })
+        })
+        .await
+    }
+
+    async fn delete(&self, id: i32, _actor: &Claims) -> Result<usize, CustomError> {
+        self.run(move |conn| soft_delete::<Books>(conn, id)).await
+    }
+
+    async fn restore(&self, id: i32, _actor: &Claims) -> Result<Books, CustomError> {
+        self.run(move |conn| undelete(conn, id)).await
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<usize, CustomError> {
+        self.run(move |conn| purge_deleted::<Books>(conn, deleted_before))
+            .await
+    }
+}
+
+#[async_trait]
+impl MemberRepository for SqliteRepository {
+    async fn get(
+        &self,
+        params: &HashMap<String, String>,
+        pagination: &Pagination,
+        include_deleted: bool,
+    ) -> Result<(Vec<Members>, i64), CustomError> {
+        let (params, pagination) = (params.clone(), pagination.clone());
+        self.run(move |conn| select_page(conn, &params, &pagination, include_deleted))
+            .await
+    }
+
+    async fn chunk(
+        &self,
+        params: &HashMap<String, String>,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<Members>, CustomError> {
+        let params = params.clone();
+        self.run(move |conn| select_chunk(conn, &params, after, limit))
+            .await
+    }
+
+    async fn find(&self, id: i32, include_deleted: bool) -> Result<Members, CustomError> {
+        self.run(move |conn| {
+            select_one(conn, id, include_deleted)?.ok_or_else(|| not_found::<Members>(id))
+        })
+        .await
+    }
+
+    async fn create(&self, member: Member, _actor: &Claims) -> Result<Members, CustomError> {
+        member.validate()?;
+        self.run(move |conn| insert(conn, member)).await
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        member: Member,
+        precondition: &Precondition,
+        _actor: &Claims,
+    ) -> Result<Members, CustomError> {
+        member.validate()?;
+        let precondition = precondition.clone();
+        self.run(move |conn| save(conn, id, &precondition, |_| Ok(Some(member))))
+            .await
+    }
+
+    async fn patch(
+        &self,
+        id: i32,
+        patch: Patch,
+        precondition: &Precondition,
+        _actor: &Claims,
+    ) -> Result<Members, CustomError> {
+        let precondition = precondition.clone();
+        self.run(move |conn| {
+            save(conn, id, &precondition, |before: &Members| {
+                let member = patch.apply(&Member::of(before))?;
+                member.validate()?;
+                Ok((member != Member::of(before)).then_some(member))
+            })
+        })
+        .await
+    }
+
+    async fn delete(&self, id: i32, _actor: &Claims) -> Result<usize, CustomError> {
+        self.run(move |conn| soft_delete::<Members>(conn, id)).await
+    }
+
+    async fn restore(&self, id: i32, _actor: &Claims) -> Result<Members, CustomError> {
+        self.run(move |conn| undelete(conn, id)).await
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<usize, CustomError> {
+        self.run(move |conn| purge_deleted::<Members>(conn, deleted_before))
+            .await
+    }
+}
+
+/// Columns read into a user, in the order of [`read_user`].
+const USER_COLUMNS: &str = "id, username, password_hash, created_at, role";
+
+fn read_user(row: &Row) -> rusqlite::Result<Users> {
+    Ok(Users {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        created_at: row.get(3)?,
+        role: row.get(4)?,
+    })
+}
+
+#[async_trait]
+impl UserRepository for SqliteRepository {
+    async fn find_by_username(&self, username: &str) -> Result<Option<Users>, CustomError> {
+        let username = username.to_string();
+        self.run(move |conn| {
+            let sql = format!("SELECT {USER_COLUMNS} FROM users WHERE username = ?");
+            Ok(conn.query_row(&sql, [username], read_user).optional()?)
+        })
+        .await
+    }
+
+    async fn count(&self) -> Result<i64, CustomError> {
+        self.run(|conn| Ok(conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?))
+            .await
+    }
+
+    async fn insert(
+        &self,
+        username: &str,
+        password_hash: &str,
+        role: Role,
+    ) -> Result<Users, CustomError> {
+        let (username, password_hash) = (username.to_string(), password_hash.to_string());
+        self.run(move |conn| {
+            let sql = format!(
+                "INSERT INTO users (username, password_hash, created_at, role) \
+                 VALUES (?, ?, ?, ?) RETURNING {USER_COLUMNS}"
+            );
+            let values = params![username, password_hash, now(), role.as_str()];
+            Ok(conn.query_row(&sql, values, read_user)?)
+        })
+        .await
+    }
+}
@@ -1,5 +1,47 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    audit_log (id) {
+        id -> Int4,
+        actor_id -> Int4,
+        actor -> Varchar,
+        action -> Varchar,
+        entity -> Varchar,
+        entity_id -> Int4,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    authors (id) {
+        id -> Int4,
+        name -> Varchar,
+    }
+}
+
+diesel::table! {
+    book_authors (book_id, author_id) {
+        book_id -> Int4,
+        author_id -> Int4,
+    }
+}
+
+diesel::table! {
+    book_publishers (book_id, publisher_id) {
+        book_id -> Int4,
+        publisher_id -> Int4,
+    }
+}
+
+diesel::table! {
+    book_subjects (book_id, subject_id) {
+        book_id -> Int4,
+        subject_id -> Int4,
+    }
+}
+
 diesel::table! {
     books (id) {
         id -> Int4,
@@ -7,6 +49,58 @@ diesel::table! {
         isbn -> Varchar,
         copies_available -> Int4,
         copies -> Int4,
+        deleted_at -> Nullable<Timestamp>,
+        version -> Int4,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    fines (id) {
+        id -> Int4,
+        member_id -> Int4,
+        loan_id -> Nullable<Int4>,
+        amount_cents -> Int4,
+        reason -> Varchar,
+        created_at -> Timestamp,
+        waived_at -> Nullable<Timestamp>,
+        waive_reason -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    holds (id) {
+        id -> Int4,
+        member_id -> Int4,
+        book_id -> Int4,
+        status -> Varchar,
+        placed_at -> Timestamp,
+        ready_at -> Nullable<Timestamp>,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    items (id) {
+        id -> Int4,
+        barcode -> Varchar,
+        book_id -> Int4,
+        status -> Varchar,
+        condition -> Varchar,
+        shelf_location -> Nullable<Varchar>,
+        acquired_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    loans (id) {
+        id -> Int4,
+        member_id -> Int4,
+        book_id -> Int4,
+        loaned_at -> Timestamp,
+        due_at -> Timestamp,
+        returned_at -> Nullable<Timestamp>,
+        item_id -> Nullable<Int4>,
     }
 }
 
@@ -18,10 +112,76 @@ diesel::table! {
         email -> Varchar,
         address -> Varchar,
         age -> Int4,
+        deleted_at -> Nullable<Timestamp>,
+        version -> Int4,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    payments (id) {
+        id -> Int4,
+        member_id -> Int4,
+        amount_cents -> Int4,
+        note -> Nullable<Varchar>,
+        paid_at -> Timestamp,
     }
 }
 
+diesel::table! {
+    publishers (id) {
+        id -> Int4,
+        name -> Varchar,
+    }
+}
+
+diesel::table! {
+    subjects (id) {
+        id -> Int4,
+        name -> Varchar,
+    }
+}
+
+diesel::table! {
+    users (id) {
+        id -> Int4,
+        username -> Varchar,
+        password_hash -> Varchar,
+        created_at -> Timestamp,
+        role -> Varchar,
+    }
+}
+
+diesel::joinable!(book_authors -> authors (author_id));
+diesel::joinable!(book_authors -> books (book_id));
+diesel::joinable!(book_publishers -> books (book_id));
+diesel::joinable!(book_publishers -> publishers (publisher_id));
+diesel::joinable!(book_subjects -> books (book_id));
+diesel::joinable!(book_subjects -> subjects (subject_id));
+diesel::joinable!(fines -> loans (loan_id));
+diesel::joinable!(fines -> members (member_id));
+diesel::joinable!(holds -> books (book_id));
+diesel::joinable!(holds -> members (member_id));
+diesel::joinable!(items -> books (book_id));
+diesel::joinable!(loans -> books (book_id));
+diesel::joinable!(loans -> items (item_id));
+diesel::joinable!(loans -> members (member_id));
+diesel::joinable!(payments -> members (member_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
+    authors,
+    book_authors,
+    book_publishers,
+    book_subjects,
     books,
+    fines,
+    holds,
+    items,
+    loans,
     members,
+    payments,
+    publishers,
+    subjects,
+    users,
 );
@@ -0,0 +1,120 @@
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Float4, Int4, Text, Varchar};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::error_handler::CustomError;
+use crate::utils::pagination::Pagination;
+
+/// A book matching a search, best matches first.
+#[derive(Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct SearchResult {
+    #[diesel(sql_type = Int4)]
+    pub id: i32,
+    #[diesel(sql_type = Varchar)]
+    pub title: String,
+    #[diesel(sql_type = Varchar)]
+    pub isbn: String,
+    #[diesel(sql_type = Int4)]
+    pub copies_available: i32,
+    #[diesel(sql_type = Int4)]
+    pub copies: i32,
+    #[diesel(sql_type = Float4)]
+    pub rank: f32,
+    /// Title, HTML-escaped, with the matched words wrapped in `<mark>` tags.
+    #[diesel(sql_type = Text)]
+    pub headline: String,
+}
+
+#[derive(QueryableByName)]
+struct Total {
+    #[diesel(sql_type = BigInt)]
+    total: i64,
+}
+
+impl SearchResult {
+    /// Page of the books matching `q` by relevance, along with how many books match in total.
+    pub fn search(q: &str, pagination: Pagination) -> Result<(Vec<Self>, i64), CustomError> {
+        let terms = search_terms(q)?;
+        let mut conn = db::connection()?;
+
+        let total = diesel::sql_query(
+            "SELECT count(*) AS total \
+             FROM books, to_tsquery('english', $1) query \
+             WHERE books.deleted_at IS NULL AND books_search_vector(books.title, books.isbn) @@ query",
+        )
+        .bind::<Text, _>(&terms)
+        .get_result::<Total>(&mut conn)?
+        .total;
+
+        let results = diesel::sql_query(
+            "SELECT books.id, books.title, books.isbn, books.copies_available, books.copies, \
+                 ts_rank(books_search_vector(books.title, books.isbn), query) AS rank, \
+                 ts_headline('english', \
+                     replace(replace(replace(books.title, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), \
+                     query, \
+                     'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS headline \
+             FROM books, to_tsquery('english', $1) query \
+             WHERE books.deleted_at IS NULL AND books_search_vector(books.title, books.isbn) @@ query \
+             ORDER BY rank DESC, books.id \
+             LIMIT $2 OFFSET $3",
+        )
+        .bind::<Text, _>(&terms)
+        .bind::<BigInt, _>(pagination.limit)
+        .bind::<BigInt, _>(pagination.offset)
+        .load::<SearchResult>(&mut conn)?;
+
+        Ok((results, total))
+    }
+}
+
+/// Turn the words typed by a patron into a `tsquery` where every word has to match the start of
+/// a word of the book, so partial titles are found. Hyphens inside an ISBN are dropped, as they
+/// are in the search index.
+///
+/// # Examples
+///
+/// ```
+/// use lib_api::search::search_terms;
+///
+/// assert_eq!("rust:* & prog:*", search_terms("Rust  prog").unwrap());
+/// assert_eq!("9781593278281:*", search_terms("978-1-59327-828-1").unwrap());
+/// assert_eq!("sci:* & fi:*", search_terms("sci-fi").unwrap());
+/// ```
+///
+/// ```
+/// use lib_api::search::search_terms;
+///
+/// match search_terms(" & !") {
+///     Err(e) if e.to_string() == "the search needs at least one letter or digit" => (),
+///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+///     Ok(_) => panic!("Returned an Ok variant!"),
+/// }
+/// ```
+pub fn search_terms(q: &str) -> Result<String, CustomError> {
+    let mut terms = Vec::new();
+    for word in q.split_whitespace() {
+        let isbn_like = word
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '-' || c == 'X' || c == 'x');
+        if isbn_like && word.chars().any(|c| c.is_ascii_digit()) {
+            terms.push(word.replace('-', "").to_lowercase());
+            continue;
+        }
+        terms.extend(
+            word.split(|c: char| !c.is_alphanumeric())
+                .filter(|term| !term.is_empty())
+                .map(|term| term.to_lowercase()),
+        );
+    }
+    let terms: Vec<String> = terms.into_iter().map(|term| format!("{term}:*")).collect();
+
+    match terms.is_empty() {
+        true => Err(CustomError::new(
+            400,
+            "the search needs at least one letter or digit".to_string(),
+        )),
+        false => Ok(terms.join(" & ")),
+    }
+}
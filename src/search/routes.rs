@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+
+use crate::auth::RequireRole;
+use crate::error_handler::CustomError;
+use crate::search::SearchResult;
+use crate::telemetry;
+use crate::utils::pagination::{Page, Pagination};
+use crate::utils::response;
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    responses(
+        (status = 200, description = "Search the catalogue, best matches first", body = inline(response::SearchResponse)),
+        (status = 400, description = "Error", body = inline(response::ErrorResponse))
+    ),
+    params(
+        ("q" = String, Query, description = "Words of the title or digits of the ISBN, partial words match too"),
+        ("limit" = Option<i64>, Query, description = "Max number of books per page, 50 by default and 500 at most"),
+        ("offset" = Option<i64>, Query, description = "Number of books to skip"),
+    )
+)]
+#[get("/search", wrap = "RequireRole::any()")]
+async fn search(
+    req: HttpRequest,
+    _param: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, CustomError> {
+    let mut params = _param.into_inner();
+    let pagination = Pagination::from_params(&mut params, &[])?;
+    let q = params
+        .remove("q")
+        .ok_or_else(|| CustomError::new(400, "the parameter 'q' is required".to_string()))?;
+    if let Some(key) = params.keys().next() {
+        return Err(CustomError::new(
+            400,
+            format!("the parameter '{key}' is incorrect"),
+        ));
+    }
+
+    let (query, page) = (q.clone(), pagination.clone());
+    let (books, total) = telemetry::block(move || SearchResult::search(&query, page))
+        .await
+        .unwrap()?;
+
+    params.insert("q".to_string(), q);
+    Ok(HttpResponse::Ok().json(Page::new(books, total, &pagination, req.path(), &params)))
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(search);
+}
@@ -0,0 +1,13 @@
+//! Subjects of books, each a name linked to its books through `book_subjects`. The listing,
+//! lookup, create, update and delete are generated by [`named_model`]. Names are unique, so a
+//! second subject with the same name answers 409.
+
+use crate::utils::named::named_model;
+
+named_model!(
+    subjects,
+    book_subjects,
+    Subject,
+    Subjects,
+    SUBJECTS_SORTABLE
+);
@@ -0,0 +1,23 @@
+//! Routes generated by [`named_routes`]: `GET /subjects` and `GET /subjects/{id}` for any signed-in
+//! user, kiosks included, and `POST /subjects`, `PUT /subjects/{id}` and `DELETE /subjects/{id}`
+//! for admins and librarians. Their queries run on the blocking thread pool.
+
+use crate::subjects::{Subject, Subjects, SUBJECTS_SORTABLE};
+use crate::utils::named::named_routes;
+
+named_routes! {
+    records: Subjects,
+    record: Subject,
+    sortable: SUBJECTS_SORTABLE,
+    page: SubjectsResponse,
+    list: "/subjects",
+    one: "/subjects/{id}",
+    find_all: "Get a page of all subjects",
+    limit: "Max number of subjects per page, 50 by default and 500 at most",
+    offset: "Number of subjects to skip",
+    find: "Get a subject identified with id",
+    create: "Create a new subject",
+    update: "Modify a subject",
+    delete: "Delete a subject and remove it from its books",
+    conflict: "A subject with this name already exists",
+}
@@ -1,30 +1,142 @@
 use actix_web::web;
-use utoipa::OpenApi;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
+use crate::audit;
+use crate::auth;
+use crate::authors;
 use crate::books;
+use crate::db;
+use crate::fines;
+use crate::health;
+use crate::holds;
+use crate::items;
+use crate::loans;
 use crate::members;
+use crate::metrics;
+use crate::publishers;
+use crate::search;
+use crate::subjects;
+use crate::utils::batch;
+use crate::utils::import;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        health::status,
+        health::live,
+        health::ready,
+        metrics::find_all,
+        auth::login,
+        auth::create,
         members::find_all,
         members::filter,
+        members::import,
+        members::export,
+        members::batch,
         members::find,
         members::create,
         members::update,
+        members::patch,
         members::delete,
+        members::restore,
         books::find_all,
         books::filter,
+        books::import,
+        books::export,
+        books::batch,
         books::find,
         books::create,
         books::update,
-        books::delete
+        books::patch,
+        books::delete,
+        books::restore,
+        books::link,
+        authors::find_all,
+        authors::find,
+        authors::create,
+        authors::update,
+        authors::delete,
+        publishers::find_all,
+        publishers::find,
+        publishers::create,
+        publishers::update,
+        publishers::delete,
+        subjects::find_all,
+        subjects::find,
+        subjects::create,
+        subjects::update,
+        subjects::delete,
+        items::find_all,
+        items::find,
+        items::create,
+        items::update,
+        items::delete,
+        loans::find_all,
+        loans::find,
+        loans::checkout,
+        loans::return_book,
+        holds::find_all,
+        holds::find,
+        holds::position,
+        holds::queue,
+        holds::place,
+        holds::cancel,
+        fines::balance,
+        fines::pay,
+        fines::find,
+        fines::waive,
+        search::search,
+        audit::find_all
     ),
-    components(schemas(members::Members), schemas(books::Books))
+    components(
+        schemas(health::Health, health::Readiness, db::PoolState),
+        schemas(auth::User, auth::Users, auth::Token, auth::Role),
+        schemas(members::Members),
+        schemas(books::Books, books::BookDetails),
+        schemas(authors::Authors, authors::Author),
+        schemas(publishers::Publishers, publishers::Publisher),
+        schemas(subjects::Subjects, subjects::Subject),
+        schemas(items::Items, items::Item),
+        schemas(loans::Loans, loans::Loan),
+        schemas(holds::Holds, holds::Hold, holds::HoldPosition),
+        schemas(
+            fines::Fines,
+            fines::Payments,
+            fines::Payment,
+            fines::Waiver,
+            fines::Balance
+        ),
+        schemas(search::SearchResult),
+        schemas(audit::Audits),
+        schemas(import::ImportReport, import::RowReport),
+        schemas(batch::BatchReport, batch::OperationReport, batch::Mode)
+    ),
+    modifiers(&SecurityAddon),
+    security(("bearer_auth" = []))
 )]
 pub struct ApiDoc;
 
+/// Declare the JWT bearer scheme so Swagger UI can authorize requests.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
 pub fn init_swagger(config: &mut web::ServiceConfig) {
     let openapi = ApiDoc::openapi();
     config.service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-doc/openapi.json", openapi));
@@ -0,0 +1,91 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use tracing::field::Empty;
+use tracing::Instrument;
+
+use crate::telemetry::{request_id_or_new, with_request_id, REQUEST_ID_HEADER};
+
+/// Give every request an id, taken from its `X-Request-Id` header or a new one, log it in a
+/// span around everything the request runs and echo it in the response.
+///
+/// Wrap it outside of every other middleware so their logs and errors carry the id.
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let request_id = request_id_or_new(
+            req.headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        );
+        let span = tracing::info_span!(
+            "request",
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.path(),
+            route = Empty,
+            status = Empty,
+        );
+
+        Box::pin(with_request_id(request_id.clone(), async move {
+            let started_at = Instant::now();
+            let mut res = service.call(req).instrument(span.clone()).await?;
+
+            if let Some(route) = res.request().match_pattern() {
+                span.record("route", route.as_str());
+            }
+            span.record("status", res.status().as_u16());
+            span.in_scope(|| {
+                tracing::info!(
+                    elapsed_ms = started_at.elapsed().as_secs_f64() * 1000.0,
+                    "request finished"
+                )
+            });
+
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(res)
+        }))
+    }
+}
@@ -0,0 +1,5 @@
+pub use middleware::*;
+pub use model::*;
+
+mod middleware;
+mod model;
@@ -0,0 +1,168 @@
+use std::time::Instant;
+
+use actix_web::web;
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use tracing::Span;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
+
+use crate::config::LogConfig;
+use crate::error_handler::CustomError;
+
+/// Header carrying the id of a request, read from the client and echoed in the response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request id taken from a client, longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, `None` outside of [`RequestTracing`](crate::telemetry::RequestTracing).
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Run `f` with `id` as the id of the request.
+pub(crate) async fn with_request_id<F: std::future::Future>(id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(id, f).await
+}
+
+/// The id a client sent, when it is printable and not too long, or a new one.
+pub(crate) fn request_id_or_new(inbound: Option<&str>) -> String {
+    match inbound {
+        Some(id)
+            if !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            id.to_string()
+        }
+        _ => uuid::Uuid::new_v4().to_string(),
+    }
+}
+
+/// [`web::block`] keeping the span of the request, so the queries run on the blocking thread
+/// are logged with its id.
+pub async fn block<F, R>(f: F) -> Result<R, actix_web::error::BlockingError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let span = Span::current();
+    web::block(move || span.in_scope(f)).await
+}
+
+/// Logs every diesel query at debug level, in the span of the request that ran it.
+#[derive(Default)]
+struct QueryTracing {
+    started_at: Option<Instant>,
+}
+
+impl Instrumentation for QueryTracing {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } => self.started_at = Some(Instant::now()),
+            InstrumentationEvent::FinishQuery { query, error, .. } => {
+                let elapsed_ms = self
+                    .started_at
+                    .take()
+                    .map(|started_at| started_at.elapsed().as_secs_f64() * 1000.0);
+                match error {
+                    Some(e) => {
+                        tracing::warn!(target: "db", statement = %query, elapsed_ms, error = %e, "query failed")
+                    }
+                    None => tracing::debug!(target: "db", statement = %query, elapsed_ms, "query"),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn query_tracing() -> Option<Box<dyn Instrumentation>> {
+    Some(Box::new(QueryTracing::default()))
+}
+
+/// Keeps the exporter of the spans running, dropping it sends the last ones.
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed flushing the spans: {e}");
+            }
+        }
+    }
+}
+
+/// Send the logs of the server and of its `log` dependencies to stdout, as text or JSON, and
+/// the spans to the OpenTelemetry collector when one is configured.
+pub fn init(config: &LogConfig) -> Result<Telemetry, CustomError> {
+    let filter = EnvFilter::try_new(&config.level)
+        .map_err(|e| CustomError::new(500, format!("Invalid log level: {e}")))?;
+    let output = match config.format.as_str() {
+        "json" => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+        _ => fmt::layer().boxed(),
+    };
+
+    #[cfg(feature = "otlp")]
+    let (otel, provider) = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let provider = otlp_provider(endpoint)?;
+            let tracer = opentelemetry::trace::TracerProvider::tracer(&provider, "api_rust");
+            (
+                Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+                Some(provider),
+            )
+        }
+        None => (None, None),
+    };
+    #[cfg(not(feature = "otlp"))]
+    let otel: Option<Box<dyn Layer<Registry> + Send + Sync>> = None;
+
+    Registry::default()
+        .with(otel)
+        .with(output)
+        .with(filter)
+        .try_init()
+        .map_err(|e| CustomError::new(500, format!("Failed setting up the logs: {e}")))?;
+    diesel::connection::set_default_instrumentation(query_tracing)?;
+
+    Ok(Telemetry {
+        #[cfg(feature = "otlp")]
+        provider,
+    })
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_provider(
+    endpoint: &str,
+) -> Result<opentelemetry_sdk::trace::SdkTracerProvider, CustomError> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .map_err(|e| CustomError::new(500, format!("Failed creating the span exporter: {e}")))?;
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name("api_rust")
+                .build(),
+        )
+        .build())
+}
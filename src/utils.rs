@@ -3,16 +3,120 @@ pub mod response {
 
     use utoipa::ToSchema;
 
-    use crate::books::Books;
+    use crate::audit::Audits;
+    use crate::authors::Authors;
+    use crate::books::{BookDetails, Books};
+    use crate::holds::Holds;
+    use crate::items::Items;
+    use crate::loans::Loans;
     use crate::members::Members;
+    use crate::publishers::Publishers;
+    use crate::search::SearchResult;
+    use crate::subjects::Subjects;
 
     #[derive(ToSchema)]
     pub struct MembersResponse {
         pub Ok: Vec<Members>,
+        /// Members matching the filters, across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
     }
     #[derive(ToSchema)]
     pub struct BooksResponse {
-        pub Ok: Vec<Books>,
+        pub Ok: Vec<BookDetails>,
+        /// Books matching the filters, across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
+    }
+    #[derive(ToSchema)]
+    pub struct AuthorsResponse {
+        pub Ok: Vec<Authors>,
+        /// Authors across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
+    }
+    #[derive(ToSchema)]
+    pub struct PublishersResponse {
+        pub Ok: Vec<Publishers>,
+        /// Publishers across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
+    }
+    #[derive(ToSchema)]
+    pub struct SubjectsResponse {
+        pub Ok: Vec<Subjects>,
+        /// Subjects across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
+    }
+    #[derive(ToSchema)]
+    pub struct ItemsResponse {
+        pub Ok: Vec<Items>,
+        /// Items matching the filters, across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
+    }
+    #[derive(ToSchema)]
+    pub struct AuditResponse {
+        pub Ok: Vec<Audits>,
+        /// Entries matching the filters, across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
+    }
+    #[derive(ToSchema)]
+    pub struct SearchResponse {
+        pub Ok: Vec<SearchResult>,
+        /// Books matching the search, across all pages.
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        /// Link to the next page, missing on the last one.
+        pub next: Option<String>,
+        /// Link to the previous page, missing on the first one.
+        pub previous: Option<String>,
+    }
+    #[derive(ToSchema)]
+    pub struct HoldsResponse {
+        pub Ok: Vec<Holds>,
+    }
+    #[derive(ToSchema)]
+    pub struct LoansResponse {
+        pub Ok: Vec<Loans>,
     }
     #[derive(ToSchema)]
     pub struct MemberResponse {
@@ -26,17 +130,1730 @@ pub mod response {
     pub struct DeleteResponse {
         pub deleted: usize,
     }
-    #[derive(ToSchema)]
-    pub struct ErrorResponse {
-        pub Err: String,
+    #[derive(ToSchema)]
+    pub struct ErrorResponse {
+        pub Err: String,
+    }
+}
+
+pub mod pagination {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::Serialize;
+
+    use crate::error_handler::CustomError;
+    use crate::utils::check;
+
+    pub const DEFAULT_LIMIT: i64 = 50;
+    pub const MAX_LIMIT: i64 = 500;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Sort {
+        pub field: String,
+        pub descending: bool,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Pagination {
+        pub limit: i64,
+        pub offset: i64,
+        pub sort: Vec<Sort>,
+    }
+
+    impl Pagination {
+        /// Take `limit`, `offset` and `sort` out of the query params and check them.
+        ///
+        /// `sort` is a comma separated list of fields, a leading `-` sorts that field in
+        /// descending order. Only the fields in `sortable` are accepted.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use lib_api::utils::pagination::{Pagination, Sort};
+        /// use std::collections::HashMap;
+        ///
+        /// let mut params = HashMap::new();
+        /// params.insert("title".to_string(), "a title".to_string());
+        /// params.insert("limit".to_string(), "10".to_string());
+        /// params.insert("sort".to_string(), "title,-id".to_string());
+        ///
+        /// let pagination = Pagination::from_params(&mut params, &["id", "title"]).unwrap();
+        /// assert_eq!(10, pagination.limit);
+        /// assert_eq!(0, pagination.offset);
+        /// assert_eq!(
+        ///     vec![
+        ///         Sort { field: "title".to_string(), descending: false },
+        ///         Sort { field: "id".to_string(), descending: true },
+        ///     ],
+        ///     pagination.sort
+        /// );
+        /// assert_eq!(1, params.len());
+        /// ```
+        ///
+        /// ```
+        /// use lib_api::utils::pagination::Pagination;
+        /// use std::collections::HashMap;
+        ///
+        /// let mut params = HashMap::new();
+        /// params.insert("sort".to_string(), "-age".to_string());
+        ///
+        /// match Pagination::from_params(&mut params, &["id", "title"]) {
+        ///     Err(e) if e.to_string() == "can not sort by 'age', use one of: id, title" => (),
+        ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+        ///     Ok(_) => panic!("Returned an Ok variant!"),
+        /// }
+        /// ```
+        pub fn from_params(
+            params: &mut HashMap<String, String>,
+            sortable: &[&str],
+        ) -> Result<Self, CustomError> {
+            let limit = match params.remove("limit") {
+                Some(limit) => i64::from(check::validate_int(&limit)?),
+                None => DEFAULT_LIMIT,
+            };
+            if !(1..=MAX_LIMIT).contains(&limit) {
+                return Err(CustomError::new(
+                    400,
+                    format!("limit must be between 1 and {MAX_LIMIT}"),
+                ));
+            }
+
+            let offset = match params.remove("offset") {
+                Some(offset) => i64::from(check::validate_int(&offset)?),
+                None => 0,
+            };
+            if offset < 0 {
+                return Err(CustomError::new(
+                    400,
+                    "offset must not be negative".to_string(),
+                ));
+            }
+
+            let mut sort = Vec::new();
+            if let Some(fields) = params.remove("sort") {
+                for field in fields.split(',').filter(|field| !field.is_empty()) {
+                    let (field, descending) = match field.strip_prefix('-') {
+                        Some(field) => (field, true),
+                        None => (field, false),
+                    };
+                    if !sortable.contains(&field) {
+                        return Err(CustomError::new(
+                            400,
+                            format!(
+                                "can not sort by '{field}', use one of: {}",
+                                sortable.join(", ")
+                            ),
+                        ));
+                    }
+                    sort.push(Sort {
+                        field: field.to_string(),
+                        descending,
+                    });
+                }
+            }
+
+            Ok(Pagination {
+                limit,
+                offset,
+                sort,
+            })
+        }
+
+        fn link(&self, path: &str, params: &HashMap<String, String>, offset: i64) -> String {
+            let mut query: BTreeMap<&str, String> = params
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.clone()))
+                .collect();
+            query.insert("limit", self.limit.to_string());
+            query.insert("offset", offset.to_string());
+            if !self.sort.is_empty() {
+                let sort: Vec<String> = self
+                    .sort
+                    .iter()
+                    .map(|sort| match sort.descending {
+                        true => format!("-{}", sort.field),
+                        false => sort.field.clone(),
+                    })
+                    .collect();
+                query.insert("sort", sort.join(","));
+            }
+
+            match serde_urlencoded::to_string(&query) {
+                Ok(query) => format!("{path}?{query}"),
+                Err(_) => path.to_string(),
+            }
+        }
+    }
+
+    /// One page of a listing, with links to the pages around it.
+    #[derive(Serialize)]
+    #[allow(non_snake_case)]
+    pub struct Page<T> {
+        pub Ok: Vec<T>,
+        pub total: i64,
+        pub limit: i64,
+        pub offset: i64,
+        pub next: Option<String>,
+        pub previous: Option<String>,
+    }
+
+    impl<T> Page<T> {
+        /// Wrap `items` found for `path` with the `params` of the request, which are kept in the
+        /// `next` and `previous` links.
+        pub fn new(
+            items: Vec<T>,
+            total: i64,
+            pagination: &Pagination,
+            path: &str,
+            params: &HashMap<String, String>,
+        ) -> Self {
+            let next = match pagination.offset + pagination.limit < total {
+                true => Some(pagination.link(path, params, pagination.offset + pagination.limit)),
+                false => None,
+            };
+            let previous = match pagination.offset > 0 {
+                true => Some(pagination.link(
+                    path,
+                    params,
+                    (pagination.offset - pagination.limit).max(0),
+                )),
+                false => None,
+            };
+
+            Page {
+                Ok: items,
+                total,
+                limit: pagination.limit,
+                offset: pagination.offset,
+                next,
+                previous,
+            }
+        }
+    }
+}
+
+pub mod import {
+    use std::collections::HashMap;
+
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::http::StatusCode;
+    use actix_web::{web, HttpRequest};
+    use diesel::result::Error as DieselError;
+    use diesel_async::{AsyncConnection, AsyncPgConnection};
+    use futures_util::future::BoxFuture;
+    use futures_util::StreamExt;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use utoipa::ToSchema;
+
+    use crate::error_handler::CustomError;
+
+    /// Largest upload accepted by the import endpoints.
+    pub const MAX_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+    /// Values of the `status` of a [`RowReport`].
+    pub mod status {
+        pub const CREATED: &str = "created";
+        pub const SKIPPED: &str = "skipped";
+        pub const FAILED: &str = "failed";
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Format {
+        Csv,
+        Ndjson,
+    }
+
+    impl Format {
+        /// Format named by the `format` query param or by a `Content-Type`.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use lib_api::utils::import::Format;
+        ///
+        /// assert_eq!(Some(Format::Csv), Format::parse("text/csv; charset=utf-8"));
+        /// assert_eq!(Some(Format::Ndjson), Format::parse("ndjson"));
+        /// assert_eq!(Some(Format::Ndjson), Format::parse("application/x-ndjson"));
+        /// assert_eq!(None, Format::parse("application/json"));
+        /// ```
+        pub fn parse(value: &str) -> Option<Self> {
+            let media_type = value.split(';').next().unwrap_or("").trim();
+            match media_type.to_lowercase().as_str() {
+                "csv" | "text/csv" => Some(Format::Csv),
+                "ndjson"
+                | "jsonl"
+                | "application/x-ndjson"
+                | "application/ndjson"
+                | "application/jsonl"
+                | "application/x-jsonlines" => Some(Format::Ndjson),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Options {
+        pub format: Format,
+        pub dry_run: bool,
+    }
+
+    impl Options {
+        /// Take `format` and `dry_run` out of the query params. Without `format` the
+        /// `Content-Type` of the upload decides, and any other param is rejected.
+        pub fn from_request(
+            req: &HttpRequest,
+            params: &mut HashMap<String, String>,
+        ) -> Result<Self, CustomError> {
+            let format = match params.remove("format") {
+                Some(format) => Some(format),
+                None => req
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|content_type| content_type.to_str().ok())
+                    .map(str::to_string),
+            };
+            let format = format.as_deref().and_then(Format::parse).ok_or_else(|| {
+                CustomError::new(
+                    415,
+                    "upload a CSV (text/csv) or NDJSON (application/x-ndjson) file".to_string(),
+                )
+            })?;
+
+            let dry_run = match params.remove("dry_run").as_deref() {
+                None | Some("false") | Some("0") => false,
+                Some("true") | Some("1") => true,
+                Some(value) => {
+                    return Err(CustomError::new(
+                        400,
+                        format!("dry_run must be true or false, not '{value}'"),
+                    ))
+                }
+            };
+
+            if let Some(key) = params.keys().next() {
+                return Err(CustomError::new(
+                    400,
+                    format!("the parameter '{key}' is incorrect"),
+                ));
+            }
+
+            Ok(Options { format, dry_run })
+        }
+    }
+
+    /// Read the whole upload, refusing it past [`MAX_UPLOAD_BYTES`].
+    pub async fn read_upload(mut payload: web::Payload) -> Result<Vec<u8>, CustomError> {
+        let mut body = Vec::new();
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.map_err(|err| CustomError::new(400, err.to_string()))?;
+            if body.len() + chunk.len() > MAX_UPLOAD_BYTES {
+                return Err(CustomError::new(
+                    413,
+                    format!("uploads are limited to {MAX_UPLOAD_BYTES} bytes"),
+                ));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    /// Parse every row of `body`, along with its line number in the upload. A row that can not
+    /// be read keeps the reason instead, so the rows after it are still reported.
+    ///
+    /// CSV uploads start with a header naming the fields, NDJSON uploads hold one JSON object
+    /// per line and may have blank lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::books::Book;
+    /// use lib_api::utils::import::{self, Format};
+    ///
+    /// let csv = "title,isbn\nDune,9780441013593\nEmma\n";
+    /// let rows = import::parse::<Book>(Format::Csv, csv.as_bytes()).unwrap();
+    /// assert_eq!(2, rows[0].0);
+    /// assert_eq!("Dune", rows[0].1.as_ref().unwrap().title);
+    /// assert_eq!(3, rows[1].0);
+    /// assert!(rows[1].1.is_err());
+    ///
+    /// let ndjson = "{\"title\": \"Dune\", \"isbn\": \"9780441013593\"}\n\n{}\n";
+    /// let rows = import::parse::<Book>(Format::Ndjson, ndjson.as_bytes()).unwrap();
+    /// assert_eq!(1, rows[0].0);
+    /// assert_eq!(3, rows[1].0);
+    /// assert!(rows[1].1.is_err());
+    /// ```
+    ///
+    /// ```
+    /// use lib_api::books::Book;
+    /// use lib_api::utils::import::{self, Format};
+    ///
+    /// match import::parse::<Book>(Format::Csv, b"title,isbn\n") {
+    ///     Err(e) if e.to_string() == "the upload has no rows" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn parse<T: DeserializeOwned>(
+        format: Format,
+        body: &[u8],
+    ) -> Result<Vec<(u64, Result<T, String>)>, CustomError> {
+        let rows = match format {
+            Format::Csv => parse_csv(body)?,
+            Format::Ndjson => parse_ndjson(body),
+        };
+        if rows.is_empty() {
+            return Err(CustomError::new(400, "the upload has no rows".to_string()));
+        }
+        Ok(rows)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn parse_csv<T: DeserializeOwned>(
+        body: &[u8],
+    ) -> Result<Vec<(u64, Result<T, String>)>, CustomError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body);
+        let headers = reader
+            .headers()
+            .map_err(|err| CustomError::new(400, format!("the CSV header is invalid: {err}")))?
+            .clone();
+
+        let mut rows = Vec::new();
+        let mut record = csv::StringRecord::new();
+        loop {
+            let line = reader.position().line();
+            match reader.read_record(&mut record) {
+                Ok(false) => break,
+                Ok(true) => {
+                    let line = record.position().map_or(line, |position| position.line());
+                    let row = record
+                        .deserialize::<T>(Some(&headers))
+                        .map_err(|err| err.to_string());
+                    rows.push((line, row));
+                }
+                Err(err) if matches!(err.kind(), csv::ErrorKind::Io(_)) => {
+                    return Err(CustomError::new(400, err.to_string()))
+                }
+                Err(err) => {
+                    let line = err.position().map_or(line, |position| position.line());
+                    rows.push((line, Err(err.to_string())));
+                }
+            }
+        }
+        Ok(rows)
+    }
+
+    fn parse_ndjson<T: DeserializeOwned>(body: &[u8]) -> Vec<(u64, Result<T, String>)> {
+        body.split(|byte| *byte == b'\n')
+            .enumerate()
+            .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|(index, line)| {
+                let row = serde_json::from_slice::<T>(line).map_err(|err| err.to_string());
+                (index as u64 + 1, row)
+            })
+            .collect()
+    }
+
+    /// What happened to a row that was read without errors.
+    pub enum Outcome {
+        /// Inserted with this id.
+        Created(i32),
+        /// Already in the database with this id, with the reason it is considered the same.
+        Skipped(i32, String),
+    }
+
+    #[derive(Serialize, ToSchema)]
+    pub struct RowReport {
+        /// Line of the row in the upload.
+        pub row: u64,
+        /// One of created, skipped or failed.
+        pub status: String,
+        /// Id of the created record, or of the existing one for skipped rows.
+        pub id: Option<i32>,
+        /// Why the row was skipped or failed.
+        pub reason: Option<String>,
+    }
+
+    #[derive(Serialize, ToSchema)]
+    pub struct ImportReport {
+        pub dry_run: bool,
+        /// Whether the created rows were saved. Nothing is saved on a dry run or when a row
+        /// failed.
+        pub committed: bool,
+        pub created: usize,
+        pub skipped: usize,
+        pub failed: usize,
+        pub rows: Vec<RowReport>,
+    }
+
+    impl ImportReport {
+        /// Run `insert` on every row read without errors, all of them in one transaction.
+        ///
+        /// Each row gets its own savepoint, so a row refused by the database is reported and
+        /// the rest of the batch carries on. The transaction is only committed when no row
+        /// failed and this is not a dry run, so a dry run reports exactly what a real import
+        /// would do. `insert` returns a boxed future, as it borrows the connection of the
+        /// savepoint.
+        pub async fn run<T, F>(
+            conn: &mut AsyncPgConnection,
+            rows: Vec<(u64, Result<T, String>)>,
+            dry_run: bool,
+            mut insert: F,
+        ) -> Result<Self, CustomError>
+        where
+            T: Send,
+            F: for<'c> FnMut(
+                    &'c mut AsyncPgConnection,
+                    T,
+                ) -> BoxFuture<'c, Result<Outcome, CustomError>>
+                + Send,
+        {
+            let mut report = ImportReport {
+                dry_run,
+                committed: false,
+                created: 0,
+                skipped: 0,
+                failed: 0,
+                rows: Vec::with_capacity(rows.len()),
+            };
+
+            let result = conn
+                .transaction::<_, DieselError, _>(async |conn| {
+                    for (row, record) in rows {
+                        let outcome = match record {
+                            Ok(record) => {
+                                conn.transaction(async |conn| insert(conn, record).await)
+                                    .await
+                            }
+                            Err(reason) => Err(CustomError::new(400, reason)),
+                        };
+                        report.push(row, outcome);
+                    }
+                    match dry_run || report.failed > 0 {
+                        true => Err(DieselError::RollbackTransaction),
+                        false => Ok(()),
+                    }
+                })
+                .await;
+
+            match result {
+                Ok(()) => report.committed = true,
+                Err(DieselError::RollbackTransaction) => (),
+                Err(err) => return Err(err.into()),
+            }
+            Ok(report)
+        }
+
+        fn push(&mut self, row: u64, outcome: Result<Outcome, CustomError>) {
+            let (status, id, reason) = match outcome {
+                Ok(Outcome::Created(id)) => {
+                    self.created += 1;
+                    (status::CREATED, Some(id), None)
+                }
+                Ok(Outcome::Skipped(id, reason)) => {
+                    self.skipped += 1;
+                    (status::SKIPPED, Some(id), Some(reason))
+                }
+                Err(err) => {
+                    self.failed += 1;
+                    (status::FAILED, None, Some(err.error_message))
+                }
+            };
+            self.rows.push(RowReport {
+                row,
+                status: status.to_string(),
+                id,
+                reason,
+            });
+        }
+
+        /// 422 when a row failed, so clients notice nothing was saved.
+        pub fn status_code(&self) -> StatusCode {
+            match self.failed {
+                0 => StatusCode::OK,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            }
+        }
+    }
+}
+
+pub mod batch {
+    use actix_web::http::StatusCode;
+    use diesel::result::Error as DieselError;
+    use diesel_async::{AsyncConnection, AsyncPgConnection};
+    use futures_util::future::BoxFuture;
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+    use utoipa::ToSchema;
+
+    use crate::error_handler::CustomError;
+
+    /// Most operations accepted in one batch.
+    pub const MAX_OPERATIONS: usize = 1000;
+
+    /// How a batch treats the operations that succeeded when another one failed.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Mode {
+        /// Nothing is saved unless every operation succeeds.
+        #[default]
+        Atomic,
+        /// The operations that succeed are saved.
+        BestEffort,
+    }
+
+    /// One operation of a batch, `data` holds the same fields as the body of `POST` or `PUT`.
+    #[derive(Debug, Deserialize)]
+    #[serde(tag = "op", rename_all = "lowercase")]
+    pub enum Operation<T> {
+        Create {
+            data: T,
+        },
+        Update {
+            id: i32,
+            data: T,
+            /// Version the record must be at, like `If-Match`. The operation fails with a 428
+            /// when it is missing.
+            version: Option<i32>,
+        },
+        Delete {
+            id: i32,
+        },
+    }
+
+    #[derive(Deserialize)]
+    pub struct Batch {
+        #[serde(default)]
+        pub mode: Mode,
+        /// Read one by one, so a malformed operation only fails itself.
+        pub operations: Vec<Value>,
+    }
+
+    /// Result of an operation, shaped like the response it would get on its own route.
+    #[derive(Serialize, ToSchema)]
+    pub struct OperationReport {
+        /// Position of the operation in the batch.
+        pub index: usize,
+        pub status: u16,
+        #[serde(rename = "Ok", skip_serializing_if = "Option::is_none")]
+        #[schema(value_type = Option<Object>)]
+        pub ok: Option<Value>,
+        #[serde(rename = "Err", skip_serializing_if = "Option::is_none")]
+        pub err: Option<String>,
+    }
+
+    #[derive(Serialize, ToSchema)]
+    pub struct BatchReport {
+        pub mode: Mode,
+        /// Whether the operations that succeeded were saved.
+        pub committed: bool,
+        pub succeeded: usize,
+        pub failed: usize,
+        pub results: Vec<OperationReport>,
+    }
+
+    impl BatchReport {
+        /// Run `apply` on every operation of `batch`, all of them in one transaction.
+        ///
+        /// Each operation gets its own savepoint, so a failed one leaves no trace and the rest
+        /// of the batch carries on. In [`Mode::Atomic`] the transaction is rolled back when an
+        /// operation failed, in [`Mode::BestEffort`] it is always committed. Like the insert of
+        /// an import, `apply` returns a boxed future.
+        pub async fn run<T, F>(
+            conn: &mut AsyncPgConnection,
+            batch: Batch,
+            mut apply: F,
+        ) -> Result<Self, CustomError>
+        where
+            T: DeserializeOwned + Send,
+            F: for<'c> FnMut(
+                    &'c mut AsyncPgConnection,
+                    Operation<T>,
+                ) -> BoxFuture<'c, Result<Value, CustomError>>
+                + Send,
+        {
+            if batch.operations.is_empty() {
+                return Err(CustomError::new(
+                    400,
+                    "the batch has no operations".to_string(),
+                ));
+            }
+            if batch.operations.len() > MAX_OPERATIONS {
+                return Err(CustomError::new(
+                    413,
+                    format!("a batch takes at most {MAX_OPERATIONS} operations"),
+                ));
+            }
+
+            let mut report = BatchReport {
+                mode: batch.mode,
+                committed: false,
+                succeeded: 0,
+                failed: 0,
+                results: Vec::with_capacity(batch.operations.len()),
+            };
+
+            let result = conn
+                .transaction::<_, DieselError, _>(async |conn| {
+                    for (index, operation) in batch.operations.into_iter().enumerate() {
+                        let outcome = match serde_json::from_value::<Operation<T>>(operation) {
+                            Ok(operation) => {
+                                conn.transaction(async |conn| apply(conn, operation).await)
+                                    .await
+                            }
+                            Err(e) => Err(CustomError::new(400, format!("bad operation: {e}"))),
+                        };
+                        report.push(index, outcome);
+                    }
+                    match report.mode == Mode::Atomic && report.failed > 0 {
+                        true => Err(DieselError::RollbackTransaction),
+                        false => Ok(()),
+                    }
+                })
+                .await;
+
+            match result {
+                Ok(()) => report.committed = true,
+                Err(DieselError::RollbackTransaction) => (),
+                Err(err) => return Err(err.into()),
+            }
+            Ok(report)
+        }
+
+        fn push(&mut self, index: usize, outcome: Result<Value, CustomError>) {
+            let result = match outcome {
+                Ok(value) => {
+                    self.succeeded += 1;
+                    OperationReport {
+                        index,
+                        status: 200,
+                        ok: Some(value),
+                        err: None,
+                    }
+                }
+                Err(err) => {
+                    self.failed += 1;
+                    // Same as the response of a single request, internal errors are not told.
+                    let message = match err.error_status_code < 500 {
+                        true => err.error_message,
+                        false => "Internal server error".to_string(),
+                    };
+                    OperationReport {
+                        index,
+                        status: err.error_status_code,
+                        ok: None,
+                        err: Some(message),
+                    }
+                }
+            };
+            self.results.push(result);
+        }
+
+        /// 422 when the batch was rolled back, so clients notice nothing was saved.
+        pub fn status_code(&self) -> StatusCode {
+            match self.committed {
+                true => StatusCode::OK,
+                false => StatusCode::UNPROCESSABLE_ENTITY,
+            }
+        }
+    }
+
+    /// The `Ok` of an operation that returns `record`.
+    pub fn ok<T: Serialize>(record: &T) -> Result<Value, CustomError> {
+        serde_json::to_value(record)
+            .map_err(|e| CustomError::new(500, format!("Failed serializing record: {e}")))
+    }
+}
+
+pub mod export {
+    use std::collections::HashMap;
+    use std::future::Future;
+
+    use actix_web::web::Bytes;
+    use futures_util::stream::{self, Stream};
+    use serde::Serialize;
+
+    use crate::error_handler::CustomError;
+    use crate::utils::import::Format;
+
+    /// Rows read from the database for every chunk of an export.
+    pub const CHUNK_ROWS: i64 = 1000;
+
+    /// Take the `format` query param out of `params`, CSV when it is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::utils::export;
+    /// use lib_api::utils::import::Format;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut params = HashMap::new();
+    /// params.insert("format".to_string(), "ndjson".to_string());
+    /// assert_eq!(Format::Ndjson, export::format(&mut params).unwrap());
+    /// assert_eq!(Format::Csv, export::format(&mut params).unwrap());
+    ///
+    /// params.insert("format".to_string(), "xml".to_string());
+    /// match export::format(&mut params) {
+    ///     Err(e) if e.to_string() == "format must be csv or ndjson, not 'xml'" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    pub fn format(params: &mut HashMap<String, String>) -> Result<Format, CustomError> {
+        match params.remove("format") {
+            None => Ok(Format::Csv),
+            Some(format) => match format.as_str() {
+                "csv" => Ok(Format::Csv),
+                "ndjson" => Ok(Format::Ndjson),
+                _ => Err(CustomError::new(
+                    400,
+                    format!("format must be csv or ndjson, not '{format}'"),
+                )),
+            },
+        }
+    }
+
+    /// `Content-Type` of an export in `format`.
+    pub fn content_type(format: Format) -> &'static str {
+        match format {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// `Content-Disposition` saving an export in `format` as `name` with the matching extension.
+    pub fn content_disposition(name: &str, format: Format) -> String {
+        let extension = match format {
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+        };
+        format!("attachment; filename=\"{name}.{extension}\"")
+    }
+
+    /// Stream every row returned by `fetch`, encoded in `format`.
+    ///
+    /// `fetch` returns at most [`CHUNK_ROWS`] rows with an id above the one it is given, in
+    /// ascending id order, and `id` reads the id of a row. Chunks are read one at a time, so
+    /// only one of them is held in memory however many rows are exported.
+    pub fn rows<T, F, Fut>(
+        format: Format,
+        fetch: F,
+        id: fn(&T) -> i32,
+    ) -> impl Stream<Item = Result<Bytes, CustomError>>
+    where
+        T: Serialize + 'static,
+        F: Fn(i32) -> Fut + 'static,
+        Fut: Future<Output = Result<Vec<T>, CustomError>>,
+    {
+        stream::unfold(Some((i32::MIN, true)), move |state| {
+            let rows = state.map(|(after, first)| (fetch(after), first));
+            async move {
+                let (rows, first) = rows?;
+                let rows = match rows.await {
+                    Ok(rows) => rows,
+                    Err(err) => return Some((Err(err), None)),
+                };
+                if rows.is_empty() && !first {
+                    return None;
+                }
+
+                let next = match (rows.last(), rows.len() as i64) {
+                    (Some(last), len) if len >= CHUNK_ROWS => Some((id(last), false)),
+                    _ => None,
+                };
+                Some((encode(format, &rows, first), next))
+            }
+        })
+    }
+
+    fn encode<T: Serialize>(format: Format, rows: &[T], first: bool) -> Result<Bytes, CustomError> {
+        let error = |err: String| CustomError::new(500, format!("can not export a row: {err}"));
+        let mut buffer = Vec::new();
+        match format {
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(first)
+                    .from_writer(&mut buffer);
+                for row in rows {
+                    writer
+                        .serialize(row)
+                        .map_err(|err| error(err.to_string()))?;
+                }
+                writer.flush().map_err(|err| error(err.to_string()))?;
+            }
+            Format::Ndjson => {
+                for row in rows {
+                    serde_json::to_writer(&mut buffer, row)
+                        .map_err(|err| error(err.to_string()))?;
+                    buffer.push(b'\n');
+                }
+            }
+        }
+        Ok(Bytes::from(buffer))
+    }
+}
+
+pub mod purge {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::config;
+    use crate::error_handler::CustomError;
+    use crate::repository::Repositories;
+
+    /// Time between two purges.
+    const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    /// How long deleted books and members can still be restored.
+    pub fn retention() -> chrono::Duration {
+        chrono::Duration::days(config::get().purge.retention_days)
+    }
+
+    /// Remove for good the books and members deleted longer than [`retention`] ago, and return
+    /// how many of each were removed.
+    pub async fn run(repositories: &Repositories) -> Result<(usize, usize), CustomError> {
+        let deleted_before = Utc::now().naive_utc() - retention();
+        Ok((
+            repositories.books.purge(deleted_before).await?,
+            repositories.members.purge(deleted_before).await?,
+        ))
+    }
+
+    /// Purge every hour for as long as the server runs.
+    pub async fn schedule(repositories: Repositories) {
+        let mut interval = actix_rt::time::interval(INTERVAL);
+        loop {
+            interval.tick().await;
+            match run(&repositories).await {
+                Ok((books, members)) => {
+                    tracing::info!("Purged {books} deleted books and {members} deleted members")
+                }
+                Err(e) => tracing::error!("Failed purging deleted records: {e}"),
+            }
+        }
+    }
+}
+
+pub mod migrate {
+    use std::collections::HashSet;
+    use std::fmt;
+
+    use diesel::migration::{Migration, MigrationSource};
+    use diesel::pg::{Pg, PgConnection};
+    use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+    use crate::db;
+    use crate::error_handler::CustomError;
+
+    /// The `migrations/` directory, built into the binary.
+    pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+    /// Where a migration stands against the database.
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum Status {
+        Applied,
+        Pending,
+        /// Applied to the database but not built into this binary.
+        Missing,
+    }
+
+    impl fmt::Display for Status {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.pad(match self {
+                Status::Applied => "applied",
+                Status::Pending => "pending",
+                Status::Missing => "missing",
+            })
+        }
+    }
+
+    fn failed(error: impl fmt::Display) -> CustomError {
+        CustomError::new(500, format!("Failed running the migrations: {error}"))
+    }
+
+    type Migrations = Vec<Box<dyn Migration<Pg>>>;
+
+    /// The embedded migrations in version order, and the versions applied to the database.
+    fn versions(conn: &mut PgConnection) -> Result<(Migrations, Vec<String>), CustomError> {
+        let mut migrations = MigrationSource::<Pg>::migrations(&MIGRATIONS).map_err(failed)?;
+        migrations.sort_unstable_by(|a, b| a.name().version().cmp(&b.name().version()));
+        let mut applied: Vec<String> = conn
+            .applied_migrations()
+            .map_err(failed)?
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        applied.sort_unstable();
+        Ok((migrations, applied))
+    }
+
+    /// Every embedded migration with its status, then the applied ones missing from the build.
+    pub fn status(conn: &mut PgConnection) -> Result<Vec<(String, Status)>, CustomError> {
+        let (migrations, applied) = versions(conn)?;
+        let embedded: HashSet<String> = migrations
+            .iter()
+            .map(|m| m.name().version().to_string())
+            .collect();
+
+        let mut statuses: Vec<(String, Status)> = migrations
+            .iter()
+            .map(|m| {
+                let status = match applied.contains(&m.name().version().to_string()) {
+                    true => Status::Applied,
+                    false => Status::Pending,
+                };
+                (m.name().to_string(), status)
+            })
+            .collect();
+        statuses.extend(
+            applied
+                .into_iter()
+                .filter(|version| !embedded.contains(version))
+                .map(|version| (version, Status::Missing)),
+        );
+        Ok(statuses)
+    }
+
+    /// Apply the pending migrations in version order and return their names.
+    ///
+    /// Nothing is applied when the database has a migration this build does not know of, or
+    /// when a pending migration is older than the last applied one, since running it after
+    /// the newer ones could break them.
+    pub fn up() -> Result<Vec<String>, CustomError> {
+        let mut conn = db::connection()?;
+        let (migrations, applied) = versions(&mut conn)?;
+        let embedded: HashSet<String> = migrations
+            .iter()
+            .map(|m| m.name().version().to_string())
+            .collect();
+
+        if let Some(version) = applied.iter().find(|version| !embedded.contains(*version)) {
+            return Err(CustomError::new(
+                409,
+                format!("the applied migration {version} is missing from this build"),
+            ));
+        }
+        let pending: Vec<&Box<dyn Migration<Pg>>> = migrations
+            .iter()
+            .filter(|m| !applied.contains(&m.name().version().to_string()))
+            .collect();
+        if let (Some(first), Some(last)) = (pending.first(), applied.last()) {
+            if first.name().version().to_string() < *last {
+                return Err(CustomError::new(
+                    409,
+                    format!(
+                        "the migration {} is older than the applied migration {last}",
+                        first.name()
+                    ),
+                ));
+            }
+        }
+
+        pending
+            .into_iter()
+            .map(|m| {
+                conn.run_migration(m.as_ref()).map_err(failed)?;
+                Ok(m.name().to_string())
+            })
+            .collect()
+    }
+
+    /// Revert the last applied migration and return its name.
+    pub fn down() -> Result<String, CustomError> {
+        let mut conn = db::connection()?;
+        let (migrations, applied) = versions(&mut conn)?;
+        let last = applied
+            .last()
+            .ok_or_else(|| CustomError::new(409, "no migration is applied".to_string()))?;
+        let migration = migrations
+            .iter()
+            .find(|m| m.name().version().to_string() == *last)
+            .ok_or_else(|| {
+                CustomError::new(
+                    409,
+                    format!("the applied migration {last} is missing from this build"),
+                )
+            })?;
+        conn.revert_migration(migration.as_ref()).map_err(failed)?;
+        Ok(migration.name().to_string())
+    }
+}
+
+pub mod etag {
+    use std::future::{ready, Ready};
+
+    use actix_web::dev::Payload;
+    use actix_web::http::header::{ETag, EntityTag, IfMatch};
+    use actix_web::{FromRequest, HttpMessage, HttpRequest};
+
+    use crate::error_handler::CustomError;
+
+    /// `ETag` header of a record at `version`.
+    pub fn header(version: i32) -> ETag {
+        ETag(EntityTag::new_strong(version.to_string()))
+    }
+
+    /// The `If-Match` header a handler requires before it changes a record. Requests without
+    /// it are rejected with a 428.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use actix_web::test::TestRequest;
+    /// use lib_api::utils::etag::Precondition;
+    ///
+    /// let req = TestRequest::default()
+    ///     .insert_header(("If-Match", "\"3\""))
+    ///     .to_http_request();
+    /// let precondition = Precondition::from_request(&req).unwrap();
+    /// assert!(precondition.check(3).is_ok());
+    /// match precondition.check(4) {
+    ///     Err(e) if e.to_string() == "the record changed since it was read, its ETag is now \"4\"" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct Precondition(IfMatch);
+
+    impl Precondition {
+        /// Accept the record only at `version`.
+        pub fn version(version: i32) -> Self {
+            Precondition(IfMatch::Items(vec![EntityTag::new_strong(
+                version.to_string(),
+            )]))
+        }
+
+        /// Accept the record only at `version`, which has to be given like an `If-Match` header.
+        pub fn required(version: Option<i32>) -> Result<Self, CustomError> {
+            version.map(Precondition::version).ok_or_else(|| {
+                CustomError::new(428, "send the version of the record to update".to_string())
+            })
+        }
+
+        pub fn from_request(req: &HttpRequest) -> Result<Self, CustomError> {
+            req.get_header::<IfMatch>()
+                .map(Precondition)
+                .ok_or_else(|| {
+                    CustomError::new(
+                        428,
+                        "send the ETag of the record in an If-Match header".to_string(),
+                    )
+                })
+        }
+
+        /// Fail with a 412 unless the header matches the record at `version`.
+        pub fn check(&self, version: i32) -> Result<(), CustomError> {
+            let current = EntityTag::new_strong(version.to_string());
+            let matches = match &self.0 {
+                IfMatch::Any => true,
+                IfMatch::Items(tags) => tags.iter().any(|tag| tag.strong_eq(&current)),
+            };
+            match matches {
+                true => Ok(()),
+                false => Err(CustomError::new(
+                    412,
+                    format!("the record changed since it was read, its ETag is now {current}"),
+                )),
+            }
+        }
+    }
+
+    impl FromRequest for Precondition {
+        type Error = CustomError;
+        type Future = Ready<Result<Self, Self::Error>>;
+
+        fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+            ready(Precondition::from_request(req))
+        }
+    }
+}
+
+pub mod patch {
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::HttpRequest;
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::error_handler::CustomError;
+
+    pub const MERGE_PATCH: &str = "application/merge-patch+json";
+    pub const JSON_PATCH: &str = "application/json-patch+json";
+
+    /// One operation of a JSON Patch (RFC 6902).
+    #[derive(Debug, Clone, PartialEq, Deserialize)]
+    #[serde(tag = "op", rename_all = "lowercase")]
+    pub enum Operation {
+        Add { path: String, value: Value },
+        Remove { path: String },
+        Replace { path: String, value: Value },
+        Move { from: String, path: String },
+        Copy { from: String, path: String },
+        Test { path: String, value: Value },
+    }
+
+    /// The body of a PATCH request, a JSON Merge Patch (RFC 7396) or a JSON Patch (RFC 6902)
+    /// depending on its `Content-Type`.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Patch {
+        Merge(Value),
+        Json(Vec<Operation>),
+    }
+
+    impl Patch {
+        /// Read `body` as the patch its `Content-Type` names, other types are rejected with a 415.
+        pub fn from_request(req: &HttpRequest, body: &[u8]) -> Result<Self, CustomError> {
+            let content_type = req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .and_then(|content_type| content_type.split(';').next())
+                .map(|media_type| media_type.trim().to_lowercase());
+            let malformed = |e: serde_json::Error| CustomError::new(400, format!("bad patch: {e}"));
+
+            match content_type.as_deref() {
+                Some(MERGE_PATCH) => Ok(Patch::Merge(
+                    serde_json::from_slice(body).map_err(malformed)?,
+                )),
+                Some(JSON_PATCH) => Ok(Patch::Json(
+                    serde_json::from_slice(body).map_err(malformed)?,
+                )),
+                _ => Err(CustomError::new(
+                    415,
+                    format!("send the patch as {MERGE_PATCH} or {JSON_PATCH}"),
+                )),
+            }
+        }
+
+        /// Apply the patch to the fields of `record` and read the result back, fields that
+        /// `record` does not have can not be added.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use lib_api::books::Book;
+        /// use lib_api::utils::patch::Patch;
+        /// use serde_json::json;
+        ///
+        /// let book = Book {
+        ///     title: "Dune".to_string(),
+        ///     isbn: "9780441013593".to_string(),
+        /// };
+        /// let patch = Patch::Merge(json!({"title": "Dune Messiah"}));
+        /// assert_eq!("Dune Messiah", patch.apply(&book).unwrap().title);
+        ///
+        /// let patch = Patch::Json(serde_json::from_value(json!([
+        ///     {"op": "test", "path": "/title", "value": "Dune"},
+        ///     {"op": "copy", "from": "/title", "path": "/isbn"}
+        /// ])).unwrap());
+        /// assert_eq!("Dune", patch.apply(&book).unwrap().isbn);
+        ///
+        /// let patch = Patch::Merge(json!({"copies": 3}));
+        /// match patch.apply(&book) {
+        ///     Err(e) if e.to_string() == "the field 'copies' can not be patched" => (),
+        ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+        ///     Ok(_) => panic!("Returned an Ok variant!"),
+        /// }
+        /// ```
+        pub fn apply<T: Serialize + DeserializeOwned>(&self, record: &T) -> Result<T, CustomError> {
+            let original = serde_json::to_value(record)
+                .map_err(|e| CustomError::new(500, format!("Failed serializing record: {e}")))?;
+            let mut document = original.clone();
+            match self {
+                Patch::Merge(patch) => merge(&mut document, patch),
+                Patch::Json(operations) => {
+                    for operation in operations {
+                        apply_operation(&mut document, operation)?;
+                    }
+                }
+            }
+
+            let fields = original.as_object();
+            match document.as_object() {
+                Some(patched) => {
+                    if let Some(key) = patched
+                        .keys()
+                        .find(|key| !fields.is_some_and(|fields| fields.contains_key(*key)))
+                    {
+                        return Err(unprocessable(format!(
+                            "the field '{key}' can not be patched"
+                        )));
+                    }
+                }
+                None => return Err(unprocessable("the patch must leave an object".to_string())),
+            }
+            serde_json::from_value(document).map_err(|e| unprocessable(e.to_string()))
+        }
+    }
+
+    fn unprocessable(message: String) -> CustomError {
+        CustomError::new(422, message)
+    }
+
+    /// Apply a JSON Merge Patch to `target`: objects are merged key by key and `null` removes
+    /// a key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::utils::patch::merge;
+    /// use serde_json::json;
+    ///
+    /// let mut member = json!({"first_name": "Ada", "address": "Marylebone", "age": 36});
+    /// merge(&mut member, &json!({"age": 37, "address": null}));
+    /// assert_eq!(member, json!({"first_name": "Ada", "age": 37}));
+    /// ```
+    pub fn merge(target: &mut Value, patch: &Value) {
+        let Value::Object(patch) = patch else {
+            *target = patch.clone();
+            return;
+        };
+        if !target.is_object() {
+            *target = Value::Object(Default::default());
+        }
+        if let Value::Object(target) = target {
+            for (key, value) in patch {
+                match value {
+                    Value::Null => {
+                        target.remove(key);
+                    }
+                    value => merge(target.entry(key.clone()).or_insert(Value::Null), value),
+                }
+            }
+        }
+    }
+
+    fn apply_operation(document: &mut Value, operation: &Operation) -> Result<(), CustomError> {
+        match operation {
+            Operation::Add { path, value } => add(document, path, value.clone()),
+            Operation::Remove { path } => remove(document, path).map(|_| ()),
+            Operation::Replace { path, value } => {
+                let target = document.pointer_mut(path).ok_or_else(|| missing(path))?;
+                *target = value.clone();
+                Ok(())
+            }
+            Operation::Move { from, path } => {
+                if path.starts_with(&format!("{from}/")) {
+                    return Err(unprocessable(format!(
+                        "'{from}' can not be moved into itself"
+                    )));
+                }
+                let value = remove(document, from)?;
+                add(document, path, value)
+            }
+            Operation::Copy { from, path } => {
+                let value = document.pointer(from).ok_or_else(|| missing(from))?.clone();
+                add(document, path, value)
+            }
+            Operation::Test { path, value } => match document.pointer(path) {
+                Some(current) if current == value => Ok(()),
+                _ => Err(CustomError::new(
+                    409,
+                    format!("the test of '{path}' failed"),
+                )),
+            },
+        }
+    }
+
+    fn missing(path: &str) -> CustomError {
+        unprocessable(format!("the path '{path}' does not exist"))
+    }
+
+    /// Split a JSON Pointer into the pointer of its parent and its last, unescaped, token.
+    fn split(path: &str) -> Result<(&str, String), CustomError> {
+        let (parent, token) = path
+            .rsplit_once('/')
+            .ok_or_else(|| CustomError::new(400, format!("bad patch: '{path}' is not a path")))?;
+        Ok((parent, token.replace("~1", "/").replace("~0", "~")))
+    }
+
+    fn add(document: &mut Value, path: &str, value: Value) -> Result<(), CustomError> {
+        if path.is_empty() {
+            *document = value;
+            return Ok(());
+        }
+        let (parent, token) = split(path)?;
+        match document.pointer_mut(parent) {
+            Some(Value::Object(object)) => {
+                object.insert(token, value);
+                Ok(())
+            }
+            Some(Value::Array(array)) => {
+                let index = match token.as_str() {
+                    "-" => array.len(),
+                    token => token
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|index| *index <= array.len())
+                        .ok_or_else(|| missing(path))?,
+                };
+                array.insert(index, value);
+                Ok(())
+            }
+            _ => Err(missing(path)),
+        }
+    }
+
+    fn remove(document: &mut Value, path: &str) -> Result<Value, CustomError> {
+        let (parent, token) = split(path)?;
+        let removed = match document.pointer_mut(parent) {
+            Some(Value::Object(object)) => object.remove(&token),
+            Some(Value::Array(array)) => token
+                .parse::<usize>()
+                .ok()
+                .filter(|index| *index < array.len())
+                .map(|index| array.remove(index)),
+            _ => None,
+        };
+        removed.ok_or_else(|| missing(path))
+    }
+}
+
+pub mod named {
+    //! Authors, publishers and subjects are each a bare name that books point to through a join
+    //! table, so their model and routes are written once, here.
+
+    /// The model of a named resource kept in `$table` and joined to the books by `$books`:
+    /// `$record` holds the fields of a create or an update and `$records` a saved row.
+    macro_rules! named_model {
+        ($table:ident, $books:ident, $record:ident, $records:ident, $sortable:ident) => {
+            use diesel::prelude::*;
+
+            use $crate::db;
+            use $crate::error_handler::CustomError;
+            use $crate::schema::{$books, $table};
+            use $crate::utils::pagination::Pagination;
+
+            #[derive(
+                serde::Serialize,
+                serde::Deserialize,
+                diesel::AsChangeset,
+                diesel::Insertable,
+                utoipa::ToSchema,
+            )]
+            #[diesel(table_name = $table)]
+            pub struct $record {
+                pub name: String,
+            }
+
+            #[doc = concat!("Fields accepted by the `sort` param of `GET /", stringify!($table), "`.")]
+            pub const $sortable: [&str; 2] = ["id", "name"];
+
+            #[derive(serde::Serialize, serde::Deserialize, diesel::Queryable, utoipa::ToSchema)]
+            #[diesel(table_name = $table)]
+            pub struct $records {
+                pub id: i32,
+                pub name: String,
+            }
+
+            impl $records {
+                pub fn find_all(pagination: Pagination) -> Result<(Vec<Self>, i64), CustomError> {
+                    let mut conn = db::connection()?;
+                    let total = $table::table.count().get_result::<i64>(&mut conn)?;
+
+                    let mut query = $table::table.into_boxed();
+                    for sort in &pagination.sort {
+                        query = match (sort.field.as_str(), sort.descending) {
+                            ("id", false) => query.then_order_by($table::id.asc()),
+                            ("id", true) => query.then_order_by($table::id.desc()),
+                            ("name", false) => query.then_order_by($table::name.asc()),
+                            ("name", true) => query.then_order_by($table::name.desc()),
+                            _ => query,
+                        };
+                    }
+
+                    let records = query
+                        .then_order_by($table::id.asc())
+                        .limit(pagination.limit)
+                        .offset(pagination.offset)
+                        .load::<$records>(&mut conn)?;
+                    Ok((records, total))
+                }
+
+                pub fn find(id: i32) -> Result<Self, CustomError> {
+                    let mut conn = db::connection()?;
+                    let record = $table::table
+                        .filter($table::id.eq(id))
+                        .first(&mut conn)
+                        .optional()?
+                        .ok_or_else(|| $records::not_found(id))?;
+                    Ok(record)
+                }
+
+                #[doc = concat!(stringify!($records), " of each of `book_ids`, paired with the id of the book.")]
+                pub async fn of_books(
+                    conn: &mut diesel_async::AsyncPgConnection,
+                    book_ids: &[i32],
+                ) -> Result<Vec<(i32, Self)>, CustomError> {
+                    let query = $books::table
+                        .inner_join($table::table)
+                        .filter($books::book_id.eq_any(book_ids))
+                        .select(($books::book_id, ($table::id, $table::name)))
+                        .order(($books::book_id, $table::name, $table::id));
+                    let records =
+                        diesel_async::RunQueryDsl::load::<(i32, $records)>(query, conn).await?;
+                    Ok(records)
+                }
+
+                pub fn create(record: $record) -> Result<Self, CustomError> {
+                    record.validate()?;
+                    let mut conn = db::connection()?;
+                    let record = diesel::insert_into($table::table)
+                        .values(record)
+                        .get_result(&mut conn)?;
+                    Ok(record)
+                }
+
+                pub fn update(id: i32, record: $record) -> Result<Self, CustomError> {
+                    record.validate()?;
+                    let mut conn = db::connection()?;
+                    let record = diesel::update($table::table)
+                        .filter($table::id.eq(id))
+                        .set(record)
+                        .get_result(&mut conn)
+                        .optional()?
+                        .ok_or_else(|| $records::not_found(id))?;
+                    Ok(record)
+                }
+
+                /// Delete the record `id`, which is also removed from its books.
+                pub fn delete(id: i32) -> Result<usize, CustomError> {
+                    let mut conn = db::connection()?;
+                    let res = diesel::delete($table::table.filter($table::id.eq(id)))
+                        .execute(&mut conn)?;
+                    Ok(res)
+                }
+
+                fn not_found(id: i32) -> CustomError {
+                    CustomError::new(404, format!("{} {id} not found", stringify!($record)))
+                }
+            }
+
+            impl $record {
+                pub fn validate(&self) -> Result<(), CustomError> {
+                    if self.name.trim().is_empty() {
+                        return Err(CustomError::new(400, "name can not be empty".to_string()));
+                    }
+                    Ok(())
+                }
+            }
+        };
+    }
+
+    /// The CRUD routes of a named resource made by [`named_model`], reads for any role and
+    /// writes for staff, each running its queries through [`crate::telemetry::block`]. The paths
+    /// and descriptions are given as literals for the OpenAPI documentation, and `conflict`
+    /// documents the 409 of a resource whose names are unique.
+    macro_rules! named_routes {
+        (
+            records: $records:ident,
+            record: $record:ident,
+            sortable: $sortable:ident,
+            page: $page:ident,
+            list: $list:tt,
+            one: $one:tt,
+            find_all: $find_all:tt,
+            limit: $limit:tt,
+            offset: $offset:tt,
+            find: $find:tt,
+            create: $create:tt,
+            update: $update:tt,
+            delete: $delete:tt,
+            $(conflict: $conflict:tt,)?
+        ) => {
+            use std::collections::HashMap;
+
+            use actix_web::{web, HttpRequest, HttpResponse};
+
+            use $crate::error_handler::CustomError;
+            use $crate::telemetry;
+            use $crate::utils::pagination::{Page, Pagination};
+            use $crate::utils::response;
+
+            #[utoipa::path(
+                get,
+                path = $list,
+                responses(
+                    (status = 200, description = $find_all, body = inline(response::$page)),
+                    (status = 400, description = "Error", body = inline(response::ErrorResponse))
+                ),
+                params(
+                    ("limit" = Option<i64>, Query, description = $limit),
+                    ("offset" = Option<i64>, Query, description = $offset),
+                    ("sort" = Option<String>, Query, description = "Comma separated fields to sort by, prefix a field with '-' for descending order example (name,-id)"),
+                )
+            )]
+            #[actix_web::get($list, wrap = "crate::auth::RequireRole::any()")]
+            async fn find_all(
+                req: HttpRequest,
+                _param: web::Query<HashMap<String, String>>,
+            ) -> Result<HttpResponse, CustomError> {
+                let mut params = _param.into_inner();
+                let pagination = Pagination::from_params(&mut params, &$sortable)?;
+
+                let page = pagination.clone();
+                let (records, total) = telemetry::block(move || $records::find_all(page))
+                    .await
+                    .unwrap()?;
+
+                Ok(HttpResponse::Ok().json(Page::new(
+                    records,
+                    total,
+                    &pagination,
+                    req.path(),
+                    &HashMap::new(),
+                )))
+            }
+
+            #[utoipa::path(
+                get,
+                path = $one,
+                responses(
+                    (status = 200, description = $find, body = inline($records)),
+                    (status = 404, description = "Error", body = inline(response::ErrorResponse))
+                )
+            )]
+            #[actix_web::get($one, wrap = "crate::auth::RequireRole::any()")]
+            async fn find(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+                let id = id.into_inner();
+                let record = telemetry::block(move || $records::find(id))
+                    .await
+                    .unwrap()?;
+                Ok(HttpResponse::Ok().json(record))
+            }
+
+            #[utoipa::path(
+                post,
+                path = $list,
+                request_body = $record,
+                responses(
+                    (status = 200, description = $create, body = inline($records)),
+                    (status = 400, description = "Error", body = inline(response::ErrorResponse))
+                    $(, (status = 409, description = $conflict, body = inline(response::ErrorResponse)))?
+                )
+            )]
+            #[actix_web::post($list, wrap = "crate::auth::RequireRole::staff()")]
+            async fn create(record: web::Json<$record>) -> Result<HttpResponse, CustomError> {
+                let record = record.into_inner();
+                let record = telemetry::block(move || $records::create(record))
+                    .await
+                    .unwrap()?;
+                Ok(HttpResponse::Ok().json(record))
+            }
+
+            #[utoipa::path(
+                put,
+                path = $one,
+                request_body = $record,
+                responses(
+                    (status = 200, description = $update, body = inline($records)),
+                    (status = 400, description = "Error", body = inline(response::ErrorResponse)),
+                    (status = 404, description = "Error", body = inline(response::ErrorResponse))
+                    $(, (status = 409, description = $conflict, body = inline(response::ErrorResponse)))?
+                )
+            )]
+            #[actix_web::put($one, wrap = "crate::auth::RequireRole::staff()")]
+            async fn update(
+                id: web::Path<i32>,
+                record: web::Json<$record>,
+            ) -> Result<HttpResponse, CustomError> {
+                let (id, record) = (id.into_inner(), record.into_inner());
+                let record = telemetry::block(move || $records::update(id, record))
+                    .await
+                    .unwrap()?;
+                Ok(HttpResponse::Ok().json(record))
+            }
+
+            #[utoipa::path(
+                delete,
+                path = $one,
+                responses(
+                    (status = 200, description = $delete, body = inline(response::DeleteResponse)),
+                    (status = 400, description = "Error", body = inline(response::ErrorResponse))
+                )
+            )]
+            #[actix_web::delete($one, wrap = "crate::auth::RequireRole::staff()")]
+            async fn delete(id: web::Path<i32>) -> Result<HttpResponse, CustomError> {
+                let id = id.into_inner();
+                let deleted = telemetry::block(move || $records::delete(id))
+                    .await
+                    .unwrap()?;
+                Ok(HttpResponse::Ok().json(serde_json::json!({ "deleted": deleted })))
+            }
+
+            pub fn init_routes(config: &mut web::ServiceConfig) {
+                config.service(find_all);
+                config.service(find);
+                config.service(create);
+                config.service(update);
+                config.service(delete);
+            }
+        };
     }
+
+    pub(crate) use named_model;
+    pub(crate) use named_routes;
 }
 
 pub mod check {
     use std::collections::HashMap;
+    use std::fmt;
+    use std::str::FromStr;
 
+    use crate::auth::Role;
     use crate::error_handler::CustomError;
 
+    /// Take the `include_deleted` param out of the query params. Deleted records are hidden
+    /// unless an admin asks for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::auth::Role;
+    /// use lib_api::utils::check;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut params = HashMap::from([("include_deleted".to_string(), "true".to_string())]);
+    /// assert!(check::include_deleted(&mut params, Role::Admin).unwrap());
+    /// assert!(params.is_empty());
+    ///
+    /// let mut params = HashMap::from([("include_deleted".to_string(), "true".to_string())]);
+    /// match check::include_deleted(&mut params, Role::Librarian) {
+    ///     Err(e) if e.to_string() == "only admins can see deleted records" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    pub fn include_deleted(
+        params: &mut HashMap<String, String>,
+        role: Role,
+    ) -> Result<bool, CustomError> {
+        let include = match params.remove("include_deleted").as_deref() {
+            None | Some("false") | Some("0") => false,
+            Some("true") | Some("1") => true,
+            Some(value) => {
+                return Err(CustomError::new(
+                    400,
+                    format!("include_deleted must be true or false, not '{value}'"),
+                ))
+            }
+        };
+        if include && role != Role::Admin {
+            return Err(CustomError::new(
+                403,
+                "only admins can see deleted records".to_string(),
+            ));
+        }
+        Ok(include)
+    }
+
     /// Check if a &str is a int number.
     ///
     /// # Examples
@@ -123,7 +1940,414 @@ pub mod check {
         Ok(ids)
     }
 
-    /// Check if a params for member are correct.
+    /// An ISBN in canonical ISBN-13 form, digits only.
+    ///
+    /// Parsing accepts ISBN-10 and ISBN-13 with or without hyphens and spaces, checks the check
+    /// digit and converts ISBN-10 to the ISBN-13 of the same book.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::utils::check::Isbn;
+    ///
+    /// let isbn: Isbn = "0-441-01359-7".parse().unwrap();
+    /// assert_eq!("9780441013593", isbn.as_str());
+    /// assert_eq!(isbn, "978 0 441 01359 3".parse().unwrap());
+    /// assert_eq!(Some("0441013597".to_string()), isbn.to_isbn10());
+    ///
+    /// let isbn: Isbn = "080442957X".parse().unwrap();
+    /// assert_eq!("9780804429573", isbn.as_str());
+    /// assert_eq!(Some("080442957X".to_string()), isbn.to_isbn10());
+    /// ```
+    ///
+    /// ```
+    /// use lib_api::utils::check::Isbn;
+    ///
+    /// match "9780441013594".parse::<Isbn>() {
+    ///     Err(e) if e.to_string() == "isbn '9780441013594' has a wrong check digit" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    ///
+    /// match "1234".parse::<Isbn>() {
+    ///     Err(e) if e.to_string() == "isbn '1234' is not an ISBN-10 or ISBN-13" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    ///
+    /// match "12345678é".parse::<Isbn>() {
+    ///     Err(e) if e.to_string() == "isbn '12345678é' is not an ISBN-10 or ISBN-13" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Isbn(String);
+
+    impl Isbn {
+        pub fn as_str(&self) -> &str {
+            &self.0
+        }
+
+        /// The ISBN-10 form, which only exists for ISBN-13 starting with 978.
+        pub fn to_isbn10(&self) -> Option<String> {
+            let body = self.0.strip_prefix("978")?.get(..9)?;
+            let sum: u32 = digits(body)
+                .zip((2..=10).rev())
+                .map(|(digit, weight)| digit * weight)
+                .sum();
+            let check = match (11 - sum % 11) % 11 {
+                10 => 'X',
+                check => char::from_digit(check, 10)?,
+            };
+            Some(format!("{body}{check}"))
+        }
+
+        /// `value` without the hyphens and spaces that are commonly written in an ISBN.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use lib_api::utils::check::Isbn;
+        ///
+        /// assert_eq!("978044101", Isbn::strip("978-0 441-01"));
+        /// ```
+        pub fn strip(value: &str) -> String {
+            value
+                .chars()
+                .filter(|c| *c != '-' && !c.is_whitespace())
+                .map(|c| c.to_ascii_uppercase())
+                .collect()
+        }
+
+        /// What an `isbn` filter compares to: the canonical form of a valid ISBN for the exact
+        /// operators, and `value` without separators for the rest.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use lib_api::utils::check::{Isbn, Operator};
+        ///
+        /// assert_eq!("9780441013593", Isbn::filter_value(Operator::Eq, "0-441-01359-7"));
+        /// assert_eq!("0441", Isbn::filter_value(Operator::StartsWith, "0-441"));
+        /// ```
+        pub fn filter_value(operator: Operator, value: &str) -> String {
+            match (operator, value.parse::<Isbn>()) {
+                (Operator::Eq | Operator::Ne | Operator::IExact, Ok(isbn)) => isbn.to_string(),
+                _ => Isbn::strip(value),
+            }
+        }
+    }
+
+    impl fmt::Display for Isbn {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    impl FromStr for Isbn {
+        type Err = CustomError;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            let isbn = Isbn::strip(value);
+            if !isbn.is_ascii() {
+                return Err(CustomError::new(
+                    400,
+                    format!("isbn '{value}' is not an ISBN-10 or ISBN-13"),
+                ));
+            }
+            let valid = match isbn.len() {
+                10 if isbn[..9].bytes().all(|c| c.is_ascii_digit())
+                    && (isbn.as_bytes()[9].is_ascii_digit() || isbn.ends_with('X')) =>
+                {
+                    let sum: u32 = digits(&isbn[..9])
+                        .chain([isbn[9..].parse().unwrap_or(10)])
+                        .zip((1..=10).rev())
+                        .map(|(digit, weight)| digit * weight)
+                        .sum();
+                    sum.is_multiple_of(11)
+                }
+                13 if isbn.bytes().all(|c| c.is_ascii_digit()) => {
+                    isbn13_check(&isbn[..12]).is_some_and(|check| isbn.ends_with(check))
+                }
+                _ => {
+                    return Err(CustomError::new(
+                        400,
+                        format!("isbn '{value}' is not an ISBN-10 or ISBN-13"),
+                    ))
+                }
+            };
+            if !valid {
+                return Err(CustomError::new(
+                    400,
+                    format!("isbn '{value}' has a wrong check digit"),
+                ));
+            }
+
+            match isbn.len() {
+                10 => {
+                    let body = format!("978{}", &isbn[..9]);
+                    let check = isbn13_check(&body).unwrap_or('0');
+                    Ok(Isbn(format!("{body}{check}")))
+                }
+                _ => Ok(Isbn(isbn)),
+            }
+        }
+    }
+
+    fn digits(value: &str) -> impl Iterator<Item = u32> + '_ {
+        value.chars().filter_map(|c| c.to_digit(10))
+    }
+
+    /// Check digit of the first 12 digits of an ISBN-13.
+    fn isbn13_check(body: &str) -> Option<char> {
+        let sum: u32 = digits(body)
+            .zip([1, 3].into_iter().cycle())
+            .map(|(digit, weight)| digit * weight)
+            .sum();
+        char::from_digit((10 - sum % 10) % 10, 10)
+    }
+
+    /// Comparison applied by a filter param, taken from the `__<operator>` suffix of its key.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Operator {
+        Eq,
+        Ne,
+        Gt,
+        Gte,
+        Lt,
+        Lte,
+        Contains,
+        IContains,
+        StartsWith,
+        EndsWith,
+        IExact,
+    }
+
+    impl Operator {
+        /// Operators that make sense on integer fields.
+        pub fn is_numeric(&self) -> bool {
+            matches!(
+                self,
+                Operator::Eq
+                    | Operator::Ne
+                    | Operator::Gt
+                    | Operator::Gte
+                    | Operator::Lt
+                    | Operator::Lte
+            )
+        }
+
+        /// Operators that make sense on text fields.
+        pub fn is_text(&self) -> bool {
+            matches!(
+                self,
+                Operator::Eq
+                    | Operator::Ne
+                    | Operator::Contains
+                    | Operator::IContains
+                    | Operator::StartsWith
+                    | Operator::EndsWith
+                    | Operator::IExact
+            )
+        }
+    }
+
+    /// Split a filter param key into its field and operator, `age__gte` is `age` compared with
+    /// `>=`. A key without suffix is an exact match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::utils::check::{self, Operator};
+    ///
+    /// assert_eq!(("age", Operator::Gte), check::parse_filter("age__gte").unwrap());
+    /// assert_eq!(("title", Operator::Eq), check::parse_filter("title").unwrap());
+    /// ```
+    ///
+    /// ```
+    /// use lib_api::utils::check;
+    ///
+    /// match check::parse_filter("age__between") {
+    ///     Err(e) if e.to_string() == "the operator 'between' in 'age__between' is incorrect" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    pub fn parse_filter(key: &str) -> Result<(&str, Operator), CustomError> {
+        let (field, operator) = match key.split_once("__") {
+            Some((field, operator)) => (field, operator),
+            None => return Ok((key, Operator::Eq)),
+        };
+
+        let operator = match operator {
+            "eq" => Operator::Eq,
+            "ne" => Operator::Ne,
+            "gt" => Operator::Gt,
+            "gte" => Operator::Gte,
+            "lt" => Operator::Lt,
+            "lte" => Operator::Lte,
+            "contains" => Operator::Contains,
+            "icontains" => Operator::IContains,
+            "startswith" => Operator::StartsWith,
+            "endswith" => Operator::EndsWith,
+            "iexact" => Operator::IExact,
+            _ => {
+                return Err(CustomError::new(
+                    400,
+                    format!("the operator '{operator}' in '{key}' is incorrect"),
+                ))
+            }
+        };
+        Ok((field, operator))
+    }
+
+    /// Build the `LIKE` pattern for a text operator, escaping the wildcards typed by the user.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::utils::check::{self, Operator};
+    ///
+    /// assert_eq!("%rust%", check::like_pattern(Operator::IContains, "rust"));
+    /// assert_eq!("%@gg.com", check::like_pattern(Operator::EndsWith, "@gg.com"));
+    /// assert_eq!("100\\%%", check::like_pattern(Operator::StartsWith, "100%"));
+    /// ```
+    pub fn like_pattern(operator: Operator, value: &str) -> String {
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        match operator {
+            Operator::Contains | Operator::IContains => format!("%{value}%"),
+            Operator::StartsWith => format!("{value}%"),
+            Operator::EndsWith => format!("%{value}"),
+            _ => value,
+        }
+    }
+
+    /// Add an integer filter param, already split by [`parse_filter`], to a boxed diesel query.
+    macro_rules! filter_int {
+        ($query:expr, $column:expr, $operator:expr, $value:expr) => {{
+            use $crate::utils::check::Operator;
+            let value: i32 = $value;
+            match $operator {
+                Operator::Ne => $query.filter($column.ne(value)),
+                Operator::Gt => $query.filter($column.gt(value)),
+                Operator::Gte => $query.filter($column.ge(value)),
+                Operator::Lt => $query.filter($column.lt(value)),
+                Operator::Lte => $query.filter($column.le(value)),
+                _ => $query.filter($column.eq(value)),
+            }
+        }};
+    }
+
+    /// Add a text filter param, already split by [`parse_filter`], to a boxed diesel query.
+    macro_rules! filter_text {
+        ($query:expr, $column:expr, $operator:expr, $value:expr) => {{
+            use $crate::utils::check::{like_pattern, Operator};
+            let value: &str = $value;
+            match $operator {
+                Operator::Ne => $query.filter($column.ne(value.to_string())),
+                Operator::Contains | Operator::StartsWith | Operator::EndsWith => {
+                    $query.filter($column.like(like_pattern($operator, value)))
+                }
+                Operator::IContains | Operator::IExact => {
+                    $query.filter($column.ilike(like_pattern($operator, value)))
+                }
+                _ => $query.filter($column.eq(value.to_string())),
+            }
+        }};
+    }
+
+    pub(crate) use filter_int;
+    pub(crate) use filter_text;
+
+    /// Check filter params against the integer and text fields of a resource. `ids` is always
+    /// accepted and `id` can not be combined with it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lib_api::utils::check;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut params = HashMap::new();
+    /// params.insert("age__gte".to_string(), "18".to_string());
+    /// params.insert("email__endswith".to_string(), "@gg.com".to_string());
+    ///
+    /// match check::validate_filters(&params, &["id", "age"], &["email"]) {
+    ///     Ok(..) => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    /// }
+    /// ```
+    ///
+    /// ```
+    /// use lib_api::utils::check;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut params = HashMap::new();
+    /// params.insert("age__icontains".to_string(), "1".to_string());
+    ///
+    /// match check::validate_filters(&params, &["id", "age"], &["email"]) {
+    ///     Err(e) if e.to_string() == "the operator in 'age__icontains' can not be used on 'age'" => (),
+    ///     Err(e) => panic!("Returned incorrect Err! => {e}"),
+    ///     Ok(_) => panic!("Returned an Ok variant!"),
+    /// }
+    /// ```
+    pub fn validate_filters(
+        params: &HashMap<String, String>,
+        int_fields: &[&str],
+        text_fields: &[&str],
+    ) -> Result<bool, CustomError> {
+        for (key, value) in params {
+            if key == "ids" {
+                parse_ids(value)?;
+                continue;
+            }
+
+            let (field, operator) = parse_filter(key)?;
+
+            let valid_operator = if int_fields.contains(&field) {
+                operator.is_numeric()
+            } else if text_fields.contains(&field) {
+                operator.is_text()
+            } else {
+                return Err(CustomError::new(
+                    400,
+                    format!("the parameter '{key}' is incorrect"),
+                ));
+            };
+            if !valid_operator {
+                return Err(CustomError::new(
+                    400,
+                    format!("the operator in '{key}' can not be used on '{field}'"),
+                ));
+            }
+        }
+
+        if params.get("id").is_some() && params.get("ids").is_some() {
+            return Err(CustomError::new(
+                400,
+                "select only one of them, id xor ids".to_string(),
+            ));
+        }
+
+        for (key, value) in params {
+            if key == "ids" {
+                continue;
+            }
+            let (field, _) = parse_filter(key)?;
+            if int_fields.contains(&field) {
+                validate_int(value)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Check if a params for member are correct. Every field but `ids` takes an operator suffix,
+    /// see [`parse_filter`].
     ///
     /// pub struct Member {
     ///     pub id: i32,
@@ -193,57 +2417,15 @@ pub mod check {
     /// }
     ///```
     pub fn validate_members_params(params: &HashMap<String, String>) -> Result<bool, CustomError> {
-        let keys = vec![
-            "id".to_string(),
-            "ids".to_string(),
-            "first_name".to_string(),
-            "last_name".to_string(),
-            "email".to_string(),
-            "address".to_string(),
-            "age".to_string(),
-        ];
-
-        for key in params.keys() {
-            if !keys.contains(key) {
-                return Err(CustomError::new(
-                    400,
-                    format!("the parameter '{key}' is incorrect"),
-                ));
-            }
-        }
-
-        if params.get("id").is_some() && params.get("ids").is_some() {
-            return Err(CustomError::new(
-                400,
-                "select only one of them, id xor ids".to_string(),
-            ));
-        }
-
-        if let Some(id) = params.get("id") {
-            match validate_int(id) {
-                Ok(..) => (),
-                Err(err) => return Err(err),
-            }
-        }
-
-        if let Some(ids) = params.get("ids") {
-            match parse_ids(ids) {
-                Ok(..) => (),
-                Err(err) => return Err(err),
-            }
-        }
-
-        if let Some(age) = params.get("age") {
-            match validate_int(age) {
-                Ok(..) => (),
-                Err(err) => return Err(err),
-            }
-        }
-
-        Ok(true)
+        validate_filters(
+            params,
+            &["id", "age"],
+            &["first_name", "last_name", "email", "address"],
+        )
     }
 
-    /// Check if a params for book are correct.
+    /// Check if a params for book are correct. Every field but `ids` takes an operator suffix,
+    /// see [`parse_filter`].
     ///
     /// pub struct Member {
     ///     pub id: i32,
@@ -291,60 +2473,18 @@ pub mod check {
     /// }
     /// ```
     pub fn validate_book_params(params: &HashMap<String, String>) -> Result<bool, CustomError> {
-        let keys = vec![
-            "id".to_string(),
-            "ids".to_string(),
-            "title".to_string(),
-            "isbn".to_string(),
-            "copies_available".to_string(),
-            "copies".to_string(),
-        ];
-
-        for key in params.keys() {
-            if !keys.contains(key) {
-                return Err(CustomError::new(
-                    400,
-                    format!("the parameter '{key}' is incorrect"),
-                ));
-            }
-        }
-
-        if params.get("id").is_some() && params.get("ids").is_some() {
-            return Err(CustomError::new(
-                400,
-                "select only one of them, id xor ids".to_string(),
-            ));
-        }
-
-        if let Some(id) = params.get("id") {
-            match validate_int(id) {
-                Ok(..) => (),
-                Err(err) => return Err(err),
-            }
-        }
-
-        if let Some(ids) = params.get("ids") {
-            match parse_ids(ids) {
-                Ok(..) => (),
-                Err(err) => return Err(err),
-            }
-        }
-
-        if let Some(copies_available) = params.get("copies_available") {
-            match validate_int(copies_available) {
-                Ok(..) => (),
-                Err(err) => return Err(err),
-            }
-        }
-
-        if let Some(copies) = params.get("copies") {
-            match validate_int(copies) {
-                Ok(..) => (),
-                Err(err) => return Err(err),
-            }
-        }
-
-        Ok(true)
+        validate_filters(
+            params,
+            &[
+                "id",
+                "copies_available",
+                "copies",
+                "author_id",
+                "publisher_id",
+                "subject_id",
+            ],
+            &["title", "isbn", "author", "publisher", "subject"],
+        )
     }
 }
 
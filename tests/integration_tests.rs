@@ -1,30 +1,100 @@
+use std::env;
+
 use actix_web::test::TestRequest;
 use actix_web::{test, web, App};
+use chrono::Utc;
 use dotenv::dotenv;
+use serde_json::{json, Value};
 
+use lib_api::audit;
+use lib_api::auth;
+use lib_api::authors;
 use lib_api::books;
+use lib_api::config::{self, Config};
+use lib_api::fines;
+use lib_api::health;
+use lib_api::holds;
+use lib_api::items;
+use lib_api::loans;
 use lib_api::members;
+use lib_api::metrics;
+use lib_api::publishers;
+use lib_api::repository::Repositories;
+use lib_api::search;
+use lib_api::subjects;
+use lib_api::telemetry;
 
 fn init_routes(config: &mut web::ServiceConfig) {
+    settings().init(config);
+    Repositories::postgres().init(config);
+    health::init_routes(config);
+    metrics::init_routes(config);
+    auth::init_routes(config);
     members::init_routes(config);
     books::init_routes(config);
+    items::init_routes(config);
+    authors::init_routes(config);
+    publishers::init_routes(config);
+    subjects::init_routes(config);
+    loans::init_routes(config);
+    holds::init_routes(config);
+    fines::init_routes(config);
+    search::init_routes(config);
+    audit::init_routes(config);
+}
+
+fn bearer() -> (&'static str, String) {
+    bearer_for(auth::Role::Admin)
+}
+
+/// The configuration of the tests, with a JWT secret when the environment has none, and an
+/// in-memory SQLite database when it names none, so the SQLite test runs without Postgres.
+fn settings() -> &'static Config {
+    dotenv().ok();
+    if env::var("JWT_SECRET").is_err() {
+        env::set_var("JWT_SECRET", "integration-tests-secret");
+    }
+    if env::var("DATABASE_URL").is_err() {
+        env::set_var("DATABASE_URL", "sqlite::memory:");
+    }
+    config::get()
+}
+
+fn bearer_for(role: auth::Role) -> (&'static str, String) {
+    settings();
+    let user = auth::Users {
+        id: 1,
+        username: "tests".to_string(),
+        password_hash: String::new(),
+        created_at: Utc::now().naive_utc(),
+        role: role.to_string(),
+    };
+    let token = auth::create_token(&user).unwrap();
+    ("Authorization", format!("Bearer {}", token.token))
 }
 
 #[actix_rt::test]
 async fn get_all_members() {
-    let app = test::init_service(App::new().configure(init_routes)).await;
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
     dotenv().ok();
 
-    let resp = TestRequest::get().uri("/members").send_request(&app).await;
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/members")
+        .send_request(&app)
+        .await;
     assert!(resp.status().is_success(), "Failed to find members");
 }
 
 #[actix_rt::test]
 async fn get_members() {
     dotenv().ok();
-    let app = test::init_service(App::new().configure(init_routes)).await;
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
 
     let resp = TestRequest::get()
+        .insert_header(bearer())
         .uri("/members/filter?id=1&first_name=username")
         .send_request(&app)
         .await;
@@ -34,20 +104,1533 @@ async fn get_members() {
 #[actix_rt::test]
 async fn get_all_books() {
     dotenv().ok();
-    let app = test::init_service(App::new().configure(init_routes)).await;
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
 
-    let resp = TestRequest::get().uri("/books").send_request(&app).await;
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books")
+        .send_request(&app)
+        .await;
     assert!(resp.status().is_success(), "Failed to find books");
 }
 
 #[actix_rt::test]
 async fn get_books() {
     dotenv().ok();
-    let app = test::init_service(App::new().configure(init_routes)).await;
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
 
     let resp = TestRequest::get()
+        .insert_header(bearer())
         .uri("/books/filter?id=1&isbn=1234")
         .send_request(&app)
         .await;
     assert!(resp.status().is_success(), "Failed to find books");
 }
+
+#[actix_rt::test]
+async fn checkout_and_return_book() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "loan_title", "isbn": "9780000001016"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/items")
+        .set_json(json!({"barcode": "9780000001016-1", "book_id": book["id"]}))
+        .to_request();
+    let _: Value = test::call_and_read_body_json(&app, req).await;
+    let book_id = book["id"].as_i64().unwrap();
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/loans")
+        .set_json(json!({"member_id": 1, "book_id": book_id}))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to check out book");
+    let loan: Value = test::read_body_json(resp).await;
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/loans")
+        .set_json(json!({"member_id": 1, "book_id": book_id}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Checked out a book without copies");
+
+    let resp = TestRequest::delete()
+        .insert_header(bearer())
+        .uri(&format!("/books/{book_id}"))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Deleted a book that is lent");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri(&format!("/loans/{}/return", loan["id"]))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to return book");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri(&format!("/loans/{}/return", loan["id"]))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Returned a loan twice");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/books/{book_id}"))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(book["copies_available"], 1);
+}
+
+#[actix_rt::test]
+async fn returned_copy_goes_to_hold_queue() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "hold_title", "isbn": "9780000001023"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/items")
+        .set_json(json!({"barcode": "9780000001023-1", "book_id": book["id"]}))
+        .to_request();
+    let _: Value = test::call_and_read_body_json(&app, req).await;
+    let req = TestRequest::post().insert_header(bearer())
+        .uri("/members")
+        .set_json(json!({"first_name": "hold", "last_name": "member", "email": "hold@gg.com", "address": "elm street", "age": 30}))
+        .to_request();
+    let member: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/loans")
+        .set_json(json!({"member_id": 1, "book_id": book["id"]}))
+        .to_request();
+    let loan: Value = test::call_and_read_body_json(&app, req).await;
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/holds")
+        .set_json(json!({"member_id": member["id"], "book_id": book["id"]}))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to place hold");
+    let hold: Value = test::read_body_json(resp).await;
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/holds/{}/position", hold["id"]))
+        .to_request();
+    let position: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(position["position"], 1);
+
+    let resp = TestRequest::delete()
+        .insert_header(bearer())
+        .uri(&format!("/members/{}", member["id"]))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Deleted a member with a waiting hold");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri(&format!("/loans/{}/return", loan["id"]))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to return book");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/holds/{}", hold["id"]))
+        .to_request();
+    let hold: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(hold["status"], "ready");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/loans")
+        .set_json(json!({"member_id": 1, "book_id": book["id"]}))
+        .send_request(&app)
+        .await;
+    assert_eq!(
+        resp.status(),
+        409,
+        "Checked out a copy set aside for a hold"
+    );
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/loans")
+        .set_json(json!({"member_id": member["id"], "book_id": book["id"]}))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to check out held copy");
+}
+
+#[actix_rt::test]
+async fn new_copy_goes_to_hold_queue() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "queued_title", "isbn": "9780000001153"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    let mut holds = Vec::new();
+    for name in ["first", "second"] {
+        let req = TestRequest::post().insert_header(bearer())
+            .uri("/members")
+            .set_json(json!({"first_name": name, "last_name": "queued", "email": format!("{name}.queued@gg.com"), "address": "elm street", "age": 30}))
+            .to_request();
+        let member: Value = test::call_and_read_body_json(&app, req).await;
+        let req = TestRequest::post()
+            .insert_header(bearer())
+            .uri("/holds")
+            .set_json(json!({"member_id": member["id"], "book_id": book["id"]}))
+            .to_request();
+        let hold: Value = test::call_and_read_body_json(&app, req).await;
+        holds.push(hold);
+    }
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/items")
+        .set_json(json!({"barcode": "9780000001153-1", "book_id": book["id"]}))
+        .to_request();
+    let item: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(item["status"], "on_hold");
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/holds/{}", holds[0]["id"]))
+        .to_request();
+    let hold: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(hold["status"], "ready");
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/items")
+        .set_json(json!({"barcode": "9780000001153-2", "book_id": book["id"], "status": "damaged"}))
+        .to_request();
+    let item: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(item["status"], "damaged");
+    let req = TestRequest::put()
+        .insert_header(bearer())
+        .uri(&format!("/items/{}", item["id"]))
+        .set_json(
+            json!({"barcode": "9780000001153-2", "book_id": book["id"], "status": "available"}),
+        )
+        .to_request();
+    let item: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(item["status"], "on_hold");
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/holds/{}", holds[1]["id"]))
+        .to_request();
+    let hold: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(hold["status"], "ready");
+}
+
+#[actix_rt::test]
+async fn payments_reduce_member_balance() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post().insert_header(bearer())
+        .uri("/members")
+        .set_json(json!({"first_name": "fine", "last_name": "member", "email": "fine@gg.com", "address": "elm street", "age": 40}))
+        .to_request();
+    let member: Value = test::call_and_read_body_json(&app, req).await;
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri(&format!("/members/{}/payments", member["id"]))
+        .set_json(json!({"amount_cents": 0}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Accepted an empty payment");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri(&format!("/members/{}/payments", member["id"]))
+        .set_json(json!({"amount_cents": 150, "note": "cash"}))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to record payment");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/members/{}/balance", member["id"]))
+        .to_request();
+    let balance: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(balance["payments_cents"], 150);
+    assert_eq!(balance["balance_cents"], -150);
+}
+
+#[actix_rt::test]
+async fn reject_requests_without_token() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let resp = TestRequest::get().uri("/books").send_request(&app).await;
+    assert_eq!(resp.status(), 401, "Listed books without a token");
+
+    let resp = TestRequest::get()
+        .uri("/books")
+        .insert_header(("Authorization", "Bearer not-a-token"))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 401, "Listed books with an invalid token");
+
+    for path in ["/healthcheck-admin", "/metricsX", "/auth/login-anything"] {
+        let resp = TestRequest::get().uri(path).send_request(&app).await;
+        assert_eq!(resp.status(), 401, "Reached {path} without a token");
+    }
+}
+
+#[actix_rt::test]
+async fn login_issues_usable_token() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let credentials = json!({"username": "login_user", "password": "s3cret"});
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/users")
+        .set_json(&credentials)
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to create user");
+
+    let resp = TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({"username": "login_user", "password": "wrong"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 401, "Logged in with a wrong password");
+
+    let req = TestRequest::post()
+        .uri("/auth/login")
+        .set_json(&credentials)
+        .to_request();
+    let token: Value = test::call_and_read_body_json(&app, req).await;
+
+    let resp = TestRequest::get()
+        .uri("/members")
+        .insert_header((
+            "Authorization",
+            format!("Bearer {}", token["token"].as_str().unwrap()),
+        ))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to use login token");
+}
+
+#[actix_rt::test]
+async fn enforce_roles_per_route() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let resp = TestRequest::get()
+        .insert_header(bearer_for(auth::Role::Kiosk))
+        .uri("/books/filter?title=title_1")
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Kiosk failed to search books");
+
+    let resp = TestRequest::get()
+        .insert_header(bearer_for(auth::Role::Kiosk))
+        .uri("/members")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 403, "Kiosk listed members");
+
+    let resp = TestRequest::delete()
+        .insert_header(bearer_for(auth::Role::Kiosk))
+        .uri("/books/1")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 403, "Kiosk deleted a book");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer_for(auth::Role::Librarian))
+        .uri("/users")
+        .set_json(json!({"username": "not_created", "password": "s3cret"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 403, "Librarian created a user");
+}
+
+#[actix_rt::test]
+async fn paginate_and_sort_books() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?limit=1&offset=0&sort=-id&ids=1,2,3")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["limit"], 1);
+    assert_eq!(page["Ok"].as_array().unwrap().len(), 1);
+    assert!(page["total"].as_i64().unwrap() >= 1);
+    assert!(page["previous"].is_null());
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books?sort=author")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Sorted books by an unknown field");
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/members?limit=0")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Listed members with an empty page");
+}
+
+#[actix_rt::test]
+async fn filter_with_operators() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/members/filter?age__gte=38&age__lte=38&first_name__icontains=USER&email__endswith=@gg.com")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    let members = page["Ok"].as_array().unwrap();
+    assert!(members.iter().any(|member| member["id"] == 1));
+    assert!(members.iter().all(|member| member["age"] == 38));
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?copies_available__gt=100000")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 0);
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?copies__icontains=1")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Used a text operator on a number");
+}
+
+#[actix_rt::test]
+async fn search_books_by_partial_title() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "Programming Searchable Rust", "isbn": "978-1-59327-828-1"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = TestRequest::get()
+        .insert_header(bearer_for(auth::Role::Kiosk))
+        .uri("/search?q=searcha%20prog")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    let results = page["Ok"].as_array().unwrap();
+    let found = results
+        .iter()
+        .find(|result| result["id"] == book["id"])
+        .unwrap();
+    assert!(found["headline"].as_str().unwrap().contains("<mark>"));
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(
+            json!({"title": "Escaped <img src=x onerror=alert(1)> & co", "isbn": "9780000001146"}),
+        )
+        .to_request();
+    let escaped: Value = test::call_and_read_body_json(&app, req).await;
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/search?q=escaped")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    let results = page["Ok"].as_array().unwrap();
+    let found = results
+        .iter()
+        .find(|result| result["id"] == escaped["id"])
+        .unwrap();
+    assert_eq!(
+        found["headline"],
+        "<mark>Escaped</mark> &lt;img src=x onerror=alert(1)&gt; &amp; co"
+    );
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/search?q=9781593278281")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    let results = page["Ok"].as_array().unwrap();
+    assert!(results.iter().any(|result| result["id"] == book["id"]));
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/search")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Searched without a query");
+}
+
+#[actix_rt::test]
+async fn import_books_and_members() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let csv = "title,isbn\nimported_title,978-0-00-000103-0\nimported_title,0000001031\n";
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .insert_header(("Content-Type", "text/csv"))
+        .uri("/books/import?dry_run=true")
+        .set_payload(csv)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["committed"], false);
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["skipped"], 1);
+    assert_eq!(report["rows"][1]["row"], 3);
+    assert_eq!(report["rows"][1]["id"], report["rows"][0]["id"]);
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?isbn=9780000001030")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 0, "Saved books on a dry run");
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .insert_header(("Content-Type", "text/csv"))
+        .uri("/books/import")
+        .set_payload(csv)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["committed"], true);
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?isbn=0-00-000103-1")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["Ok"][0]["id"], report["rows"][0]["id"]);
+
+    let ndjson = concat!(
+        r#"{"first_name": "imported", "last_name": "member", "email": "imported@gg.com", "address": "elm street", "age": 20}"#,
+        "\n",
+        r#"{"first_name": "imported", "last_name": "member", "email": "not-an-email", "address": "elm street", "age": 20}"#,
+        "\n",
+    );
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/members/import?format=ndjson")
+        .set_payload(ndjson)
+        .send_request(&app)
+        .await;
+    assert_eq!(
+        resp.status(),
+        422,
+        "Imported a member with an invalid email"
+    );
+    let report: Value = test::read_body_json(resp).await;
+    assert_eq!(report["committed"], false);
+    assert_eq!(report["rows"][1]["status"], "failed");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/members/filter?email=imported@gg.com")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 0, "Saved part of a failed import");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer_for(auth::Role::Kiosk))
+        .insert_header(("Content-Type", "text/csv"))
+        .uri("/books/import")
+        .set_payload(csv)
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 403, "Kiosk imported books");
+}
+
+#[actix_rt::test]
+async fn create_and_import_check_rows_alike() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+    let member = json!({"first_name": "aged", "last_name": "member", "email": "aged@gg.com", "address": "elm street", "age": 200});
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/members")
+        .set_json(&member)
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Created a member aged 200");
+    let error: Value = test::read_body_json(resp).await;
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/members/import?format=ndjson")
+        .set_payload(member.to_string())
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 422, "Imported a member aged 200");
+    let report: Value = test::read_body_json(resp).await;
+    assert_eq!(report["rows"][0]["reason"], error["Err"]);
+    assert_eq!(error["Err"], "age must be between 0 and 150");
+}
+
+#[actix_rt::test]
+async fn export_filtered_books() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    for isbn in ["9780000001047", "9780000001054"] {
+        let req = TestRequest::post()
+            .insert_header(bearer())
+            .uri("/books")
+            .set_json(json!({"title": "export_title", "isbn": isbn }))
+            .to_request();
+        let _: Value = test::call_and_read_body_json(&app, req).await;
+    }
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/export?title=export_title")
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to export books");
+    assert_eq!(
+        resp.headers().get("Content-Disposition").unwrap(),
+        "attachment; filename=\"books.csv\""
+    );
+    let body = test::read_body(resp).await;
+    let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().lines().collect();
+    assert_eq!(
+        lines[0],
+        "id,title,isbn,copies_available,copies,version,updated_at"
+    );
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains("9780000001047") && lines[2].contains("9780000001054"));
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/export?format=ndjson&isbn=9780000001054")
+        .send_request(&app)
+        .await;
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "application/x-ndjson"
+    );
+    let body = test::read_body(resp).await;
+    let book: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(book["isbn"], "9780000001054");
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/members/export?nickname=user")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Exported with an unknown filter");
+}
+
+#[actix_rt::test]
+async fn normalize_isbn() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "isbn_title", "isbn": "0-00-000106-6"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(book["isbn"], "9780000001061");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "isbn_title", "isbn": "978-0-00-000106-1"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Saved the same ISBN twice");
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "isbn_title", "isbn": "9780000001062"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Saved an ISBN with a wrong check digit");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?isbn=0000001066")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["Ok"][0]["id"], book["id"]);
+}
+
+#[actix_rt::test]
+async fn books_by_author_and_subject() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "related_title", "isbn": "9780000001078"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+
+    let mut ids = Vec::new();
+    for (uri, name) in [
+        ("/authors", "Ursula Related"),
+        ("/publishers", "Related Press"),
+        ("/subjects", "related fantasy"),
+    ] {
+        let req = TestRequest::post()
+            .insert_header(bearer())
+            .uri(uri)
+            .set_json(json!({ "name": name }))
+            .to_request();
+        let record: Value = test::call_and_read_body_json(&app, req).await;
+        ids.push(record["id"].clone());
+    }
+
+    for (relation, id) in ["authors", "publishers", "subjects"].iter().zip(&ids) {
+        let resp = TestRequest::put()
+            .insert_header(bearer())
+            .uri(&format!("/books/{}/{relation}", book["id"]))
+            .set_json(json!([id]))
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success(), "Failed to link {relation}");
+    }
+
+    let req = TestRequest::get()
+        .insert_header(bearer_for(auth::Role::Kiosk))
+        .uri("/books/filter?author__icontains=ursula%20rel&subject=related%20fantasy&include=authors,publisher")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    let found = &page["Ok"][0];
+    assert_eq!(found["id"], book["id"]);
+    assert_eq!(found["authors"][0]["name"], "Ursula Related");
+    assert_eq!(found["publishers"][0]["id"], ids[1]);
+    assert!(found.get("subjects").is_none());
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/books/filter?author_id={}", ids[0]))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["Ok"][0]["id"], book["id"]);
+
+    let resp = TestRequest::delete()
+        .insert_header(bearer())
+        .uri(&format!("/authors/{}", ids[0]))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to delete the author");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}?include=authors,subjects", book["id"]))
+        .to_request();
+    let found: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(found["authors"], json!([]));
+    assert_eq!(found["subjects"][0]["id"], ids[2]);
+
+    let resp = TestRequest::put()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}/authors", book["id"]))
+        .set_json(json!([ids[0]]))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 404, "Linked a deleted author");
+}
+
+#[actix_rt::test]
+async fn copies_follow_item_status() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "items_title", "isbn": "9780000001085"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(book["copies"], 0);
+
+    let mut items = Vec::new();
+    for barcode in ["items-1", "items-2", "items-3"] {
+        let req = TestRequest::post()
+            .insert_header(bearer())
+            .uri("/items")
+            .set_json(json!({"barcode": barcode, "book_id": book["id"], "shelf_location": "A1"}))
+            .to_request();
+        let item: Value = test::call_and_read_body_json(&app, req).await;
+        items.push(item);
+    }
+
+    let req = TestRequest::put()
+        .insert_header(bearer())
+        .uri(&format!("/items/{}", items[2]["id"]))
+        .set_json(json!({"barcode": "items-3", "book_id": book["id"], "status": "lost"}))
+        .to_request();
+    let _: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/loans")
+        .set_json(json!({"member_id": 1, "book_id": book["id"], "barcode": "items-2"}))
+        .to_request();
+    let loan: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(loan["item_id"], items[1]["id"]);
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}", book["id"]))
+        .to_request();
+    let found: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(found["copies"], 2);
+    assert_eq!(found["copies_available"], 1);
+
+    let resp = TestRequest::put()
+        .insert_header(bearer())
+        .uri(&format!("/items/{}", items[1]["id"]))
+        .set_json(json!({"barcode": "items-2", "book_id": book["id"], "status": "available"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Shelved a copy that is on loan");
+
+    let resp = TestRequest::delete()
+        .insert_header(bearer())
+        .uri(&format!("/items/{}", items[1]["id"]))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Deleted a copy that is on loan");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/items?book_id={}&status=on_loan", book["id"]))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["Ok"][0]["barcode"], "items-2");
+}
+
+#[actix_rt::test]
+async fn audit_member_changes() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/members")
+        .set_json(json!({"first_name": "audited", "last_name": "member", "email": "audited@gg.com", "address": "elm street", "age": 40}))
+        .to_request();
+    let member: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = TestRequest::put()
+        .insert_header(bearer())
+        .insert_header(("If-Match", format!("\"{}\"", member["version"])))
+        .uri(&format!("/members/{}", member["id"]))
+        .set_json(json!({"first_name": "audited", "last_name": "member", "email": "audited@gg.org", "address": "elm street", "age": 40}))
+        .to_request();
+    let _: Value = test::call_and_read_body_json(&app, req).await;
+
+    let resp = TestRequest::delete()
+        .insert_header(bearer())
+        .uri(&format!("/members/{}", member["id"]))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to delete the member");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/audit?entity=members&id={}", member["id"]))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 3);
+    let entries = page["Ok"].as_array().unwrap();
+    let actions: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["delete", "update", "create"]);
+    assert_eq!(entries[0]["actor"], "tests");
+    assert_eq!(entries[0]["before"]["email"], "audited@gg.org");
+    assert_eq!(entries[1]["before"]["email"], "audited@gg.com");
+    assert_eq!(entries[1]["after"]["email"], "audited@gg.org");
+    assert!(entries[1]["after"].get("first_name").is_none());
+    assert_eq!(entries[2]["after"]["first_name"], "audited");
+
+    let resp = TestRequest::get()
+        .insert_header(bearer_for(auth::Role::Librarian))
+        .uri("/audit")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 403, "A librarian read the audit log");
+}
+
+#[actix_rt::test]
+async fn soft_delete_and_restore_book() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let mut ids = Vec::new();
+    for isbn in ["9780000001092", "9780000001108"] {
+        let req = TestRequest::post()
+            .insert_header(bearer())
+            .uri("/books")
+            .set_json(json!({"title": "soft_deleted_title", "isbn": isbn}))
+            .to_request();
+        let book: Value = test::call_and_read_body_json(&app, req).await;
+        let resp = TestRequest::delete()
+            .insert_header(bearer())
+            .uri(&format!("/books/{}", book["id"]))
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success(), "Failed to delete the book");
+        ids.push(book["id"].clone());
+    }
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}", ids[0]))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 404, "Found a deleted book");
+
+    let resp = TestRequest::get()
+        .insert_header(bearer_for(auth::Role::Librarian))
+        .uri("/books/filter?title=soft_deleted_title&include_deleted=true")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 403, "A librarian listed deleted books");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?title=soft_deleted_title&include_deleted=true")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 2);
+    assert!(page["Ok"][0]["deleted_at"].is_string());
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}/restore", ids[0]))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(book.get("deleted_at").is_none());
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}/restore", ids[0]))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Restored a book that is not deleted");
+
+    books::Books::purge(Utc::now().naive_utc()).await.unwrap();
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?title=soft_deleted_title&include_deleted=true")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["Ok"][0]["id"], ids[0]);
+}
+
+#[actix_rt::test]
+async fn reject_stale_book_updates() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books")
+        .set_json(json!({"title": "versioned_title", "isbn": "9780000001115"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}", book["id"]))
+        .send_request(&app)
+        .await;
+    let etag = resp
+        .headers()
+        .get("ETag")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(etag, "\"1\"");
+
+    let resp = TestRequest::put()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}", book["id"]))
+        .set_json(json!({"title": "versioned_title_2", "isbn": "9780000001115"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 428, "Updated a book without If-Match");
+
+    let resp = TestRequest::put()
+        .insert_header(bearer())
+        .insert_header(("If-Match", etag.as_str()))
+        .uri(&format!("/books/{}", book["id"]))
+        .set_json(json!({"title": "versioned_title_2", "isbn": "9780000001115"}))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to update the book");
+    assert_eq!(resp.headers().get("ETag").unwrap(), "\"2\"");
+
+    let resp = TestRequest::put()
+        .insert_header(bearer())
+        .insert_header(("If-Match", etag.as_str()))
+        .uri(&format!("/books/{}", book["id"]))
+        .set_json(json!({"title": "versioned_title_3", "isbn": "9780000001115"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 412, "Overwrote a newer version of the book");
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri(&format!("/books/{}", book["id"]))
+        .to_request();
+    let found: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(found["title"], "versioned_title_2");
+    assert_eq!(found["version"], 2);
+}
+
+#[actix_rt::test]
+async fn patch_member_fields() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/members")
+        .set_json(json!({"first_name": "patched", "last_name": "member", "email": "patched@gg.com", "address": "elm street", "age": 20}))
+        .to_request();
+    let member: Value = test::call_and_read_body_json(&app, req).await;
+    let uri = format!("/members/{}", member["id"]);
+
+    let req = TestRequest::patch()
+        .insert_header(bearer())
+        .insert_header(("If-Match", "\"1\""))
+        .insert_header(("Content-Type", "application/merge-patch+json"))
+        .uri(&uri)
+        .set_payload(r#"{"age": 21}"#)
+        .to_request();
+    let patched: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(patched["age"], 21);
+    assert_eq!(patched["email"], "patched@gg.com");
+    assert_eq!(patched["version"], 2);
+
+    let req = TestRequest::patch()
+        .insert_header(bearer())
+        .insert_header(("If-Match", "\"2\""))
+        .insert_header(("Content-Type", "application/json-patch+json"))
+        .uri(&uri)
+        .set_payload(r#"[{"op": "test", "path": "/age", "value": 21}, {"op": "replace", "path": "/address", "value": "oak street"}]"#)
+        .to_request();
+    let patched: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(patched["address"], "oak street");
+    assert_eq!(patched["version"], 3);
+
+    let resp = TestRequest::patch()
+        .insert_header(bearer())
+        .insert_header(("If-Match", "\"3\""))
+        .insert_header(("Content-Type", "application/json-patch+json"))
+        .uri(&uri)
+        .set_payload(
+            r#"[{"op": "test", "path": "/age", "value": 99}, {"op": "remove", "path": "/age"}]"#,
+        )
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Applied a patch whose test failed");
+
+    let resp = TestRequest::patch()
+        .insert_header(bearer())
+        .insert_header(("If-Match", "\"3\""))
+        .insert_header(("Content-Type", "application/merge-patch+json"))
+        .uri(&uri)
+        .set_payload(r#"{"age": "old"}"#)
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 422, "Saved an age that is not a number");
+
+    let resp = TestRequest::patch()
+        .insert_header(bearer())
+        .insert_header(("If-Match", "\"3\""))
+        .insert_header(("Content-Type", "application/json-patch+json"))
+        .uri(&uri)
+        .set_payload(r#"[{"op": "replace", "path": "/email", "value": "x"}]"#)
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Saved an invalid email");
+
+    let resp = TestRequest::patch()
+        .insert_header(bearer())
+        .insert_header(("If-Match", "\"3\""))
+        .insert_header(("Content-Type", "application/merge-patch+json"))
+        .uri(&uri)
+        .set_payload(r#"{"age": 999}"#)
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 400, "Saved an age of 999");
+
+    let resp = TestRequest::patch()
+        .insert_header(bearer())
+        .insert_header(("If-Match", "\"3\""))
+        .uri(&uri)
+        .set_json(json!({"age": 22}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 415, "Applied a patch sent as plain JSON");
+}
+
+#[actix_rt::test]
+async fn batch_books_atomic_and_best_effort() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let operations = json!([
+        {"op": "create", "data": {"title": "batch_title", "isbn": "9780000001122"}},
+        {"op": "create", "data": {"title": "batch_title", "isbn": "9780000001123"}},
+        {"op": "update", "id": 1, "version": 999, "data": {"title": "title_1", "isbn": "9780000001122"}},
+        {"op": "update", "id": 1, "data": {"title": "title_1", "isbn": "9780000001122"}},
+        {"op": "remove", "id": 1}
+    ]);
+
+    let resp = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books/batch")
+        .set_json(json!({"operations": operations}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 422, "Saved an atomic batch with failures");
+    let report: Value = test::read_body_json(resp).await;
+    assert_eq!(report["committed"], false);
+    assert_eq!(report["succeeded"], 1);
+    let statuses: Vec<&Value> = report["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| &result["status"])
+        .collect();
+    assert_eq!(statuses, [200, 400, 412, 428, 400]);
+    assert_eq!(
+        report["results"][1]["Err"],
+        "isbn '9780000001123' has a wrong check digit"
+    );
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?title=batch_title")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 0);
+
+    let req = TestRequest::post()
+        .insert_header(bearer())
+        .uri("/books/batch")
+        .set_json(json!({"mode": "best_effort", "operations": operations}))
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["committed"], true);
+    assert_eq!(report["failed"], 4);
+    let id = report["results"][0]["Ok"]["id"].clone();
+
+    let req = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/filter?title=batch_title")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["Ok"][0]["id"], id);
+}
+
+#[actix_rt::test]
+async fn books_and_members_on_sqlite() {
+    dotenv().ok();
+    let repositories = Repositories::sqlite(":memory:").unwrap();
+    let admin = config::AuthConfig {
+        admin_username: Some("sqlite_admin".to_string()),
+        admin_password: Some("s3cret".to_string()),
+        ..settings().auth.clone()
+    };
+    auth::Users::bootstrap(repositories.users.as_ref(), &admin)
+        .await
+        .unwrap();
+    let app = test::init_service(App::new().wrap(auth::Authentication).configure(|config| {
+        repositories.init(config);
+        auth::init_routes(config);
+        books::init_routes(config);
+        members::init_routes(config);
+    }))
+    .await;
+
+    let resp = TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({"username": "sqlite_admin", "password": "wrong"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 401, "Logged in with a wrong password");
+    let req = TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({"username": "sqlite_admin", "password": "s3cret"}))
+        .to_request();
+    let token: Value = test::call_and_read_body_json(&app, req).await;
+    let bearer = (
+        "Authorization",
+        format!("Bearer {}", token["token"].as_str().unwrap()),
+    );
+
+    let req = TestRequest::post()
+        .insert_header(bearer.clone())
+        .uri("/books")
+        .set_json(json!({"title": "sqlite_title", "isbn": "9780000001139"}))
+        .to_request();
+    let book: Value = test::call_and_read_body_json(&app, req).await;
+    let uri = format!("/books/{}", book["id"]);
+
+    let resp = TestRequest::get()
+        .insert_header(bearer.clone())
+        .uri(&uri)
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.headers().get("ETag").unwrap(), "\"1\"");
+
+    let resp = TestRequest::put()
+        .insert_header(bearer.clone())
+        .insert_header(("If-Match", "\"1\""))
+        .uri(&uri)
+        .set_json(json!({"title": "sqlite_title_2", "isbn": "9780000001139"}))
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to update the book");
+    assert_eq!(resp.headers().get("ETag").unwrap(), "\"2\"");
+
+    let resp = TestRequest::put()
+        .insert_header(bearer.clone())
+        .insert_header(("If-Match", "\"1\""))
+        .uri(&uri)
+        .set_json(json!({"title": "sqlite_title_3", "isbn": "9780000001139"}))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 412, "Overwrote a newer version of the book");
+
+    let req = TestRequest::get()
+        .insert_header(bearer.clone())
+        .uri("/books/filter?title__contains=title_2")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["Ok"][0]["title"], "sqlite_title_2");
+
+    let resp = TestRequest::delete()
+        .insert_header(bearer.clone())
+        .uri(&uri)
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to delete the book");
+    let resp = TestRequest::get()
+        .insert_header(bearer.clone())
+        .uri(&uri)
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 404, "Found a deleted book");
+
+    let req = TestRequest::post()
+        .insert_header(bearer.clone())
+        .uri(&format!("{uri}/restore"))
+        .to_request();
+    let restored: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(restored["id"], book["id"]);
+    let resp = TestRequest::post()
+        .insert_header(bearer.clone())
+        .uri(&format!("{uri}/restore"))
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 409, "Restored a book that is not deleted");
+
+    let req = TestRequest::post()
+        .insert_header(bearer.clone())
+        .uri("/members")
+        .set_json(json!({"first_name": "sqlite", "last_name": "member", "email": "sqlite@gg.com", "address": "elm street", "age": 30}))
+        .to_request();
+    let member: Value = test::call_and_read_body_json(&app, req).await;
+    let req = TestRequest::get()
+        .insert_header(bearer.clone())
+        .uri("/members/filter?age__gte=30")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["Ok"][0]["id"], member["id"]);
+}
+
+#[actix_rt::test]
+async fn layer_config_sources() {
+    let dir = env::temp_dir().join(format!("config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("api.toml");
+    std::fs::write(
+        &file,
+        "[server]\nport = 7000\nworkers = 2\n\n[database]\nurl = \"postgres://file/library\"\npool_size = 4\n\n[features]\nswagger_ui = false\n",
+    )
+    .unwrap();
+    let file = file.to_str().unwrap().to_string();
+
+    let var = |name: &str| match name {
+        "CONFIG_FILE" => Some(file.clone()),
+        "JWT_SECRET" => Some("secret".to_string()),
+        "PORT" => Some("7001".to_string()),
+        "DATABASE_POOL_SIZE" => Some("6".to_string()),
+        _ => None,
+    };
+    let args: Vec<String> = ["--port", "7002", "--log-level=debug"]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let config = Config::load_with(&args, var).unwrap();
+    assert_eq!(config.server.port, 7002);
+    assert_eq!(config.server.workers, 2);
+    assert_eq!(config.server.host, "127.0.0.1");
+    assert_eq!(config.database.url, "postgres://file/library");
+    assert_eq!(config.database.pool_size, 6);
+    assert_eq!(config.log.level, "debug");
+    assert!(!config.features.swagger_ui);
+    assert!(config.features.purge);
+
+    let var = |name: &str| match name {
+        "CONFIG_FILE" => Some(file.clone()),
+        "PORT" => Some("http".to_string()),
+        "FINE_CAP_CENTS" => Some("-5".to_string()),
+        _ => None,
+    };
+    let args: Vec<String> = ["--pool", "4"].iter().map(ToString::to_string).collect();
+    let errors = Config::load_with(&args, var).unwrap_err().0;
+    assert_eq!(
+        errors,
+        [
+            "PORT: 'http' is not a port",
+            "--pool: unknown flag",
+            "auth.jwt_secret is not set, set JWT_SECRET",
+            "fines must be non negative amounts of cents",
+        ]
+    );
+
+    std::fs::write(dir.join("api.toml"), "[server]\nhots = \"0.0.0.0\"\n").unwrap();
+    let var = |name: &str| match name {
+        "CONFIG_FILE" => Some(file.clone()),
+        _ => None,
+    };
+    let errors = Config::load_with(&[], var).unwrap_err().0;
+    assert!(errors[0].contains("unknown field `hots`"), "{}", errors[0]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[actix_rt::test]
+async fn probe_health_without_token() {
+    dotenv().ok();
+    let app =
+        test::init_service(App::new().wrap(auth::Authentication).configure(init_routes)).await;
+
+    let req = TestRequest::get().uri("/health").to_request();
+    let health: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
+    assert!(health["git_sha"].is_string());
+    assert!(health["uptime_seconds"].is_u64());
+
+    let resp = TestRequest::get()
+        .uri("/health/live")
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed the liveness probe");
+
+    let resp = TestRequest::get()
+        .uri("/health/ready")
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed the readiness probe");
+    let readiness: Value = test::read_body_json(resp).await;
+    assert_eq!(readiness["status"], "ready");
+    assert_eq!(readiness["database"], "ok");
+    assert_eq!(readiness["pending_migrations"], json!([]));
+    assert_eq!(readiness["pools"][0]["name"], "blocking");
+}
+
+#[actix_rt::test]
+async fn expose_prometheus_metrics() {
+    dotenv().ok();
+    let app = test::init_service(
+        App::new()
+            .wrap(auth::Authentication)
+            .wrap(metrics::Metrics)
+            .configure(init_routes),
+    )
+    .await;
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/1")
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to get the book");
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/members/999999")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 404);
+    let resp = TestRequest::get()
+        .uri("/no/such/route")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 401);
+
+    let resp = TestRequest::get().uri("/metrics").send_request(&app).await;
+    assert!(resp.status().is_success(), "Failed to get the metrics");
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "text/plain; version=0.0.4"
+    );
+    let body = test::read_body(resp).await;
+    let body = std::str::from_utf8(&body).unwrap();
+    for line in [
+        r#"http_requests_total{method="GET",route="/books/{id}",status="200"}"#,
+        r#"http_requests_total{method="GET",route="/members/{id}",status="404"}"#,
+        r#"http_requests_total{method="GET",route="unmatched",status="401"}"#,
+        r#"http_request_duration_seconds_bucket{method="GET",route="/books/{id}",le="0.005"}"#,
+        r#"api_errors_total{status="404"}"#,
+        "db_pool_idle_connections ",
+        "db_pool_active_connections ",
+        r#"db_async_pool_idle_connections{worker="#,
+        r#"db_async_pool_active_connections{worker="#,
+    ] {
+        assert!(body.contains(line), "Missing {line} in the metrics");
+    }
+}
+
+#[actix_rt::test]
+async fn correlate_requests_with_an_id() {
+    dotenv().ok();
+    let app = test::init_service(
+        App::new()
+            .wrap(auth::Authentication)
+            .wrap(telemetry::RequestTracing)
+            .configure(init_routes),
+    )
+    .await;
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .insert_header(("X-Request-Id", "client-chosen-id"))
+        .uri("/books/1")
+        .send_request(&app)
+        .await;
+    assert!(resp.status().is_success(), "Failed to get the book");
+    assert_eq!(
+        resp.headers().get("X-Request-Id").unwrap(),
+        "client-chosen-id"
+    );
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .uri("/books/1")
+        .send_request(&app)
+        .await;
+    let generated = resp
+        .headers()
+        .get("X-Request-Id")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(
+        uuid::Uuid::parse_str(generated).is_ok(),
+        "Failed to generate an id"
+    );
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .insert_header(("X-Request-Id", "x".repeat(200)))
+        .uri("/books/1")
+        .send_request(&app)
+        .await;
+    let replaced = resp
+        .headers()
+        .get("X-Request-Id")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(
+        uuid::Uuid::parse_str(replaced).is_ok(),
+        "Failed to replace a long id"
+    );
+
+    let resp = TestRequest::get()
+        .insert_header(bearer())
+        .insert_header(("X-Request-Id", "missing-member"))
+        .uri("/members/999999")
+        .send_request(&app)
+        .await;
+    assert_eq!(resp.status(), 404);
+    assert_eq!(
+        resp.headers().get("X-Request-Id").unwrap(),
+        "missing-member"
+    );
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["request_id"], "missing-member");
+}